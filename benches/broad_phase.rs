@@ -0,0 +1,67 @@
+// Compares the spatial hash broad phase against checking every pair of bodies.
+// Run with `cargo bench --bench broad_phase`.
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use Game2DEngine::collision::*;
+use Game2DEngine::types::*;
+
+const FRAMES: u32 = 20;
+
+// The old gather_contacts loop: every body against every later one
+fn brute_force(positions: &[Vec2i], sizes: &[(usize, usize)]) -> usize {
+    let mut touching = 0;
+    for a in 0..positions.len() {
+        for b in a + 1..positions.len() {
+            let ar = Rect::from_pos_size(positions[a], sizes[a]);
+            let br = Rect::from_pos_size(positions[b], sizes[b]);
+            if contact_normal(ar, br).is_some() {
+                touching += 1;
+            }
+        }
+    }
+    touching
+}
+
+fn hashed(positions: &[Vec2i], sizes: &[(usize, usize)]) -> usize {
+    broad_phase(positions, sizes)
+        .into_iter()
+        .filter(|(a, b)| {
+            let ar = Rect::from_pos_size(positions[*a], sizes[*a]);
+            let br = Rect::from_pos_size(positions[*b], sizes[*b]);
+            contact_normal(ar, br).is_some()
+        })
+        .count()
+}
+
+// Average time per frame
+fn time(f: impl Fn() -> usize) -> (Duration, usize) {
+    let start = Instant::now();
+    let mut found = 0;
+    for _ in 0..FRAMES {
+        found = f();
+    }
+    (start.elapsed() / FRAMES, found)
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(39);
+    println!(
+        "{:>6} {:>14} {:>14} {:>8}",
+        "bodies", "brute force", "spatial hash", "touching"
+    );
+    for &n in &[10, 100, 1000, 5000] {
+        // Player-sized bodies spread over a level with about as much room per body as a busy game
+        let side = ((n * 64 * 64) as f64).sqrt() as i32;
+        let positions: Vec<Vec2i> = (0..n)
+            .map(|_| Vec2i(rng.gen_range(0..side), rng.gen_range(0..side)))
+            .collect();
+        let sizes = vec![(32, 32); n];
+        let (brute, expected) = time(|| brute_force(&positions, &sizes));
+        let (hash, found) = time(|| hashed(&positions, &sizes));
+        assert_eq!(found, expected);
+        println!("{:>6} {:>14?} {:>14?} {:>8}", n, brute, hash, found);
+    }
+}
@@ -0,0 +1,301 @@
+// Enemies and the behaviors that drive them
+use crate::animation::Animation;
+use crate::collision::{
+    sweep_tiles, tiles_overlapping, Body, LAYER_ALL, LAYER_ENEMY, LAYER_PLAYER,
+};
+use crate::components::*;
+use crate::ecs::{Entity, World};
+use crate::resources::Handle;
+use crate::texture::Texture;
+use crate::tiles::*;
+use crate::types::*;
+use std::rc::Rc;
+
+// How much faster enemies fall each frame, and how fast they can fall
+const GRAVITY: f32 = 1.0;
+const MAX_FALL: f32 = 8.0;
+
+/// What an enemy knows about the world when deciding how to move.
+/// Positions are snapped to whole pixels.
+pub struct Senses<'a> {
+    pub pos: Vec2i,
+    pub size: (usize, usize),
+    pub player: Vec2i,
+    pub maps: &'a [Tilemap],
+    pub frame: usize,
+}
+
+impl<'a> Senses<'a> {
+    pub fn rect(&self) -> Rect {
+        Rect::from_pos_size(self.pos, self.size)
+    }
+    // Standing on something solid?
+    pub fn grounded(&self) -> bool {
+        let r = self.rect();
+        solid_at(self.maps, Vec2i(r.x, r.bottom()))
+            || solid_at(self.maps, Vec2i(r.right() - 1, r.bottom()))
+    }
+    // Would stepping dx pixels sideways walk into a solid tile?
+    pub fn wall_ahead(&self, dx: i32) -> bool {
+        solid_at(
+            self.maps,
+            Vec2i(self.leading_edge(dx), self.rect().center().1),
+        )
+    }
+    // Would stepping dx pixels sideways leave nothing underfoot?
+    pub fn ledge_ahead(&self, dx: i32) -> bool {
+        !solid_at(
+            self.maps,
+            Vec2i(self.leading_edge(dx), self.rect().bottom()),
+        )
+    }
+    // The column we'd step into moving dx pixels sideways
+    fn leading_edge(&self, dx: i32) -> i32 {
+        let r = self.rect();
+        if dx > 0 {
+            r.right() - 1 + dx
+        } else {
+            r.x + dx
+        }
+    }
+}
+
+/// Decides an enemy's velocity each frame.
+/// Implement this to add new kinds of enemies.
+pub trait Behavior: std::fmt::Debug {
+    fn think(&mut self, senses: &Senses, vel: &mut Vec2f);
+    // Whether gravity pulls this enemy down
+    fn falls(&self) -> bool {
+        true
+    }
+}
+
+/// An enemy's behavior, as a component
+#[derive(Debug)]
+pub struct Brain(pub Box<dyn Behavior>);
+
+/// Marks entities that hurt the player
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Enemy;
+
+/// Walks back and forth, turning around at walls and at the edges of ledges
+#[derive(Debug)]
+pub struct Patrol {
+    pub speed: f32,
+    dir: i32,
+}
+
+impl Patrol {
+    pub fn new(speed: f32) -> Self {
+        Self { speed, dir: 1 }
+    }
+}
+
+impl Behavior for Patrol {
+    fn think(&mut self, senses: &Senses, vel: &mut Vec2f) {
+        let step = self.dir * self.speed.ceil() as i32;
+        if senses.wall_ahead(step) || (senses.grounded() && senses.ledge_ahead(step)) {
+            self.dir = -self.dir;
+        }
+        vel.0 = self.dir as f32 * self.speed;
+    }
+}
+
+/// Runs towards the player once they're within range, otherwise stands still
+#[derive(Debug)]
+pub struct Chase {
+    pub speed: f32,
+    pub range: f32,
+}
+
+impl Behavior for Chase {
+    fn think(&mut self, senses: &Senses, vel: &mut Vec2f) {
+        let to_player = Vec2f::from(senses.player) - Vec2f::from(senses.pos);
+        vel.0 = if to_player.length() <= self.range {
+            to_player.0.signum() * self.speed.min(to_player.0.abs())
+        } else {
+            0.0
+        };
+    }
+}
+
+/// Patrols, but jumps instead of walking off ledges
+#[derive(Debug)]
+pub struct Jumper {
+    pub speed: f32,
+    pub jump: f32,
+    dir: i32,
+}
+
+impl Jumper {
+    pub fn new(speed: f32, jump: f32) -> Self {
+        Self {
+            speed,
+            jump,
+            dir: 1,
+        }
+    }
+}
+
+impl Behavior for Jumper {
+    fn think(&mut self, senses: &Senses, vel: &mut Vec2f) {
+        let step = self.dir * self.speed.ceil() as i32;
+        if senses.wall_ahead(step) {
+            self.dir = -self.dir;
+        }
+        if senses.grounded() && senses.ledge_ahead(step) {
+            vel.1 = -self.jump;
+        }
+        vel.0 = self.dir as f32 * self.speed;
+    }
+}
+
+/// Flies back and forth between walls, bobbing up and down in a sine wave
+#[derive(Debug)]
+pub struct Flyer {
+    pub speed: f32,
+    pub amplitude: f32,
+    // Frames per bob
+    pub period: usize,
+    dir: i32,
+}
+
+impl Flyer {
+    pub fn new(speed: f32, amplitude: f32, period: usize) -> Self {
+        Self {
+            speed,
+            amplitude,
+            period: period.max(1),
+            dir: 1,
+        }
+    }
+}
+
+impl Behavior for Flyer {
+    fn think(&mut self, senses: &Senses, vel: &mut Vec2f) {
+        if senses.wall_ahead(self.dir * self.speed.ceil() as i32) {
+            self.dir = -self.dir;
+        }
+        vel.0 = self.dir as f32 * self.speed;
+        // Moving along the wave's slope keeps the flyer centered on the height it started at
+        let phase = (senses.frame % self.period) as f32 / self.period as f32;
+        let w = std::f32::consts::TAU / self.period as f32;
+        vel.1 = self.amplitude * w * (phase * std::f32::consts::TAU).cos();
+    }
+    fn falls(&self) -> bool {
+        false
+    }
+}
+
+impl EnemyKind {
+    pub fn behavior(self) -> Box<dyn Behavior> {
+        match self {
+            EnemyKind::Patroller => Box::new(Patrol::new(1.5)),
+            EnemyKind::Chaser => Box::new(Chase {
+                speed: 2.5,
+                range: 256.0,
+            }),
+            EnemyKind::Jumper => Box::new(Jumper::new(2.0, 12.0)),
+            EnemyKind::Flyer => Box::new(Flyer::new(1.5, 32.0, 120)),
+        }
+    }
+}
+
+/// Spawns the enemies from a level's entity list; positions are in tiles
+pub fn spawn_enemies(
+    world: &mut World,
+    entities: &[(EntityType, i32, i32)],
+    texture: &Handle<Texture>,
+    anim: &Rc<Animation>,
+) {
+    for (etype, x, y) in entities.iter() {
+        if let EntityType::Enemy(kind) = etype {
+            let e = world.spawn();
+            world.insert(e, Position((Vec2i(*x, *y) * TILE_SZ as i32).into()));
+            world.insert(e, Velocity(Vec2f(0.0, 0.0)));
+            world.insert(e, Size(32, 32));
+            world.insert(
+                e,
+                Sprite {
+                    texture: texture.clone(),
+                    anim: anim.start(),
+                },
+            );
+            // Enemies bump into each other but overlap the player, which is what hurts them
+            world.insert(
+                e,
+                Body {
+                    layers: LAYER_ENEMY,
+                    mask: LAYER_ALL & !LAYER_PLAYER,
+                    ..Body::default()
+                },
+            );
+            world.insert(e, Brain(kind.behavior()));
+            world.insert(e, Enemy);
+        }
+    }
+}
+
+pub fn despawn_enemies(world: &mut World) {
+    let enemies: Vec<Entity> = world.query::<Enemy>().map(|(e, _)| e).collect();
+    for e in enemies {
+        world.despawn(e);
+    }
+}
+
+/// Lets every enemy decide where to go, then moves them, swept against the tiles
+/// like the player is. Bumping into other bodies happens afterwards along with everything else.
+pub fn update_enemies(world: &mut World, maps: &[Tilemap], player: Vec2i, frame: usize) {
+    let bodies: Vec<(Entity, Vec2f, (usize, usize))> = world
+        .query2::<Brain, Position>()
+        .filter_map(|(e, _, pos)| world.get::<Size>(e).map(|s| (e, pos.0, (s.0, s.1))))
+        .collect();
+    for (e, pos, size) in bodies {
+        let senses = Senses {
+            pos: pos.snap(),
+            size,
+            player,
+            maps,
+            frame,
+        };
+        let mut vel = world.get::<Velocity>(e).map(|v| v.0).unwrap_or_default();
+        let brain = world.get_mut::<Brain>(e).unwrap();
+        brain.0.think(&senses, &mut vel);
+        if brain.0.falls() {
+            vel.1 = (vel.1 + GRAVITY).min(MAX_FALL);
+        }
+        let wanted = pos + vel;
+        let swept = sweep_tiles(pos.snap(), size, wanted.snap() - pos.snap(), maps);
+        let mut pos = wanted;
+        if swept.blocked_x() {
+            pos.0 = swept.pos.0 as f32;
+        }
+        if swept.blocked_y() {
+            // Landed, or bumped a ceiling
+            pos.1 = swept.pos.1 as f32;
+            vel.1 = 0.0;
+        }
+        world.insert(e, Velocity(vel));
+        world.insert(e, Position(pos));
+    }
+}
+
+/// Is any enemy overlapping this rectangle?
+pub fn enemy_touching(world: &World, r: Rect) -> bool {
+    world.query2::<Enemy, Position>().any(|(e, _, pos)| {
+        world
+            .get::<Size>(e)
+            .is_some_and(|size| Rect::from_pos_size(pos.0.snap(), (size.0, size.1)).intersects(r))
+    })
+}
+
+// Is there a solid bit of tile under this point in any of the maps?
+fn solid_at(maps: &[Tilemap], p: Vec2i) -> bool {
+    let pixel = Rect {
+        x: p.0,
+        y: p.1,
+        w: 1,
+        h: 1,
+    };
+    tiles_overlapping(maps, pixel).any(|(t, r)| t.blocks(p - r.pos()))
+}
@@ -1,4 +1,4 @@
-use crate::types::Rect;
+use crate::types::{Rect, Vec2i};
 use std::rc::Rc;
 #[derive(Debug)]
 pub struct Animation {
@@ -21,6 +21,13 @@ impl Animation {
     pub fn freeze(r: Rect) -> Self {
         Self::new(vec![(r, 0)], false)
     }
+    /// The same animation with every frame shifted over, e.g. for a sheet packed into an atlas
+    pub fn moved(&self, by: Vec2i) -> Self {
+        Self::new(
+            self.frames.iter().map(|(r, t)| (r.translate(by), *t)),
+            self.looping,
+        )
+    }
     // Should hold some data...
     // Be used to decide what frame to use...
     // And sprites can be updated based on that information.
@@ -38,7 +45,7 @@ impl Animation {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AnimationState {
     animation: Rc<Animation>,
     time: usize,
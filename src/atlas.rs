@@ -0,0 +1,313 @@
+// Lots of little pictures, like sprite sheets, text glyphs and icons, packed into one texture.
+// An AtlasBuilder collects pictures by name and packs them in rows, tallest first; the Atlas
+// it builds has the texture and where each picture ended up in it. Atlases can also be saved
+// as a PNG with a table of names next to it, which is how the atlas tool makes them ahead of time.
+use crate::animation::Animation;
+use crate::error::EngineError;
+use crate::graphics::Screen;
+use crate::resources::Handle;
+use crate::texture::{open_image, Texture};
+use crate::types::{Rect, Rgba, Vec2i};
+use image::{imageops, Rgba as Pixel, RgbaImage};
+use rusttype::{point, Font, Scale};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Atlases are packed this wide unless there's something wider to fit in
+const DEFAULT_WIDTH: u32 = 512;
+
+/// Pictures waiting to be packed into an atlas
+pub struct AtlasBuilder {
+    images: Vec<(String, RgbaImage)>,
+    /// How wide to make the atlas; it's as tall as it needs to be
+    pub width: u32,
+    /// Empty pixels around each picture
+    pub padding: u32,
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        Self {
+            images: vec![],
+            width: DEFAULT_WIDTH,
+            padding: 1,
+        }
+    }
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds a picture under name, replacing any picture already called that
+    pub fn add(&mut self, name: impl Into<String>, image: RgbaImage) -> &mut Self {
+        let name = name.into();
+        self.images.retain(|(n, _)| *n != name);
+        self.images.push((name, image));
+        self
+    }
+    pub fn add_file(
+        &mut self,
+        name: impl Into<String>,
+        path: &Path,
+    ) -> Result<&mut Self, EngineError> {
+        let image = open_image(path)?;
+        Ok(self.add(name, image))
+    }
+    /// Adds every PNG in a directory, each named after its file without the extension
+    pub fn add_dir(&mut self, dir: &Path) -> Result<&mut Self, EngineError> {
+        let entries = fs::read_dir(dir).map_err(|err| EngineError::from_io(dir, err))?;
+        let mut paths = vec![];
+        for entry in entries {
+            let path = entry.map_err(|err| EngineError::from_io(dir, err))?.path();
+            if path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+            {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        for path in paths {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            self.add_file(name.into_owned(), &path)?;
+        }
+        Ok(self)
+    }
+    /// Adds a line of text as one picture, just big enough to hold it
+    pub fn add_text(
+        &mut self,
+        name: impl Into<String>,
+        text: &str,
+        font: &Font,
+        scale: f32,
+        color: Rgba,
+    ) -> &mut Self {
+        self.add(name, render_text(text, font, scale, color))
+    }
+    /// Adds a picture of each character for Atlas::draw_text, named by glyph_name.
+    /// Each is as wide as the character moves the pen along and as tall as a line,
+    /// so they line up just by putting them side by side.
+    pub fn add_glyphs(
+        &mut self,
+        font_name: &str,
+        font: &Font,
+        scale: f32,
+        chars: &str,
+        color: Rgba,
+    ) -> &mut Self {
+        for c in chars.chars() {
+            let image = render_text(c.encode_utf8(&mut [0; 4]), font, scale, color);
+            self.add(glyph_name(font_name, c), image);
+        }
+        self
+    }
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+    /// Packs every picture into one texture
+    pub fn build(&self) -> Atlas {
+        let pad = self.padding;
+        // Tallest first, so rows waste as little height as they can; names break ties so
+        // the same pictures always pack the same way
+        let mut order: Vec<&(String, RgbaImage)> = self.images.iter().collect();
+        order.sort_by(|a, b| b.1.height().cmp(&a.1.height()).then(a.0.cmp(&b.0)));
+        let widest = order.iter().map(|(_, image)| image.width()).max();
+        let width = self.width.max(widest.unwrap_or(0) + 2 * pad);
+
+        let mut rects = HashMap::new();
+        let (mut x, mut y, mut row) = (pad, pad, 0);
+        for (name, image) in order.iter() {
+            let (w, h) = image.dimensions();
+            if x > pad && x + w + pad > width {
+                x = pad;
+                y += row + pad;
+                row = 0;
+            }
+            let r = Rect {
+                x: x as i32,
+                y: y as i32,
+                w: w as u16,
+                h: h as u16,
+            };
+            rects.insert(name.clone(), r);
+            x += w + pad;
+            row = row.max(h);
+        }
+        let mut image = RgbaImage::new(width, y + row + pad);
+        for (name, picture) in order {
+            let r = rects[name];
+            imageops::replace(&mut image, picture, r.x as u32, r.y as u32);
+        }
+        Atlas::new(Path::new("atlas"), image, rects)
+    }
+}
+
+/// Lots of pictures in one texture, found by name
+pub struct Atlas {
+    texture: Handle<Texture>,
+    // Straight out of the builder or the file, not premultiplied, for saving
+    image: RgbaImage,
+    rects: HashMap<String, Rect>,
+}
+
+impl Atlas {
+    fn new(key: &Path, image: RgbaImage, rects: HashMap<String, Rect>) -> Self {
+        Self {
+            texture: Handle::new(key, Texture::new(image.clone())),
+            image,
+            rects,
+        }
+    }
+    /// Reads an atlas that was saved: the PNG at path, and the table next to it
+    pub fn with_file(path: &Path) -> Result<Self, EngineError> {
+        let image = open_image(path)?;
+        let table_path = table_path(path);
+        let table = fs::read_to_string(&table_path)
+            .map_err(|err| EngineError::from_io(&table_path, err))?;
+        let too_big = |_| EngineError::decode_failed(path, "too big for an atlas");
+        let bounds = Rect {
+            x: 0,
+            y: 0,
+            w: u16::try_from(image.width()).map_err(too_big)?,
+            h: u16::try_from(image.height()).map_err(too_big)?,
+        };
+        let mut rects = HashMap::new();
+        for line in table.lines().filter(|line| !line.is_empty()) {
+            let bad = || EngineError::decode_failed(&table_path, format!("bad line {:?}", line));
+            let mut fields = line.splitn(5, ' ');
+            let mut number = || {
+                fields
+                    .next()
+                    .and_then(|f| f.parse::<i32>().ok())
+                    .ok_or_else(bad)
+            };
+            let (x, y, w, h) = (number()?, number()?, number()?, number()?);
+            let name = fields.next().ok_or_else(bad)?;
+            let outside = || {
+                EngineError::decode_failed(&table_path, format!("{} is outside the picture", name))
+            };
+            let r = match (u16::try_from(w), u16::try_from(h)) {
+                (Ok(w), Ok(h)) => Rect { x, y, w, h },
+                _ => return Err(outside()),
+            };
+            if r.union(bounds) != bounds {
+                return Err(outside());
+            }
+            rects.insert(name.to_string(), r);
+        }
+        Ok(Self::new(path, image, rects))
+    }
+    // Takes over old's texture handle, so whoever got it from old sees this version's picture
+    pub(crate) fn keep_texture_handle(&mut self, old: &Atlas) {
+        old.texture.set(self.texture.get());
+        self.texture = old.texture.clone();
+    }
+    /// Writes the atlas out as a PNG at path, with its table next to it
+    pub fn save(&self, path: &Path) -> Result<(), EngineError> {
+        self.image
+            .save(path)
+            .map_err(|err| EngineError::write_failed(path, err))?;
+        let mut entries: Vec<(&String, &Rect)> = self.rects.iter().collect();
+        entries.sort_by_key(|(name, _)| *name);
+        let table: String = entries
+            .into_iter()
+            .map(|(name, r)| format!("{} {} {} {} {}\n", r.x, r.y, r.w, r.h, name))
+            .collect();
+        let table_path = table_path(path);
+        fs::write(&table_path, table).map_err(|err| EngineError::write_failed(&table_path, err))
+    }
+    /// The packed texture, for Screen::bitblt and sprites
+    pub fn texture(&self) -> &Handle<Texture> {
+        &self.texture
+    }
+    /// Where in the texture the picture called name is
+    pub fn rect(&self, name: &str) -> Option<Rect> {
+        self.rects.get(name).copied()
+    }
+    pub fn len(&self) -> usize {
+        self.rects.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+    /// Draws the picture called name with its top-left corner at to, if there is one
+    pub fn draw(&self, screen: &mut Screen, name: &str, to: Vec2i) {
+        if let Some(r) = self.rect(name) {
+            screen.bitblt(&self.texture.get(), r, to);
+        }
+    }
+    /// Draws a line of text with glyphs added by AtlasBuilder::add_glyphs.
+    /// Characters the atlas doesn't have are left out.
+    pub fn draw_text(&self, screen: &mut Screen, font_name: &str, text: &str, to: Vec2i) {
+        let texture = self.texture.get();
+        let mut at = to;
+        for c in text.chars() {
+            if let Some(r) = self.rect(&glyph_name(font_name, c)) {
+                screen.bitblt(&texture, r, at);
+                at.0 += r.w as i32;
+            }
+        }
+    }
+    /// A copy of anim, made for a sprite sheet on its own, that plays from the sheet
+    /// after it was packed in as name
+    pub fn animation(&self, name: &str, anim: &Animation) -> Option<Animation> {
+        Some(anim.moved(self.rect(name)?.pos()))
+    }
+    /// An animation going through pictures packed separately, each shown for frame_time frames
+    pub fn sequence(&self, names: &[&str], frame_time: usize, looping: bool) -> Option<Animation> {
+        let frames: Option<Vec<(Rect, usize)>> = names
+            .iter()
+            .map(|name| Some((self.rect(name)?, frame_time)))
+            .collect();
+        Some(Animation::new(frames?, looping))
+    }
+}
+
+/// What AtlasBuilder::add_glyphs calls a character from a font
+pub fn glyph_name(font_name: &str, c: char) -> String {
+    format!("{}/{}", font_name, c)
+}
+
+/// Draws a line of text into a picture just big enough for it.
+/// The color isn't premultiplied, and the background is transparent.
+pub fn render_text(text: &str, font: &Font, scale: f32, color: Rgba) -> RgbaImage {
+    let scale = Scale::uniform(scale);
+    let metrics = font.v_metrics(scale);
+    let glyphs: Vec<_> = font
+        .layout(text, scale, point(0.0, metrics.ascent))
+        .collect();
+    let width = glyphs.last().map_or(0.0, |g| {
+        g.position().x + g.unpositioned().h_metrics().advance_width
+    });
+    let height = metrics.ascent - metrics.descent;
+    let mut image = RgbaImage::new(width.ceil() as u32, height.ceil() as u32);
+    let (w, h) = (image.width() as i32, image.height() as i32);
+    for glyph in glyphs.iter() {
+        if let Some(bb) = glyph.pixel_bounding_box() {
+            glyph.draw(|x, y, coverage| {
+                let (x, y) = (bb.min.x + x as i32, bb.min.y + y as i32);
+                if x < 0 || y < 0 || x >= w || y >= h {
+                    return;
+                }
+                let alpha = (coverage * color.3 as f32).round() as u8;
+                let px = image.get_pixel_mut(x as u32, y as u32);
+                // Where glyphs overlap, whichever covers more wins
+                if alpha > px[3] {
+                    *px = Pixel([color.0, color.1, color.2, alpha]);
+                }
+            });
+        }
+    }
+    image
+}
+
+// Where an atlas' table goes, next to its picture
+fn table_path(path: &Path) -> PathBuf {
+    path.with_extension("atlas")
+}
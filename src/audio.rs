@@ -0,0 +1,580 @@
+// Sound effects and music.
+// The mixer adds every playing sound together into stereo samples, a game frame's worth at a
+// time, and the Output decides where those go: the sound card, a WAV file, or nowhere.
+use crate::error::EngineError;
+use crate::types::{Vec2f, Vec2i};
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Sample frames (a left and a right sample each) per second, for everything the mixer deals in
+pub const SAMPLE_RATE: u32 = 44100;
+/// Sample frames mixed each time the game updates, 60 times a second
+pub const FRAMES_PER_UPDATE: usize = SAMPLE_RATE as usize / 60;
+
+/// A whole sound in memory, as interleaved left and right samples from -1 to 1 at SAMPLE_RATE
+pub struct Sound {
+    samples: Vec<f32>,
+}
+
+impl Sound {
+    /// Reads a WAV file, in any sample format, rate or number of channels
+    pub fn with_file(path: &Path) -> Result<Self, EngineError> {
+        let wav_error = |err| match err {
+            hound::Error::IoError(err) => EngineError::from_io(path, err),
+            hound::Error::Unsupported | hound::Error::InvalidSampleFormat => {
+                EngineError::UnsupportedFormat(path.to_path_buf())
+            }
+            err => EngineError::decode_failed(path, err),
+        };
+        let reader = WavReader::open(path).map_err(wav_error)?;
+        Self::from_wav(reader).map_err(wav_error)
+    }
+    /// Takes interleaved stereo samples at SAMPLE_RATE
+    pub fn new(samples: Vec<f32>) -> Self {
+        assert_eq!(
+            samples.len() % 2,
+            0,
+            "Sound samples should come in left/right pairs"
+        );
+        Self { samples }
+    }
+    fn from_wav<R: std::io::Read>(reader: WavReader<R>) -> Result<Self, hound::Error> {
+        let spec = reader.spec();
+        let samples: Vec<f32> = match spec.sample_format {
+            SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<_, _>>()?,
+            SampleFormat::Int => {
+                let full = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .into_samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 / full))
+                    .collect::<Result<_, _>>()?
+            }
+        };
+        // Mono plays out of both sides; past stereo, only the first two channels are kept
+        let channels = spec.channels.max(1) as usize;
+        let stereo: Vec<(f32, f32)> = samples
+            .chunks_exact(channels)
+            .map(|c| (c[0], if channels > 1 { c[1] } else { c[0] }))
+            .collect();
+        let stereo = resample(&stereo, spec.sample_rate, SAMPLE_RATE);
+        Ok(Self::new(
+            stereo.into_iter().flat_map(|(l, r)| [l, r]).collect(),
+        ))
+    }
+    /// How many sample frames long the sound is
+    pub fn frames(&self) -> usize {
+        self.samples.len() / 2
+    }
+    pub fn seconds(&self) -> f32 {
+        self.frames() as f32 / SAMPLE_RATE as f32
+    }
+    fn frame(&self, i: usize) -> (f32, f32) {
+        (self.samples[i * 2], self.samples[i * 2 + 1])
+    }
+}
+
+// Stretches sound recorded at one rate to play back at another, interpolating between samples
+fn resample(frames: &[(f32, f32)], from: u32, to: u32) -> Vec<(f32, f32)> {
+    if from == to || frames.is_empty() {
+        return frames.to_vec();
+    }
+    let len = (frames.len() as u64 * to as u64 / from as u64) as usize;
+    (0..len)
+        .map(|i| {
+            let t = i as f64 * from as f64 / to as f64;
+            let a = frames[(t as usize).min(frames.len() - 1)];
+            let b = frames[(t as usize + 1).min(frames.len() - 1)];
+            let k = t.fract() as f32;
+            (a.0 + (b.0 - a.0) * k, a.1 + (b.1 - a.1) * k)
+        })
+        .collect()
+}
+
+/// Names one sound the mixer is playing
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct VoiceId(u64);
+
+struct Voice {
+    id: VoiceId,
+    sound: Rc<Sound>,
+    // The next sample frame to play
+    at: usize,
+    looping: bool,
+    volume: f32,
+    pan: f32,
+    // Where a fade is taking the volume, and how far it goes each sample frame
+    target: f32,
+    step: f32,
+    // Stop playing once the fade gets there
+    stop_after_fade: bool,
+    // For sounds out in the world: where, and how far they carry
+    place: Option<(Vec2i, Falloff)>,
+}
+
+impl Voice {
+    fn fade_to(&mut self, volume: f32, seconds: f32) {
+        let frames = seconds * SAMPLE_RATE as f32;
+        self.target = volume;
+        self.step = if frames >= 1.0 {
+            (volume - self.volume).abs() / frames
+        } else {
+            f32::INFINITY
+        };
+    }
+}
+
+/// How a sound out in the world gets quieter on its way from min_range to max_range
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Attenuation {
+    /// Steadily, in a straight line
+    Linear,
+    /// Quickly at first and then more gently, like the square of Linear
+    Quadratic,
+    /// In proportion to the distance, like real sound, then cut off at max_range
+    Inverse,
+}
+
+/// How far a sound out in the world carries, in world pixels
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Falloff {
+    /// Full volume within this distance
+    pub min_range: f32,
+    /// Silent past this distance
+    pub max_range: f32,
+    pub curve: Attenuation,
+}
+
+impl Falloff {
+    /// How loud the sound is from this far away, from 0 to 1
+    pub fn gain(&self, distance: f32) -> f32 {
+        if distance <= self.min_range {
+            return 1.0;
+        }
+        if distance >= self.max_range {
+            return 0.0;
+        }
+        let t = (distance - self.min_range) / (self.max_range - self.min_range);
+        match self.curve {
+            Attenuation::Linear => 1.0 - t,
+            Attenuation::Quadratic => (1.0 - t) * (1.0 - t),
+            Attenuation::Inverse => self.min_range.max(1.0) / distance.max(1.0),
+        }
+    }
+}
+
+/// Where sounds out in the world are heard from, usually the middle of the camera's view
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Listener {
+    pub pos: Vec2i,
+    /// How far to one side a sound has to be to only come out of that side
+    pub pan_range: f32,
+}
+
+impl Listener {
+    /// How loud a sound at pos is, and where it's panned to
+    pub fn hear(&self, pos: Vec2i, falloff: Falloff) -> (f32, f32) {
+        let offset = Vec2f::from(pos - self.pos);
+        let pan = (offset.0 / self.pan_range.max(1.0)).clamp(-1.0, 1.0);
+        (falloff.gain(offset.length()), pan)
+    }
+}
+
+/// Left and right gains for a pan from -1 (all left) to 1 (all right).
+/// The middle plays at full volume on both sides, and panning turns the far side down.
+pub fn pan_gains(pan: f32) -> (f32, f32) {
+    let pan = pan.clamp(-1.0, 1.0);
+    ((1.0 - pan).min(1.0), (1.0 + pan).min(1.0))
+}
+
+pub struct Mixer {
+    voices: Vec<Voice>,
+    // The music track playing, if any; only one plays at once, apart from crossfades
+    music: Option<VoiceId>,
+    next_id: u64,
+    /// Everything is turned up or down by this
+    pub master: f32,
+    /// Who's hearing the sounds that are out in the world
+    pub listener: Listener,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self {
+            voices: vec![],
+            music: None,
+            next_id: 0,
+            master: 1.0,
+            listener: Listener {
+                pos: Vec2i(0, 0),
+                pan_range: 320.0,
+            },
+        }
+    }
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Plays a sound once, at volume from 0 to 1 and pan from -1 (left) to 1 (right)
+    pub fn play(&mut self, sound: &Rc<Sound>, volume: f32, pan: f32) -> VoiceId {
+        self.start(sound, volume, pan, false)
+    }
+    /// Plays a sound over and over until it's stopped
+    pub fn play_looping(&mut self, sound: &Rc<Sound>, volume: f32, pan: f32) -> VoiceId {
+        self.start(sound, volume, pan, true)
+    }
+    /// Plays a sound once from a spot in the world; it's panned and turned down by where
+    /// that is from the listener
+    pub fn play_at(
+        &mut self,
+        sound: &Rc<Sound>,
+        volume: f32,
+        pos: Vec2i,
+        falloff: Falloff,
+    ) -> VoiceId {
+        let id = self.play(sound, volume, 0.0);
+        self.place(id, pos, falloff);
+        id
+    }
+    /// Plays a sound over and over from a spot in the world
+    pub fn play_looping_at(
+        &mut self,
+        sound: &Rc<Sound>,
+        volume: f32,
+        pos: Vec2i,
+        falloff: Falloff,
+    ) -> VoiceId {
+        let id = self.play_looping(sound, volume, 0.0);
+        self.place(id, pos, falloff);
+        id
+    }
+    fn place(&mut self, id: VoiceId, pos: Vec2i, falloff: Falloff) {
+        if let Some(v) = self.voice(id) {
+            v.place = Some((pos, falloff));
+        }
+    }
+    /// Moves a sound that's out in the world, e.g. along with whatever's making it
+    pub fn move_to(&mut self, id: VoiceId, pos: Vec2i) {
+        if let Some((at, _)) = self.voice(id).and_then(|v| v.place.as_mut()) {
+            *at = pos;
+        }
+    }
+    fn start(&mut self, sound: &Rc<Sound>, volume: f32, pan: f32, looping: bool) -> VoiceId {
+        let id = VoiceId(self.next_id);
+        self.next_id += 1;
+        self.voices.push(Voice {
+            id,
+            sound: Rc::clone(sound),
+            at: 0,
+            looping,
+            volume,
+            pan,
+            target: volume,
+            step: 0.0,
+            stop_after_fade: false,
+            place: None,
+        });
+        id
+    }
+    /// Switches to a new music track, fading it in over fade seconds while the old one fades out
+    pub fn play_music(&mut self, track: &Rc<Sound>, volume: f32, fade: f32) -> VoiceId {
+        self.stop_music(fade);
+        let id = self.play_looping(track, 0.0, 0.0);
+        self.fade_to(id, volume, fade);
+        self.music = Some(id);
+        id
+    }
+    /// Fades the music out over fade seconds
+    pub fn stop_music(&mut self, fade: f32) {
+        if let Some(id) = self.music.take() {
+            self.stop(id, fade);
+        }
+    }
+    pub fn music(&self) -> Option<VoiceId> {
+        self.music
+    }
+    fn voice(&mut self, id: VoiceId) -> Option<&mut Voice> {
+        self.voices.iter_mut().find(|v| v.id == id)
+    }
+    pub fn is_playing(&self, id: VoiceId) -> bool {
+        self.voices.iter().any(|v| v.id == id)
+    }
+    /// How many sounds are playing
+    pub fn playing(&self) -> usize {
+        self.voices.len()
+    }
+    /// Changes a sound's volume straight away, cancelling any fade
+    pub fn set_volume(&mut self, id: VoiceId, volume: f32) {
+        if let Some(v) = self.voice(id) {
+            v.volume = volume;
+            v.target = volume;
+            v.step = 0.0;
+        }
+    }
+    pub fn set_pan(&mut self, id: VoiceId, pan: f32) {
+        if let Some(v) = self.voice(id) {
+            v.pan = pan;
+        }
+    }
+    /// Turns a sound smoothly up or down to volume over some seconds
+    pub fn fade_to(&mut self, id: VoiceId, volume: f32, seconds: f32) {
+        if let Some(v) = self.voice(id) {
+            v.fade_to(volume, seconds);
+        }
+    }
+    /// Stops a sound after fading it out for some seconds, or right away for 0
+    pub fn stop(&mut self, id: VoiceId, fade: f32) {
+        if fade <= 0.0 {
+            self.voices.retain(|v| v.id != id);
+        } else if let Some(v) = self.voice(id) {
+            v.fade_to(0.0, fade);
+            v.stop_after_fade = true;
+        }
+    }
+    /// Mixes the next out.len() / 2 sample frames into out, as interleaved left and right samples
+    pub fn mix(&mut self, out: &mut [f32]) {
+        for s in out.iter_mut() {
+            *s = 0.0;
+        }
+        let master = self.master;
+        let listener = self.listener;
+        for v in self.voices.iter_mut() {
+            // Sounds in the world are placed once per mix, often enough to follow things around
+            let (distance_gain, pan) = match v.place {
+                Some((pos, falloff)) => listener.hear(pos, falloff),
+                None => (1.0, v.pan),
+            };
+            let (lg, rg) = pan_gains(pan);
+            for frame in out.chunks_exact_mut(2) {
+                if v.at >= v.sound.frames() {
+                    if !v.looping || v.sound.frames() == 0 {
+                        break;
+                    }
+                    v.at = 0;
+                }
+                let (l, r) = v.sound.frame(v.at);
+                let gain = v.volume * distance_gain * master;
+                frame[0] += l * lg * gain;
+                frame[1] += r * rg * gain;
+                v.at += 1;
+                // Move the volume along the fade
+                if v.volume < v.target {
+                    v.volume = (v.volume + v.step).min(v.target);
+                } else if v.volume > v.target {
+                    v.volume = (v.volume - v.step).max(v.target);
+                } else if v.stop_after_fade {
+                    break;
+                }
+            }
+        }
+        self.voices.retain(|v| {
+            let finished = !v.looping && v.at >= v.sound.frames();
+            let faded_out = v.stop_after_fade && v.volume == v.target;
+            !(finished || faded_out)
+        });
+        if let Some(id) = self.music {
+            if !self.voices.iter().any(|v| v.id == id) {
+                self.music = None;
+            }
+        }
+        for s in out.iter_mut() {
+            *s = s.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+/// The most a Buffer output keeps, in seconds; anything played after that is dropped
+pub const BUFFER_LIMIT: usize = 60;
+
+// 16-bit stereo, which is what both recordings and saved buffers are written as
+const WAV_SPEC: WavSpec = WavSpec {
+    channels: 2,
+    sample_rate: SAMPLE_RATE,
+    bits_per_sample: 16,
+    sample_format: SampleFormat::Int,
+};
+
+/// Where mixed sound goes
+pub enum Output {
+    /// Nowhere: the game runs silently
+    Null,
+    /// Kept in memory, as interleaved stereo samples at SAMPLE_RATE, for tests to look at.
+    /// Stops growing once it holds BUFFER_LIMIT seconds.
+    Buffer(Vec<f32>),
+    /// Written out to the WAV file at the path as it's played
+    Wav(WavWriter<BufWriter<File>>, PathBuf),
+    /// Played on the sound card
+    #[cfg(feature = "device")]
+    Device(device::Device),
+}
+
+impl Output {
+    /// Records to a new WAV file at path
+    pub fn wav(path: &Path) -> Result<Self, EngineError> {
+        WavWriter::create(path, WAV_SPEC)
+            .map(|writer| Output::Wav(writer, path.to_path_buf()))
+            .map_err(|err| match err {
+                hound::Error::IoError(err) => EngineError::from_io(path, err),
+                err => EngineError::write_failed(path, err),
+            })
+    }
+    /// Plays on the default sound card, or says why it can't
+    #[cfg(feature = "device")]
+    pub fn device() -> Result<Self, String> {
+        device::Device::open().map(Output::Device)
+    }
+    #[cfg(not(feature = "device"))]
+    pub fn device() -> Result<Self, String> {
+        Err("built without the device feature".to_string())
+    }
+}
+
+/// The mixer and its output, stepped along with the game
+pub struct Audio {
+    pub mixer: Mixer,
+    pub output: Output,
+    // Reused every update, so mixing doesn't allocate
+    scratch: Vec<f32>,
+}
+
+impl Audio {
+    pub fn new(output: Output) -> Self {
+        Self {
+            mixer: Mixer::new(),
+            output,
+            scratch: vec![0.0; FRAMES_PER_UPDATE * 2],
+        }
+    }
+    /// Mixes one game frame's worth of sound and sends it to the output
+    pub fn update(&mut self) {
+        self.mixer.mix(&mut self.scratch);
+        match &mut self.output {
+            Output::Null => {}
+            Output::Buffer(samples) => {
+                let room = (BUFFER_LIMIT * SAMPLE_RATE as usize * 2).saturating_sub(samples.len());
+                let n = room.min(self.scratch.len());
+                samples.extend_from_slice(&self.scratch[..n]);
+            }
+            Output::Wav(writer, _) => {
+                if let Err(err) = write_samples(writer, &self.scratch) {
+                    eprintln!("Stopped recording: {}", err);
+                    self.output = Output::Null;
+                }
+            }
+            #[cfg(feature = "device")]
+            Output::Device(device) => device.queue(&self.scratch),
+        }
+    }
+    /// Everything played so far, if it's being kept in memory
+    pub fn recorded(&self) -> &[f32] {
+        match &self.output {
+            Output::Buffer(samples) => samples,
+            _ => &[],
+        }
+    }
+    /// Writes everything played so far to a 16-bit stereo WAV file
+    pub fn save_wav(&self, path: &Path) -> Result<(), hound::Error> {
+        let mut writer = WavWriter::create(path, WAV_SPEC)?;
+        write_samples(&mut writer, self.recorded())?;
+        writer.finalize()
+    }
+    /// Stops playing, finishing off the file if it was being recorded
+    pub fn close(self) -> Result<(), EngineError> {
+        match self.output {
+            Output::Wav(writer, path) => writer
+                .finalize()
+                .map_err(|err| EngineError::write_failed(&path, err)),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn write_samples<W>(writer: &mut WavWriter<W>, samples: &[f32]) -> Result<(), hound::Error>
+where
+    W: std::io::Write + std::io::Seek,
+{
+    for s in samples {
+        writer.write_sample((s * i16::MAX as f32) as i16)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "device")]
+mod device {
+    use super::{FRAMES_PER_UPDATE, SAMPLE_RATE};
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use cpal::{Sample, SampleFormat, Stream, StreamConfig};
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    // How far ahead of the sound card the game can get, in samples, before the oldest are dropped.
+    // A few updates' worth covers a slow frame without letting the sound lag far behind.
+    const MAX_QUEUED: usize = FRAMES_PER_UPDATE * 2 * 4;
+
+    /// The default sound card, playing samples as the game queues them.
+    /// The card pulls from the queue on a thread of its own, and plays silence if it runs dry.
+    pub struct Device {
+        queue: Arc<Mutex<VecDeque<f32>>>,
+        // Dropping this stops playback
+        _stream: Stream,
+    }
+
+    impl Device {
+        pub fn open() -> Result<Self, String> {
+            let device = cpal::default_host()
+                .default_output_device()
+                .ok_or("there's no sound card")?;
+            let range = device
+                .supported_output_configs()
+                .map_err(|err| err.to_string())?
+                .find(|c| {
+                    c.channels() == 2
+                        && c.min_sample_rate().0 <= SAMPLE_RATE
+                        && SAMPLE_RATE <= c.max_sample_rate().0
+                })
+                .ok_or("the sound card can't play 44.1kHz stereo")?;
+            let format = range.sample_format();
+            let config = range.with_sample_rate(cpal::SampleRate(SAMPLE_RATE)).config();
+            let queue = Arc::new(Mutex::new(VecDeque::new()));
+            let stream = match format {
+                SampleFormat::F32 => play::<f32>(&device, &config, &queue),
+                SampleFormat::I16 => play::<i16>(&device, &config, &queue),
+                SampleFormat::U16 => play::<u16>(&device, &config, &queue),
+            }
+            .map_err(|err| err.to_string())?;
+            stream.play().map_err(|err| err.to_string())?;
+            Ok(Self {
+                queue,
+                _stream: stream,
+            })
+        }
+        pub fn queue(&mut self, samples: &[f32]) {
+            let mut queue = self.queue.lock().unwrap();
+            queue.extend(samples);
+            let excess = queue.len().saturating_sub(MAX_QUEUED);
+            queue.drain(..excess);
+        }
+    }
+
+    fn play<T: Sample>(
+        device: &cpal::Device,
+        config: &StreamConfig,
+        queue: &Arc<Mutex<VecDeque<f32>>>,
+    ) -> Result<Stream, cpal::BuildStreamError> {
+        let queue = Arc::clone(queue);
+        device.build_output_stream(
+            config,
+            move |out: &mut [T], _| {
+                let mut queue = queue.lock().unwrap();
+                for s in out.iter_mut() {
+                    *s = T::from(&queue.pop_front().unwrap_or(0.0));
+                }
+            },
+            |err| eprintln!("Sound card trouble: {}", err),
+        )
+    }
+}
@@ -0,0 +1,25 @@
+// Packs every PNG in a directory into one atlas ahead of time, for Resources::load_atlas:
+//     cargo run --bin atlas -- content/icons content/icons.png
+// writes content/icons.png and its table, content/icons.atlas.
+use std::path::Path;
+
+use Game2DEngine::atlas::AtlasBuilder;
+use Game2DEngine::error::EngineError;
+
+fn main() -> Result<(), EngineError> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        eprintln!(
+            "usage: {} <directory of PNGs> <atlas PNG to write>",
+            args[0]
+        );
+        std::process::exit(2);
+    }
+    let mut builder = AtlasBuilder::new();
+    builder.add_dir(Path::new(&args[1]))?;
+    let atlas = builder.build();
+    atlas.save(Path::new(&args[2]))?;
+    let (w, h) = atlas.texture().get().size();
+    println!("Packed {} pictures into {}x{}", atlas.len(), w, h);
+    Ok(())
+}
@@ -1,36 +1,37 @@
 use std::collections::HashMap;
 use std::path::Path;
 use std::rc::Rc;
-use rusttype::Font;
 use winit::dpi::LogicalSize;
 
 use winit::window::WindowBuilder;
 use winit_input_helper::WinitInputHelper;
 
 use Game2DEngine::animation::*;
+use Game2DEngine::camera::Camera;
+use Game2DEngine::minimap::Minimap;
+use Game2DEngine::audio::{Audio, Output};
+use Game2DEngine::components::*;
+use Game2DEngine::ecs::World;
+use Game2DEngine::error::EngineError;
 use Game2DEngine::graphics::Screen;
 use Game2DEngine::tiles::*;
 use Game2DEngine::types::*;
-// Imagine a Resources struct (we'll call it AssetDB or Assets in the future)
-// which wraps all accesses to textures, sounds, animations, etc.
+// Resources keeps every texture, tileset, map, animation, sound and font, loading each file once
 use Game2DEngine::resources::*;
 use Game2DEngine::server::Server;
 use Game2DEngine::states::*;
 use Game2DEngine::save::*;
+use Game2DEngine::Control;
 
 const WIDTH: usize = 320 * 2;
 const HEIGHT: usize = 240 * 2;
+// How often to check for changed files, in frames
+const RELOAD_FRAMES: usize = 30;
 const CHARACTER: char = 'b';
 const SIZE: f32 = 20.0;
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-enum EntityType {
-    // Player,
-    Enemy,
-}
-
 // type Level = (Vec<Tilemap>, Vec<(EntityType, i32, i32)>);
-fn main() {
+fn main() -> Result<(), EngineError> {
     let window_builder = {
         let size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
         WindowBuilder::new()
@@ -41,51 +42,52 @@ fn main() {
     };
     // Here's our resources...
     let mut rsrc = Resources::new();
-    let tileset = Rc::new(Tileset::new(
+    let tileset = rsrc.add("tilesets/side", Tileset::new(
         vec![
-            Tile {solid: false,jump_reset: false,restart: false},//0
-            Tile {solid: false,jump_reset: false,restart: false},//1
-            Tile {solid: false,jump_reset: false,restart: false},//2
-            Tile {solid: false,jump_reset: false,restart: true},//3
-            Tile {solid: false,jump_reset: false,restart: false},//4
-            Tile {solid: false,jump_reset: false,restart: false},//5
-            Tile {solid: false,jump_reset: false,restart: false},//6
-            Tile {solid: false,jump_reset: false,restart: false},//7
-            Tile {solid: true, jump_reset: true,restart: false},//8
-            Tile {solid: false,jump_reset: false,restart: false},//9
-            Tile {solid: false,jump_reset: false,restart: false},//10
-            Tile {solid: false,jump_reset: false,restart: false},//11
-            Tile {solid: false,jump_reset: false,restart: false},//12
-            Tile {solid: false,jump_reset: false,restart: false},//13
-            Tile {solid: false,jump_reset: false,restart: false},//14
-            Tile {solid: false,jump_reset: false, restart: true},//15
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Ladder},//0 water
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//1
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//2
+            Tile {solid: false,jump_reset: false,restart: true, shape: TileShape::Full},//3
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//4
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//5
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//6
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//7
+            Tile {solid: true, jump_reset: true,restart: false, shape: TileShape::Full},//8
+            Tile {solid: true, jump_reset: true,restart: false, shape: TileShape::OneWay},//9 bridge
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//10
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//11
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//12
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//13
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//14
+            Tile {solid: false,jump_reset: false, restart: true, shape: TileShape::Full},//15
         ],
-        &rsrc.load_texture(Path::new("content/tilesheet.png")),
+        &rsrc.load_tilesheet(Path::new("content/tilesheet.png"))?,
     ));
     // overworld tileset
-    let overworld_tileset = Rc::new(Tileset::new(
+    let overworld_tileset = rsrc.add("tilesets/title", Tileset::new(
         vec![
-            Tile {solid: false,jump_reset: false,restart: false},//0
-            Tile {solid: false,jump_reset: false,restart: false},//1
-            Tile {solid: false,jump_reset: false,restart: false},//2
-            Tile {solid: false,jump_reset: false,restart: false},//3
-            Tile {solid: false,jump_reset: false,restart: false},//4
-            Tile {solid: false,jump_reset: false,restart: false},//5
-            Tile {solid: false,jump_reset: false,restart: false},//6
-            Tile {solid: false,jump_reset: false,restart: false},//7
-            Tile {solid: false,jump_reset: false,restart: false},//8
-            Tile {solid: false,jump_reset: false,restart: false},//9
-            Tile {solid: true,jump_reset: false,restart: false},//10
-            Tile {solid: false,jump_reset: false,restart: false},//11
-            Tile {solid: false,jump_reset: false,restart: false},//12
-            Tile {solid: false,jump_reset: false,restart: false},//13
-            Tile {solid: false,jump_reset: false,restart: false},//14
-            Tile {solid: false,jump_reset: false, restart: true},//15
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//0
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//1
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//2
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//3
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//4
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//5
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//6
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//7
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//8
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//9
+            Tile {solid: true,jump_reset: false,restart: false, shape: TileShape::Full},//10
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//11
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//12
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//13
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//14
+            Tile {solid: false,jump_reset: false, restart: true, shape: TileShape::Full},//15
         ],
-        &rsrc.load_texture(Path::new("content/tilesheet.png")),
+        &rsrc.load_tilesheet(Path::new("content/tilesheet.png"))?,
     ));
 
-    pub fn get_maps(tileset: &Rc<Tileset>) -> Vec<Tilemap> {
+    pub fn get_maps(tileset: &Handle<Tileset>) -> Vec<Tilemap> {
+        let tileset = tileset.get();
         let map = Tilemap::new(
             Vec2i(0, 0),
             (8, 8),
@@ -332,68 +334,14 @@ fn main() {
         ];
     }
 
-    fn get_side_maps(tileset: &Rc<Tileset>) -> Vec<Tilemap>{
-        return vec![Tilemap::new(
-            Vec2i(0, 0),
-            // Map size
-            (130, 54),
-            &tileset,
-            // Tile grid
-            vec![8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,8,8,8,8,8,8,8,8,8,8,8,8,8,1,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,8,8,1,1,1,1,1,1,1,1,1,1,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 8,1,1,1,1,1,1,1,1,8,8,1,1,1,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,8,8,8,8,8,8,8,8,1,8,8,8,8,8,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,8,1,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 8,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,8,8,8,8,8,8,8,8,8,1,1,8,1,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 8,1,1,5,1,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,8,1,1,1,1,1,1,1,8,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 8,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,5,1,8,0,0,8,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,8,1,1,1,1,1,1,1,8,1,1,8,1,8,1,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 8,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,8,0,0,8,8,1,1,1,8,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,8,1,1,1,1,1,1,1,8,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 8,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,8,0,0,8,8,1,1,1,8,8,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 8,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,8,0,0,8,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,8,1,1,8,1,8,1,8,1,1,1,8,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 8,1,1,8,8,8,1,1,1,8,1,1,4,1,1,1,1,1,8,0,0,8,8,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,8,1,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 8,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,8,0,0,8,8,1,1,1,1,1,1,1,1,1,1,8,1,8,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,5,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 8,2,2,2,2,2,2,2,2,8,1,1,1,1,1,1,1,1,8,0,0,8,8,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,8,1,1,1,8,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,1,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,2,2,1,1,1,1,1,1,8,1,1,1,8,1,8,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,2,1,1,2,1,1,1,1,1,8,1,8,1,1,1,8,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,0,8,1,1,1,8,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,2,1,1,1,1,2,1,1,1,1,8,1,1,8,1,1,8,1,1,1,8,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,0,8,1,1,1,8,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,1,1,1,1,2,1,1,1,1,1,1,2,1,1,1,8,1,1,1,8,1,8,1,1,1,8,1,1,1,1,8,1,1,1,1,8,1,1,8,8,1,8,8,1,1,1,8,1,1,8,0,8,1,4,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,2,1,1,1,1,1,1,1,1,2,1,1,8,1,1,8,1,1,8,1,1,1,8,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,8,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,0,1,1,2,2,1,1,2,2,1,1,0,1,8,1,8,1,1,1,8,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,8,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,0,1,1,1,1,1,1,1,1,1,1,0,1,8,1,1,1,1,1,8,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,0,1,1,1,1,1,1,1,1,1,1,0,1,8,1,1,1,1,1,8,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,8,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,0,1,1,1,1,1,1,1,1,1,1,0,1,8,1,1,1,1,1,8,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,8,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,8,1,1,0,1,1,1,2,1,1,2,1,1,1,0,1,8,1,1,1,1,1,8,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,1,1,1,1,8,1,1,0,1,1,1,1,2,2,1,1,1,1,0,1,8,1,1,1,1,1,8,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,8,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,2,1,1,1,1,1,1,1,1,2,1,1,8,1,1,8,1,1,8,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,8,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,2,1,1,1,1,1,1,2,1,1,1,8,1,1,8,1,1,8,1,1,1,8,1,1,1,1,8,8,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,2,1,1,1,1,2,1,1,1,1,8,1,1,8,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,8,1,1,8,1,4,1,8,1,1,1,8,1,1,8,1,8,1,1,1,8,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,8,1,1,1,1,1,1,2,1,1,2,1,1,1,1,1,8,1,1,8,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,8,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,2,2,1,1,1,1,1,1,8,1,1,8,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,8,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,8,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,5,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,8,1,1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,0,8,1,1,1,8,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,8,8,8,1,8,1,1,1,8,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,0,8,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,0,8,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,4,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,8,1,1,1,8,1,1,8,1,8,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,
-                 1,8,8,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,8,8,8,8,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,8,8,1,1,1,1,8,1,1,1,1,8,1,1,8,1,1,1,1,1,1,1,8,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,8,3,3,3,1,1,1,1,8,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,8,1,1,1,1,8,1,1,1,1,1,1,3,1,1,1,3,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,8,1,1,1,1,8,1,1,1,1,1,1,3,1,1,1,3,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,8,1,1,1,1,8,1,1,1,1,1,1,3,1,1,1,3,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,8,1,1,1,1,8,1,1,1,1,1,1,3,1,5,1,3,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,8,1,1,1,1,8,1,1,1,1,1,1,3,1,1,1,3,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,8,1,1,1,1,8,1,1,1,1,1,1,1,3,3,3,1,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,1,1,1,8,1,1,1,1,8,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,8,8,8,8,8,8,8,8,1,8,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,1,1,1,1,8,1,1,1,1,1,1,1,1,1,1,1,1,1,8,4,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,5,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,
-                 1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,3,3,3,3,8,1,1,1,1,1,1,1,1,1,1,1,1,1,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,8,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1]
-        )]
+    // Loaded through rsrc so changes to the file show up while we play
+    fn get_side_maps(
+        rsrc: &Resources,
+        tileset: &Handle<Tileset>,
+    ) -> Result<Vec<Tilemap>, EngineError> {
+        let tileset = &tileset.get();
+        let grid = rsrc.load_map("content/game2_side.map")?;
+        Ok(vec![Tilemap::with_grid(Vec2i(0, 0), grid, tileset)?])
     }
 
     // Here's our game rules (the engine doesn't know about these)
@@ -401,13 +349,15 @@ fn main() {
         (
             // level 0 is the side scroller
             // The map
-            get_side_maps(&tileset),
+            get_side_maps(&rsrc, &tileset)?,
             // Initial entities on level start
             vec![
-                (Player::new(), 15, 29),
-                (Player::new(), 9, 6),
-                (Player::new(), 10, 5),
-                (Player::new(), 11, 6),
+                (EntityType::Player, 15, 29),
+                (EntityType::Enemy(EnemyKind::Patroller), 12, 21),
+                (EntityType::Enemy(EnemyKind::Chaser), 28, 14),
+                (EntityType::Enemy(EnemyKind::Jumper), 46, 52),
+                (EntityType::Enemy(EnemyKind::Patroller), 90, 50),
+                (EntityType::Enemy(EnemyKind::Flyer), 100, 45),
             ],
         ),
         (
@@ -416,15 +366,12 @@ fn main() {
             get_maps(&overworld_tileset),
             // Initial entities on level start
             vec![
-                (Player::new(), 10, 6),
-                (Player::new(), 9, 6),
-                (Player::new(), 10, 5),
-                (Player::new(), 11, 6),
+                (EntityType::Player, 10, 6),
             ],
         ),
     ];
-    let player_tex = rsrc.load_texture(Path::new("content/wiry_all_side.png"));
-    let player_anim = Rc::new(Animation::new(
+    let player_tex = rsrc.load_texture(Path::new("content/wiry_all_side.png"))?;
+    let player_anim = rsrc.add("animations/player", Animation::new(
         vec![
             (
                 Rect {
@@ -456,112 +403,87 @@ fn main() {
         ],
         true,
     ));
-    let enemy_tex = rsrc.load_texture(Path::new("content/tilesheet.png"));
-    let enemy_anim = Rc::new(Animation::freeze(Rect {
+    let enemy_tex = rsrc.load_tilesheet(Path::new("content/tilesheet.png"))?;
+    let enemy_anim = rsrc.add("animations/enemy", Animation::freeze(Rect {
         x: 0,
         y: 32,
         w: 32,
         h: 32,
     }));
-    let overworld_player_tex = rsrc.load_texture(Path::new("content/wiry_all_side.png"));
-    let overworld_player_anim = Rc::new(Animation::new(
-        vec![
-            (
-                Rect {
-                    x: 0,
-                    y: 0,
-                    w: 32,
-                    h: 32,
-                },
-                8,
-            ),
-            (
-                Rect {
-                    x: 32,
-                    y: 0,
-                    w: 32,
-                    h: 32,
-                },
-                8,
-            ),
-            (
-                Rect {
-                    x: 64,
-                    y: 0,
-                    w: 32,
-                    h: 32,
-                },
-                8,
-            ),
-        ],
-        true,
-    ));
-    let background_tex = rsrc.load_texture(Path::new("content/badland_background.png"));
+    let background_tex = rsrc.load_texture(Path::new("content/badland_background.png"))?;
     // And here's our game state, which is just stuff that changes.
     // We'll say an entity is a type, a position, a velocity, a size, a texture, and an animation state.
     // State here will stitch them all together.
 
     let mut server = Server::new();
     server.connect("45.10.152.68:32306");
-    let mut player = load("save2.json");
+    let mut player = load_or_new("save2.json")?;
     player.id = server.id;
 
-    let cam = Vec2i((player.pos.0 - WIDTH as i32 / 2).max(0), (player.pos.1 - HEIGHT as i32 / 2).max(0));
+    let start = player.pos.snap();
+    let cam = Vec2i((start.0 - WIDTH as i32 / 2).max(0), (start.1 - HEIGHT as i32 / 2).max(0));
     let stack: Vec<Box<dyn State>> = vec![if player.world == 0 { Box::new(Title()) } else { Box::new(Scroll()) }];
     let level:usize = 1 - player.world as usize;
     let mut players = HashMap::<i32, Player>::new();
     players.entry(player.id).or_insert(player);
+    let mut entities = World::new();
+    let player_entity = spawn_player(&mut entities, &player, &player_tex, &player_anim.get());
 
     let mut map_x_boundary = 1024 as i32;
     let mut map_y_boundary = 1024 as i32;
 
-    let other_tileset = Rc::new(Tileset::new(
+    let other_tileset = rsrc.add("tilesets/overworld", Tileset::new(
         vec![
-            Tile {solid: false,jump_reset: false,restart: false},//0
-            Tile {solid: false,jump_reset: false,restart: false},//1
-            Tile {solid: false,jump_reset: false,restart: false},//2
-            Tile {solid: false,jump_reset: false,restart: false},//3
-            Tile {solid: false,jump_reset: false,restart: false},//4
-            Tile {solid: false,jump_reset: false,restart: false},//5
-            Tile {solid: false,jump_reset: false,restart: false},//6
-            Tile {solid: false,jump_reset: false,restart: false},//7
-            Tile {solid: false,jump_reset: false,restart: false},//8
-            Tile {solid: false,jump_reset: false,restart: false},//9
-            Tile {solid: true,jump_reset: false,restart: false},//10
-            Tile {solid: false,jump_reset: false,restart: false},//11
-            Tile {solid: false,jump_reset: false,restart: false},//12
-            Tile {solid: false,jump_reset: false,restart: false},//13
-            Tile {solid: false,jump_reset: false,restart: false},//14
-            Tile {solid: false,jump_reset: false, restart: true},//15
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//0
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//1
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//2
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//3
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//4
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//5
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//6
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//7
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//8
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//9
+            Tile {solid: true,jump_reset: false,restart: false, shape: TileShape::Full},//10
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//11
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//12
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//13
+            Tile {solid: false,jump_reset: false,restart: false, shape: TileShape::Full},//14
+            Tile {solid: false,jump_reset: false, restart: true, shape: TileShape::Full},//15
         ],
-        &rsrc.load_texture(Path::new("content/tilesheet.png")),
+        &rsrc.load_tilesheet(Path::new("content/tilesheet.png"))?,
     ));
-    let font_data: &[u8] = include_bytes!("../../content/helvetica.ttf");
-    let font: Font<'static> = Font::try_from_bytes(font_data).unwrap();
+    let font = rsrc.load_font("content/helvetica.ttf")?.get();
 
 
-    let game = GameState {
-        // Every entity has a position, a size, a texture, and animation state.
-        // Assume entity 0 is the player
+    // Set RECORD_AUDIO to a file name to record everything the game plays there instead
+    let output = match std::env::var_os("RECORD_AUDIO") {
+        Some(path) => Output::wav(Path::new(&path))?,
+        None if cfg!(feature = "device") => Output::device().unwrap_or_else(|err| {
+            eprintln!("Playing without sound: {}", err);
+            Output::Null
+        }),
+        // Built without sound card support
+        None => Output::Null,
+    };
+    let mut game = GameState {
+        entities,
+        player: player_entity,
         server,
         players,
-        sizes: vec![(32, 32)],
-        // Could be texture handles instead, let's talk about that in two weeks
-        textures: vec![
-            Rc::clone(&player_tex),
-            Rc::clone(&enemy_tex),
-            Rc::clone(&background_tex),
-            Rc::clone(&overworld_player_tex),
-        ],
-        anim_state: vec![
-            player_anim.start(),
-            enemy_anim.start(),
-            overworld_player_anim.start(),
-        ],
+        enemy_tex,
+        enemy_anim,
+        background_tex,
         // Current level
         level: 1 - 1,
         // Camera position
-        camera: cam,
+        camera: Camera::new(cam, (WIDTH, HEIGHT)),
+        camera_events: vec![],
+        guest: None,
+        effects: Effects::default(),
+        audio: Audio::new(output),
+        sounds: Sounds::load(&rsrc)?,
+        ambience: vec![],
         // background position
         background_pos: Vec2i(0, 0),
         state_stack: stack,
@@ -570,15 +492,16 @@ fn main() {
             speed_multiplier: 1,
             num_jumps: 0,
             portals: vec![(Vec2i(26*32, 40*32), Vec2i(3*32, 8*32)), (Vec2i(3008,416), Vec2i(68*32,30*32)), (Vec2i(2306, 1412), Vec2i(79*32, 50*32)), (Vec2i(3328, 1664), Vec2i(87*32, 20*32))],
-            restart: false,
         },
         map_x_boundary,
         map_y_boundary,
         tt_tileset: overworld_tileset,
         maps: get_maps(&other_tileset),
-        side_map: get_side_maps(&tileset),
+        minimap: Minimap::new(2),
+        side_map: get_side_maps(&rsrc, &tileset)?,
         // font,
         game: 2, 
+        start_point: Vec2i(3*32, 37*32),
         spawn_point: Vec2i(3*32, 37*32),
         texts_overworld:vec![
             Text::new(Vec2i(150,75),"Use arrow keys",&font,25.0),
@@ -602,7 +525,10 @@ fn main() {
         draw_game,
         update_game,
     );
-    save(&state.players[&state.server.id],"save2.json");
+    // The recording is finished off even if saving doesn't work
+    let saved = save(&state.players[&state.server.id],"save2.json");
+    state.audio.close()?;
+    saved
 }
 
 fn draw_game(
@@ -612,11 +538,9 @@ fn draw_game(
     screen: &mut Screen,
     frame: usize,
 ) {
-    state
-        .state_stack
-        .last()
-        .unwrap()
-        .display(&state, resources, levels, screen, frame);
+    if let Some(top) = state.state_stack.last() {
+        top.display(&state, resources, levels, screen, frame);
+    }
 }
 
 fn update_game(
@@ -625,6 +549,12 @@ fn update_game(
     state: &mut GameState,
     key_input: &WinitInputHelper,
     frame: usize,
-) {
-    process_input(state, resources, levels, frame, key_input);
+) -> Control {
+    // Pick up art that's been saved since it was loaded, so it can be tweaked while we play
+    if cfg!(debug_assertions) && frame.is_multiple_of(RELOAD_FRAMES) {
+        resources.reload_changed();
+    }
+    let control = process_input(state, resources, levels, frame, key_input);
+    state.audio.update();
+    control
 }
@@ -0,0 +1,205 @@
+// A camera that follows something around the world.
+// It's moved once a frame after physics, from wherever the target ended up.
+// Gameplay code doesn't poke at it directly; it sends CameraEvents for shakes and zooms.
+use crate::types::{Rect, Vec2f, Vec2i};
+
+/// Things gameplay asks of the camera
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CameraEvent {
+    /// Something hit hard; adds this much trauma, from 0 to 1
+    Shake(f32),
+    /// Show each world pixel as this many screen pixels
+    Zoom(usize),
+    ZoomIn,
+    ZoomOut,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Camera {
+    /// Top-left corner of the view in world pixels
+    pub pos: Vec2f,
+    /// Width and height of the screen the camera draws to
+    pub view: (usize, usize),
+    /// How far the target can wander from the middle of the view, in pixels each way,
+    /// before the camera starts following
+    pub dead_zone: (usize, usize),
+    /// How much of the way to where it wants to be the camera moves each frame; 1.0 keeps right up
+    pub smoothing: f32,
+    /// How many frames of the target's velocity to look ahead by, along each axis
+    pub look_ahead: Vec2f,
+    /// The part of the world the view has to stay inside, if any
+    pub bounds: Option<Rect>,
+    /// A corner the view can't go above or left of, for worlds that go on forever the other way
+    pub top_left: Option<Vec2i>,
+    /// How far the view can be thrown off by shaking, in world pixels
+    pub max_shake: f32,
+    /// How much trauma wears off each frame
+    pub recovery: f32,
+    /// The most screen pixels per world pixel to zoom in to
+    pub max_zoom: usize,
+    // How shaken up the camera is, from 0 to 1; the shake grows with its square
+    trauma: f32,
+    shake: Vec2f,
+    seed: u32,
+    // The zoom asked for, and the one in use after fitting everything in view
+    zoom: usize,
+    scale: usize,
+}
+
+impl Camera {
+    /// A camera with its top-left corner at pos that sticks to its target
+    pub fn new(pos: Vec2i, view: (usize, usize)) -> Self {
+        Self {
+            pos: pos.into(),
+            view,
+            dead_zone: (0, 0),
+            smoothing: 1.0,
+            look_ahead: Vec2f(0.0, 0.0),
+            bounds: None,
+            top_left: None,
+            max_shake: 12.0,
+            recovery: 0.02,
+            max_zoom: 4,
+            trauma: 0.0,
+            shake: Vec2f(0.0, 0.0),
+            seed: 1,
+            zoom: 1,
+            scale: 1,
+        }
+    }
+    /// Where to scroll the screen to, shake included
+    pub fn offset(&self) -> Vec2i {
+        (self.pos + self.shake).snap()
+    }
+    /// The part of the world in view, not counting shake
+    pub fn view_rect(&self) -> Rect {
+        Rect::from_pos_size(self.pos.snap(), self.view_size())
+    }
+    /// How much of the world fits in view at the current zoom
+    pub fn view_size(&self) -> (usize, usize) {
+        (self.view.0 / self.scale, self.view.1 / self.scale)
+    }
+    /// Screen pixels per world pixel right now; render the view_size part of the screen
+    /// and then upscale it by this much
+    pub fn scale(&self) -> usize {
+        self.scale
+    }
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+    pub fn handle(&mut self, event: CameraEvent) {
+        let zoom = match event {
+            CameraEvent::Shake(amount) => {
+                self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+                return;
+            }
+            CameraEvent::Zoom(zoom) => zoom,
+            CameraEvent::ZoomIn => self.zoom + 1,
+            CameraEvent::ZoomOut => self.zoom.saturating_sub(1),
+        };
+        self.zoom = zoom.clamp(1, self.max_zoom.max(1));
+        let center = self.pos + self.half_view();
+        self.set_scale(self.zoom, center);
+    }
+    fn half_view(&self) -> Vec2f {
+        let (w, h) = self.view_size();
+        Vec2f(w as f32, h as f32) / 2.0
+    }
+    // Zoom to scale, keeping the view centered where it was
+    fn set_scale(&mut self, scale: usize, center: Vec2f) {
+        self.scale = scale;
+        self.pos = center - self.half_view();
+        self.clamp();
+    }
+    /// Frames all the targets at once, zooming out (never past 1:1) as far as it takes to fit
+    /// their bounding box. If they don't fit even then, it follows just the first.
+    pub fn frame(&mut self, targets: &[Rect], vel: Vec2f) {
+        let all = match targets.iter().copied().reduce(|a, b| a.union(b)) {
+            Some(all) => all,
+            None => return,
+        };
+        let fits = |scale: usize| {
+            all.w as usize <= self.view.0 / scale && all.h as usize <= self.view.1 / scale
+        };
+        let fitting = (1..=self.zoom).rev().find(|s| fits(*s));
+        let scale = fitting.unwrap_or(1);
+        if scale != self.scale {
+            let center = self.pos + self.half_view();
+            self.set_scale(scale, center);
+        }
+        if fitting.is_none() {
+            self.follow(targets[0], vel);
+        } else if targets.len() > 1 {
+            // The dead zone would let someone walk out of the picture
+            let dead_zone = std::mem::take(&mut self.dead_zone);
+            self.follow(all, vel);
+            self.dead_zone = dead_zone;
+        } else {
+            self.follow(all, vel);
+        }
+    }
+    /// Moves one frame's worth towards a target moving at vel
+    pub fn follow(&mut self, target: Rect, vel: Vec2f) {
+        let lead = Vec2f(vel.0 * self.look_ahead.0, vel.1 * self.look_ahead.1);
+        let goal = Vec2f::from(target.center()) + lead;
+        let center = self.pos + self.half_view();
+        // Only move far enough to bring the goal back inside the dead zone
+        let wanted = Vec2f(
+            towards(center.0, goal.0, self.dead_zone.0 as f32),
+            towards(center.1, goal.1, self.dead_zone.1 as f32),
+        );
+        self.pos += (wanted - center) * self.smoothing.clamp(0.0, 1.0);
+        self.clamp();
+        self.settle();
+    }
+    // Shake for this frame, then calm down a little
+    fn settle(&mut self) {
+        let amount = self.max_shake * self.trauma * self.trauma;
+        self.shake = Vec2f(self.noise(), self.noise()) * amount;
+        self.trauma = (self.trauma - self.recovery).max(0.0);
+    }
+    // A repeatable jitter from -1 to 1
+    fn noise(&mut self) -> f32 {
+        // xorshift
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        (self.seed % 2001) as f32 / 1000.0 - 1.0
+    }
+    /// Centers on the target straight away, e.g. after a teleport
+    pub fn look_at(&mut self, target: Rect) {
+        self.pos = Vec2f::from(target.center()) - self.half_view();
+        self.clamp();
+    }
+    // Keep the view inside the bounds, or centered on them if they're smaller than the view
+    fn clamp(&mut self) {
+        if let Some(b) = self.bounds {
+            let (w, h) = self.view_size();
+            self.pos.0 = clamp_axis(self.pos.0, b.x, b.w as usize, w);
+            self.pos.1 = clamp_axis(self.pos.1, b.y, b.h as usize, h);
+        }
+        if let Some(corner) = self.top_left {
+            self.pos.0 = self.pos.0.max(corner.0 as f32);
+            self.pos.1 = self.pos.1.max(corner.1 as f32);
+        }
+    }
+}
+
+// Where the center has to be for goal to be at most slack away from it
+fn towards(center: f32, goal: f32, slack: f32) -> f32 {
+    if goal > center + slack {
+        goal - slack
+    } else if goal < center - slack {
+        goal + slack
+    } else {
+        center
+    }
+}
+
+fn clamp_axis(pos: f32, start: i32, len: usize, view: usize) -> f32 {
+    if len <= view {
+        start as f32 - (view - len) as f32 / 2.0
+    } else {
+        pos.clamp(start as f32, (start + (len - view) as i32) as f32)
+    }
+}
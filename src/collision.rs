@@ -1,5 +1,6 @@
 use crate::tiles::*;
 use crate::types::*;
+use std::collections::HashMap;
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum ColliderID {
@@ -7,29 +8,116 @@ pub enum ColliderID {
     Tile(Tile, Rect),
 }
 
+/// Two colliders touching or overlapping.
+/// The normal points from b towards a: the way to push a to separate them.
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub struct Contact {
-    a: ColliderID,
-    b: ColliderID,
-    mtv: (i32, i32),
+    pub a: ColliderID,
+    pub b: ColliderID,
+    pub normal: Vec2i,
+    // How far a is into b along the normal; 0 if they're just touching
+    pub depth: i32,
 }
+
+/// Collision layers. A body is on some layers and only collides with bodies on the layers in its mask.
+pub const LAYER_PLAYER: u32 = 1 << 0;
+pub const LAYER_ENEMY: u32 = 1 << 1;
+pub const LAYER_ALL: u32 = u32::MAX;
+
+/// How a body responds to being pushed by other bodies
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum BodyKind {
+    /// Pushed apart from other bodies in proportion to the other body's share of their total mass
+    Dynamic { mass: f32 },
+    /// Moves itself (or not at all) but is never pushed; other bodies get pushed all the way out of it
+    Kinematic,
+}
+
+/// A body's physical properties for body-vs-body collision, also used as a component.
+/// Tiles push every body regardless of its kind or layers.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Body {
+    pub kind: BodyKind,
+    pub layers: u32,
+    pub mask: u32,
+}
+
+impl Body {
+    /// Whether two bodies collide: each has to be on a layer the other's mask includes
+    pub fn collides_with(&self, other: &Body) -> bool {
+        self.layers & other.mask != 0 && other.layers & self.mask != 0
+    }
+    // How easily pushed this body is; 0 for kinematic bodies
+    fn inverse_mass(&self) -> f32 {
+        match self.kind {
+            BodyKind::Dynamic { mass } if mass > 0.0 => 1.0 / mass,
+            BodyKind::Dynamic { .. } | BodyKind::Kinematic => 0.0,
+        }
+    }
+}
+
+impl Default for Body {
+    // A unit mass that collides with everything
+    fn default() -> Self {
+        Self {
+            kind: BodyKind::Dynamic { mass: 1.0 },
+            layers: LAYER_ALL,
+            mask: LAYER_ALL,
+        }
+    }
+}
+
+/// Things that happened to bodies during collision, for gameplay code to react to
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum CollisionEvent {
+    /// Standing on (or just pushed up out of) a solid tile
+    Landed { body: usize, tile: Tile },
+    /// Against a solid tile to the side; the normal points away from the wall
+    HitWall {
+        body: usize,
+        tile: Tile,
+        normal: Vec2i,
+    },
+    HitCeiling { body: usize, tile: Tile },
+    /// Overlapping a tile that sends you back to the last checkpoint
+    TouchedHazard { body: usize, tile: Tile },
+    /// Overlapping a ladder or something else climbable
+    OnLadder { body: usize, tile: Tile },
+    /// Overlapping one of the trigger rects passed to detect_triggers
+    EnteredTrigger { body: usize, trigger: usize },
+}
+
+impl CollisionEvent {
+    /// Which body this happened to
+    pub fn body(&self) -> usize {
+        match *self {
+            CollisionEvent::Landed { body, .. }
+            | CollisionEvent::HitWall { body, .. }
+            | CollisionEvent::HitCeiling { body, .. }
+            | CollisionEvent::TouchedHazard { body, .. }
+            | CollisionEvent::OnLadder { body, .. }
+            | CollisionEvent::EnteredTrigger { body, .. } => body,
+        }
+    }
+}
+
 #[allow(dead_code)]
 fn rect_touching(r1: Rect, r2: Rect) -> bool {
     // r1 left is left of r2 right
-    r1.x <= r2.x+r2.w as i32 &&
+    r1.x <= r2.right() &&
         // r2 left is left of r1 right
-        r2.x <= r1.x+r1.w as i32 &&
+        r2.x <= r1.right() &&
         // those two conditions handle the x axis overlap;
         // the next two do the same for the y axis:
-        r1.y <= r2.y+r2.h as i32 &&
-        r2.y <= r1.y+r1.h as i32
+        r1.y <= r2.bottom() &&
+        r2.y <= r1.bottom()
 }
 
 pub fn rect_displacement(r1: Rect, r2: Rect) -> Option<(i32, i32)> {
     // Draw this out on paper to double check, but these quantities
     // will both be positive exactly when the conditions in rect_touching are true.
-    let x_overlap = (r1.x + r1.w as i32).min(r2.x + r2.w as i32) - r1.x.max(r2.x);
-    let y_overlap = (r1.y + r1.h as i32).min(r2.y + r2.h as i32) - r1.y.max(r2.y);
+    let x_overlap = r1.right().min(r2.right()) - r1.x.max(r2.x);
+    let y_overlap = r1.bottom().min(r2.bottom()) - r1.y.max(r2.y);
     if x_overlap >= 0 && y_overlap >= 0 {
         // This will return the magnitude of overlap in each axis.
         Some((x_overlap, y_overlap))
@@ -38,277 +126,412 @@ pub fn rect_displacement(r1: Rect, r2: Rect) -> Option<(i32, i32)> {
     }
 }
 
-fn get_map_indx(tl: Vec2i, tr: Vec2i, br: Vec2i, bl: Vec2i, maps: &Vec<Tilemap>) -> usize{
-    let mut result = 0;
-    for (mi, m) in maps.iter().enumerate(){
-        // println!("cur pos {:?}", pos);
-        // println!("map pos {:?}", m.position);
-        if (tl.0 <= m.position.0 + 256 && tl.1 <= m.position.1 + 256) &&
-            (tr.0 <= m.position.0 + 256 && tr.1 <= m.position.1 + 256) &&
-            (br.0 <= m.position.0 + 256 && br.1 <= m.position.1 + 256) &&
-            (bl.0 <= m.position.0 + 256 && bl.1 <= m.position.1 + 256) {
-            result = mi;
-            break
+// The normal and depth separating a from b along whichever axis they overlap least,
+// or None if they're apart or only meet at a corner
+pub fn contact_normal(a: Rect, b: Rect) -> Option<(Vec2i, i32)> {
+    let (x_overlap, y_overlap) = rect_displacement(a, b)?;
+    if x_overlap == 0 && y_overlap == 0 {
+        return None;
+    }
+    let (ac, bc) = (a.center(), b.center());
+    if x_overlap < y_overlap {
+        let dir = if ac.0 < bc.0 { -1 } else { 1 };
+        Some((Vec2i(dir, 0), x_overlap))
+    } else {
+        let dir = if ac.1 < bc.1 { -1 } else { 1 };
+        Some((Vec2i(0, dir), y_overlap))
+    }
+}
+
+// How far a body can sink through the top of a one-way platform and still land on it:
+// a frame's worth of falling at full speed
+const ONE_WAY_DEPTH: i32 = 10;
+// How far a body can sink into a slope's floor and still be pushed up onto it,
+// rather than out of the side as if the slope were a block
+const SLOPE_DEPTH: i32 = TILE_SZ as i32 / 2;
+
+/// The normal and depth separating a body at r from a tile, taking the tile's shape into account.
+/// None if the tile doesn't block the body.
+pub fn tile_contact(r: Rect, tile: Tile, tile_rect: Rect) -> Option<(Vec2i, i32)> {
+    if !tile.solid {
+        return None;
+    }
+    match tile.shape {
+        TileShape::OneWay => {
+            // Only the top edge counts, and only for bodies that haven't sunk far past it
+            let depth = r.bottom() - tile_rect.y;
+            let over = r.x.max(tile_rect.x) < r.right().min(tile_rect.right());
+            if over && (0..=ONE_WAY_DEPTH).contains(&depth) {
+                Some((Vec2i(0, -1), depth))
+            } else {
+                None
+            }
         }
+        TileShape::Slope { .. } => {
+            let x0 = r.x.max(tile_rect.x);
+            let x1 = r.right().min(tile_rect.right());
+            if x0 >= x1 {
+                return None;
+            }
+            // A box rests on the highest bit of floor under it, which is at one end of the span it covers
+            let height = tile
+                .shape
+                .floor_height(x0 - tile_rect.x)
+                .max(tile.shape.floor_height(x1 - tile_rect.x));
+            let depth = r.bottom() - (tile_rect.bottom() - height);
+            if depth < 0 {
+                None
+            } else if depth <= SLOPE_DEPTH && r.bottom() <= tile_rect.bottom() {
+                Some((Vec2i(0, -1), depth))
+            } else {
+                // From below or the tall side, a slope is as solid as a block
+                contact_normal(r, tile_rect)
+            }
+        }
+        shape => contact_normal(r, shape.boxed(tile_rect)?),
     }
-    result
 }
 
 // Here we will be using push() on into, so it can't be a slice
 pub fn gather_contacts(
     positions: &[Vec2i],
     sizes: &[(usize, usize)],
+    bodies: &[Body],
     tilemap: &Vec<Tilemap>,
     into: &mut Vec<Contact>,
-    num_jumps: &mut usize,
-    restart: &mut bool,
+    events: &mut Vec<CollisionEvent>,
 ) {
-    // collide mobiles against mobiles
-    for (ai, (apos, asize)) in (positions.iter().zip(sizes.iter())).enumerate() {
-        for (bi, (bpos, bsize)) in (positions.iter().zip(sizes.iter()))
-            .enumerate()
-            .skip(ai + 1)
-        {
-            let arect = Rect {
-                x: apos.0,
-                y: apos.1,
-                w: asize.0 as u16,
-                h: asize.1 as u16,
-            };
-            let brect = Rect {
-                x: bpos.0,
-                y: bpos.1,
-                w: bsize.0 as u16,
-                h: bsize.1 as u16,
-            };
-            if let Some(disp) = rect_displacement(arect, brect) {
-                into.push(Contact {
-                    a: ColliderID::Dynamic(ai),
-                    b: ColliderID::Dynamic(bi),
-                    mtv: disp,
-                });
-            }
+    // collide mobiles against mobiles, but only the ones near enough to touch
+    for (ai, bi) in broad_phase(positions, sizes) {
+        if !bodies[ai].collides_with(&bodies[bi]) {
+            continue;
+        }
+        let arect = Rect::from_pos_size(positions[ai], sizes[ai]);
+        let brect = Rect::from_pos_size(positions[bi], sizes[bi]);
+        if let Some((normal, depth)) = contact_normal(arect, brect) {
+            into.push(Contact {
+                a: ColliderID::Dynamic(ai),
+                b: ColliderID::Dynamic(bi),
+                normal,
+                depth,
+            });
         }
     }
     // collide tiles
     for (ei, (pos, size)) in (positions.iter().zip(sizes.iter())).enumerate() {
-        // get corner positions
-        let tl = Vec2i(pos.0, pos.1);
-        let tr = Vec2i(pos.0 + size.0 as i32, pos.1);
-        let br = Vec2i(pos.0 + size.0 as i32, pos.1 + size.0 as i32);
-        let bl = Vec2i(pos.0, pos.1 + size.0 as i32);
-
-        // let map = &levels[_game.level].0;
-        let map_indx = get_map_indx(tl, tr, br, bl, &tilemap);
-        // println!("map indx: {:}", map_indx);
-        let map = &tilemap[map_indx];
-        let (ttl, tlrect) = match map.tile_and_bounds_at(tl) {
-            Some((ttl, tlrect)) => (ttl, tlrect),
-            _ => (Tile{solid: false, jump_reset: false, restart: false}, Rect{x: 0, y: 0, w: 0, h: 0}),
-        };
-        let (ttr, trrect) = match map.tile_and_bounds_at(tr) {
-            Some((ttr, trrect)) => (ttr, trrect),
-            _ => (Tile{solid: false, jump_reset: false, restart: false}, Rect{x: 0, y: 0, w: 0, h: 0}),
-        };
-        let (btl, blrect) = match map.tile_and_bounds_at(bl) {
-            Some((btl, blrect)) => (btl, blrect),
-            _ => (Tile{solid: false, jump_reset: false, restart: false}, Rect{x: 0, y: 0, w: 0, h: 0}),
-        };
-        let (btr, brrect) = match map.tile_and_bounds_at(br) {
-            Some((btr, brrect)) => (btr, brrect),
-            _ => (Tile{solid: false, jump_reset: false, restart: false}, Rect{x: 0, y: 0, w: 0, h: 0}),
-        };
-        // println!("touching top left  {:?}", (ttl, tlrect));
-        // println!("touching bottom right  {:?}", (btr, brrect));
-
-        let sprite_rect = Rect {
-            x: pos.0,
-            y: pos.1,
-            w: size.0 as u16,
-            h: size.1 as u16,
-        };
-        if ttl.solid {
-            if let Some(displacement) = rect_displacement(sprite_rect, tlrect) {
-                // make contact out of displacment
-                // define contact
-                into.push(Contact {
-                    a: ColliderID::Dynamic(ei),
-                    b: ColliderID::Tile(ttl, tlrect),
-                    mtv: displacement,
-                });
+        let sprite_rect = Rect::from_pos_size(*pos, *size);
+        // Take tiles just touching the body too, so standing on the ground counts
+        for (tile, tile_rect) in tiles_overlapping(tilemap, sprite_rect.expand(1)) {
+            if tile.solid {
+                if let Some((normal, depth)) = tile_contact(sprite_rect, tile, tile_rect) {
+                    into.push(Contact {
+                        a: ColliderID::Dynamic(ei),
+                        b: ColliderID::Tile(tile, tile_rect),
+                        normal,
+                        depth,
+                    });
+                }
             }
-        }
-        if ttr.solid {
-            if let Some(displacement) = rect_displacement(sprite_rect, trrect) {
-                // make contact out of displacment
-                // define contact
-                into.push(Contact {
-                    a: ColliderID::Dynamic(ei),
-                    b: ColliderID::Tile(ttr, trrect),
-                    mtv: displacement,
-                });
+            if tile.restart && sprite_rect.intersects(tile_rect) {
+                events.push(CollisionEvent::TouchedHazard { body: ei, tile });
             }
-        }
-        if btl.solid {
-            if let Some(displacement) = rect_displacement(sprite_rect, blrect) {
-                // make contact out of displacment
-                // define contact
-                into.push(Contact {
-                    a: ColliderID::Dynamic(ei),
-                    b: ColliderID::Tile(btl, blrect),
-                    mtv: displacement,
-                });
-            }
-        }
-        if btr.solid {
-            if let Some(displacement) = rect_displacement(sprite_rect, brrect) {
-                // make contact out of displacment
-                // define contact
-                into.push(Contact {
-                    a: ColliderID::Dynamic(ei),
-                    b: ColliderID::Tile(btr, brrect),
-                    mtv: displacement,
-                });
+            if tile.climbable() && sprite_rect.intersects(tile_rect) {
+                events.push(CollisionEvent::OnLadder { body: ei, tile });
             }
         }
+    }
+}
 
-        if ttl.solid {
-            // println!("jump reset");
-        }
-        if ttr.solid {
-            // println!("touching top right");
-            // println!("jump reset ttr");
+// Grid cells are a couple of tiles across, about the size of the bodies in our games
+const BROAD_PHASE_CELL: usize = 2 * TILE_SZ;
+
+/// A uniform grid of buckets, each holding the bodies overlapping that cell.
+/// Bodies that touch always share a cell, so only bodies sharing a cell need checking against each other.
+pub struct SpatialHash {
+    cell: i32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: usize) -> Self {
+        Self {
+            cell: cell_size.max(1) as i32,
+            cells: HashMap::new(),
         }
-        if btl.jump_reset {
-            // println!("touching bottom left");
-            // println!("jump reset btl");
-            *num_jumps = 0;
+    }
+    /// Empties the grid so it can be refilled, keeping its buckets' memory
+    pub fn clear(&mut self) {
+        self.cells.values_mut().for_each(Vec::clear);
+    }
+    pub fn insert(&mut self, id: usize, r: Rect) {
+        // Rects include their right and bottom edges here so that bodies just touching share a cell
+        let (x0, y0) = (r.x.div_euclid(self.cell), r.y.div_euclid(self.cell));
+        let (x1, y1) = (r.right().div_euclid(self.cell), r.bottom().div_euclid(self.cell));
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                self.cells.entry((x, y)).or_default().push(id);
+            }
         }
-        if btr.jump_reset {
-            // println!("touching buttom right");
-            // println!("jump reset btr");
-            *num_jumps = 0;
+    }
+    /// Every pair of ids sharing a cell, once each, lower id first and in order
+    pub fn pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = vec![];
+        for ids in self.cells.values() {
+            for (i, a) in ids.iter().enumerate() {
+                for b in &ids[i + 1..] {
+                    pairs.push((*a.min(b), *a.max(b)));
+                }
+            }
         }
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+}
 
-        if ttl.restart {
-            *restart = true;
+/// The pairs of bodies that might be touching; a superset of the ones that are
+pub fn broad_phase(positions: &[Vec2i], sizes: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut grid = SpatialHash::new(BROAD_PHASE_CELL);
+    for (i, (pos, size)) in positions.iter().zip(sizes.iter()).enumerate() {
+        grid.insert(i, Rect::from_pos_size(*pos, *size));
+    }
+    grid.pairs()
+}
+
+/// Reports every body overlapping each trigger rect (checkpoints, portals, and so on)
+pub fn detect_triggers(
+    positions: &[Vec2i],
+    sizes: &[(usize, usize)],
+    triggers: &[Rect],
+    events: &mut Vec<CollisionEvent>,
+) {
+    for (body, (pos, size)) in positions.iter().zip(sizes.iter()).enumerate() {
+        let r = Rect::from_pos_size(*pos, *size);
+        for (trigger, t) in triggers.iter().enumerate() {
+            if r.intersects(*t) {
+                events.push(CollisionEvent::EnteredTrigger { body, trigger });
+            }
         }
     }
 }
 
-#[allow(unused_variables)]
 pub fn restitute(
     positions: &mut [Vec2i],
     sizes: &[(usize, usize)],
+    bodies: &[Body],
     velocities: &mut [Vec2i],
-    camera: &mut Vec2i,
     tilemap: &Vec<Tilemap>,
     contacts: &mut [Contact],
+    events: &mut Vec<CollisionEvent>,
 ) {
-    // handle restitution of dynamics against dynamics and dynamics against statics wrt contacts.
-    // You could instead make contacts `Vec<Contact>` if you think you might remove contacts.
-    // You could also add an additional parameter, a slice or vec representing how far we've displaced each dynamic, to avoid allocations if you track a vec of how far things have been moved.
-    // You might also want to pass in another &mut Vec<Contact> to be filled in with "real" touches that actually happened.
-    contacts.sort_unstable_by_key(|c| -(c.mtv.0 * c.mtv.0 + c.mtv.1 * c.mtv.1));
-    // Keep going!  Note that you can assume every contact has a dynamic object in .a.
-    // You might decide to tweak the interface of this function to separately take dynamic-static and dynamic-dynamic contacts, to avoid a branch inside of the response calculation.
-    // Or, you might decide to calculate signed mtvs taking direction into account instead of the unsigned displacements from rect_displacement up above.  Or calculate one MTV per involved entity, then apply displacements to both objects during restitution (sorting by the max or the sum of their magnitudes)
-    // let mut disp = vec![(0, 0); dynamics.len()];
-    // fix double-restitution issue
+    // Resolve the deepest contacts first; pushing out of those often resolves the shallower ones too.
+    contacts.sort_unstable_by_key(|c| -c.depth);
+    // Separate bodies from each other first, so that tiles get the last word
+    let mut pushed = vec![false; positions.len()];
     for c in contacts.iter() {
-        // let mtv = i32::min(c1.mtv.0, c1.mtv.1);
-        // let mut collided_wall: <Wall>
-        match (c.a, c.b) {
-            (ColliderID::Dynamic(ai), ColliderID::Dynamic(bi)) => {
-                // horizontal < vertical
-                let horizontal_mtv = c.mtv.0;
-                let vertical_mtv = c.mtv.1;
-                let mut a_rect = Rect {
-                    x: positions[ai].0,
-                    y: positions[ai].1,
-                    w: sizes[ai].0 as u16,
-                    h: sizes[ai].1 as u16,
-                };
-                let b_rect = Rect {
-                    x: positions[bi].0,
-                    y: positions[bi].1,
-                    w: sizes[bi].0 as u16,
-                    h: sizes[bi].1 as u16,
-                };
-                let disp = rect_displacement(a_rect, b_rect).unwrap();
-                if disp.0 == horizontal_mtv || disp.1 == vertical_mtv {
-                    continue;
-                }
-                if horizontal_mtv < vertical_mtv {
-                    if a_rect.x < b_rect.x {
-                        a_rect.x -= horizontal_mtv;
-                    // disp[player_indx] = (horizontal_mtv, 0)
-                    } else {
-                        a_rect.x += horizontal_mtv;
-                        // disp[player_indx] = (horizontal_mtv, 0)
-                    }
-                } else {
-                    if a_rect.y < b_rect.y {
-                        // println!("Box touched down side{:?}", c);
-                        a_rect.y -= vertical_mtv;
-                    // disp[player_indx] = (0, vertical_mtv)
-                    } else {
-                        // println!("Box touched up side{:?}", c);
-                        a_rect.y += vertical_mtv;
-                        // disp[player_indx] = (0, vertical_mtv)
-                    }
-                }
+        if let (ColliderID::Dynamic(ai), ColliderID::Dynamic(bi)) = (c.a, c.b) {
+            let a_rect = Rect::from_pos_size(positions[ai], sizes[ai]);
+            let b_rect = Rect::from_pos_size(positions[bi], sizes[bi]);
+            let (normal, depth) = match contact_normal(a_rect, b_rect) {
+                Some(nd) => nd,
+                None => continue,
+            };
+            // Each body moves by its share of the pair's inverse mass
+            let (a_inv, b_inv) = (bodies[ai].inverse_mass(), bodies[bi].inverse_mass());
+            if a_inv + b_inv == 0.0 {
+                continue;
             }
-            (ColliderID::Dynamic(ai), ColliderID::Tile(_bt, br)) => {
-                // println!("INSIDE Dynamic tile case");
-                // let horizontal_mtv = c.mtv.0;
-                // let vertical_mtv = c.mtv.1;
-                let a_rect = Rect {
-                    x: positions[ai].0,
-                    y: positions[ai].1,
-                    w: sizes[ai].0 as u16,
-                    h: sizes[ai].1 as u16,
-                };
-                if let Some((horizontal_mtv, vertical_mtv)) = rect_displacement(a_rect, br) {
-                    if horizontal_mtv < vertical_mtv {
-                        if a_rect.x < br.x {
-                            // println!("Box touched left side{:?}", c);
-                            positions[ai].0 -= horizontal_mtv;
-                            velocities[ai].0 = 0;
-                            camera.0 -= horizontal_mtv;
-                        //
-                        // disp[player_indx] = (horizontal_mtv, 0)
-                        } else {
-                            // println!("Box touched right side{:?}", c);
-                            positions[ai].0 += horizontal_mtv;
-                            velocities[ai].0 = 0;
-                            camera.0 += horizontal_mtv;
-                            // camera.0 = a_rect.x;
-                            // disp[player_indx] = (horizontal_mtv, 0)
-                        }
-                    } else {
-                        if a_rect.y < br.y {
-                            // println!("Box touched down side{:?}", c);
-                            positions[ai].1 -= vertical_mtv;
-                            velocities[ai].1 = 0;
-                            camera.1 -= vertical_mtv;
-                        // camera.1 = a_rect.y;
-                        // disp[player_indx] = (0, vertical_mtv)
-                        } else {
-                            // println!("Box touched up side{:?}", c);
-                            positions[ai].1 += vertical_mtv;
-                            velocities[ai].1 = 0;
-                            camera.1 += vertical_mtv;
-                            // camera.1 = a_rect.y;
-                            // disp[player_indx] = (0, vertical_mtv)
-                        }
-                    }
-                }
-                // if disp.0 == horizontal_mtv || disp.1 == vertical_mtv {
-                //     continue;
-                // }
+            let a_push = (depth as f32 * a_inv / (a_inv + b_inv)).round() as i32;
+            let b_push = depth - a_push;
+            positions[ai] += normal * a_push;
+            positions[bi] -= normal * b_push;
+            pushed[ai] |= a_push != 0;
+            pushed[bi] |= b_push != 0;
+            // Stop each one moving into the other
+            if a_inv > 0.0 {
+                stop_along(&mut velocities[ai], normal);
             }
-            _ => {}
-        };
+            if b_inv > 0.0 {
+                stop_along(&mut velocities[bi], -normal);
+            }
+        }
     }
+    // Bodies shoved into tiles they weren't touching before need contacts for those too
+    let mut tile_contacts: Vec<Contact> = contacts
+        .iter()
+        .filter(|c| matches!(c.b, ColliderID::Tile(..)))
+        .copied()
+        .collect();
+    for ai in (0..positions.len()).filter(|ai| pushed[*ai]) {
+        let a_rect = Rect::from_pos_size(positions[ai], sizes[ai]);
+        for (tile, tile_rect) in tiles_overlapping(tilemap, a_rect) {
+            let (a, b) = (ColliderID::Dynamic(ai), ColliderID::Tile(tile, tile_rect));
+            if tile_contacts.iter().any(|c| c.a == a && c.b == b) {
+                continue;
+            }
+            if let Some((normal, depth)) = tile_contact(a_rect, tile, tile_rect) {
+                tile_contacts.push(Contact {
+                    a,
+                    b,
+                    normal,
+                    depth,
+                });
+            }
+        }
+    }
+    tile_contacts.sort_by_key(|c| -c.depth);
+    for c in tile_contacts {
+        if let (ColliderID::Dynamic(ai), ColliderID::Tile(tile, br)) = (c.a, c.b) {
+            // Bodies on their way up pass through one-way platforms
+            if tile.shape == TileShape::OneWay && velocities[ai].1 < 0 {
+                continue;
+            }
+            // Earlier pushes may have moved us, so work out where we are relative to this tile now
+            let a_rect = Rect::from_pos_size(positions[ai], sizes[ai]);
+            let (normal, depth) = match tile_contact(a_rect, tile, br) {
+                Some(nd) => nd,
+                None => continue,
+            };
+            positions[ai] += normal * depth;
+            stop_along(&mut velocities[ai], normal);
+            events.push(match normal {
+                Vec2i(0, -1) => CollisionEvent::Landed { body: ai, tile },
+                Vec2i(0, 1) => CollisionEvent::HitCeiling { body: ai, tile },
+                _ => CollisionEvent::HitWall {
+                    body: ai,
+                    tile,
+                    normal,
+                },
+            });
+        }
+    }
+}
+
+// Stop moving against the normal, but leave movement along it alone
+fn stop_along(vel: &mut Vec2i, normal: Vec2i) {
+    if vel.0 * normal.0 < 0 {
+        vel.0 = 0;
+    }
+    if vel.1 * normal.1 < 0 {
+        vel.1 = 0;
+    }
+}
+
+/// A solid tile a sweep ran into. The normal points away from the tile, towards the mover.
+/// The rect is the part of the tile that blocked it.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct SweepHit {
+    pub normal: Vec2i,
+    pub tile: Tile,
+    pub rect: Rect,
+}
+
+/// Where a swept body ended up, and what stopped it on the way
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Sweep {
+    pub pos: Vec2i,
+    pub hits: Vec<SweepHit>,
+}
+
+impl Sweep {
+    // Did anything stop us moving along x (or y)?
+    pub fn blocked_x(&self) -> bool {
+        self.hits.iter().any(|h| h.normal.0 != 0)
+    }
+    pub fn blocked_y(&self) -> bool {
+        self.hits.iter().any(|h| h.normal.1 != 0)
+    }
+}
+
+// Every tile in any of the maps that overlaps r
+pub fn tiles_overlapping(tilemap: &[Tilemap], r: Rect) -> impl Iterator<Item = (Tile, Rect)> + '_ {
+    tilemap.iter().flat_map(move |m| m.tiles_in(r))
+}
+
+/// Moves a body along vel against the tilemaps, x first and then y,
+/// stopping each axis at the first solid tile in the way.
+/// Unlike gather_contacts this can't skip past thin walls however fast the body goes.
+pub fn sweep_tiles(pos: Vec2i, size: (usize, usize), vel: Vec2i, tilemap: &[Tilemap]) -> Sweep {
+    let mut r = Rect::from_pos_size(pos, size);
+    let mut hits = vec![];
+    r.x = sweep_axis(r, vel.0, true, tilemap, &mut hits);
+    r.y = sweep_axis(r, vel.1, false, tilemap, &mut hits);
+    Sweep { pos: r.pos(), hits }
+}
+
+// Where r ends up after moving d along one axis; records the tiles that stopped it
+fn sweep_axis(
+    r: Rect,
+    d: i32,
+    horizontal: bool,
+    tilemap: &[Tilemap],
+    hits: &mut Vec<SweepHit>,
+) -> i32 {
+    let start = if horizontal { r.x } else { r.y };
+    if d == 0 {
+        return start;
+    }
+    // The strip r passes through on its way, not counting where it already is
+    let len = d.unsigned_abs() as u16;
+    let strip = match (horizontal, d > 0) {
+        (true, true) => Rect {
+            x: r.right(),
+            w: len,
+            ..r
+        },
+        (true, false) => Rect {
+            x: r.x + d,
+            w: len,
+            ..r
+        },
+        (false, true) => Rect {
+            y: r.bottom(),
+            h: len,
+            ..r
+        },
+        (false, false) => Rect {
+            y: r.y + d,
+            h: len,
+            ..r
+        },
+    };
+    // Where a solid tile would stop us; never behind where we started, in case we're already stuck
+    let stop_at = |t: &Rect| match (horizontal, d > 0) {
+        (true, true) => (t.x - r.w as i32).max(start),
+        (true, false) => t.right().min(start),
+        (false, true) => (t.y - r.h as i32).max(start),
+        (false, false) => t.bottom().min(start),
+    };
+    // Slopes are left for restitute to push bodies up onto
+    let solids: Vec<(Tile, Rect)> = tiles_overlapping(tilemap, strip)
+        .filter(|(t, _)| t.solid)
+        .filter_map(|(t, rect)| match t.shape {
+            // One-way platforms only stop bodies falling onto them from above
+            TileShape::OneWay if !horizontal && d > 0 && r.bottom() <= rect.y => {
+                Some((t, Rect { h: 1, ..rect }))
+            }
+            _ => Some((t, t.shape.boxed(rect)?)),
+        })
+        .filter(|(_, rect)| rect.intersects(strip))
+        .collect();
+    let end = solids
+        .iter()
+        .map(|(_, t)| stop_at(t))
+        .fold(
+            start + d,
+            |end, stop| if d > 0 { end.min(stop) } else { end.max(stop) },
+        );
+    let normal = match (horizontal, d > 0) {
+        (true, true) => Vec2i(-1, 0),
+        (true, false) => Vec2i(1, 0),
+        (false, true) => Vec2i(0, -1),
+        (false, false) => Vec2i(0, 1),
+    };
+    hits.extend(
+        solids
+            .into_iter()
+            .filter(|(_, t)| stop_at(t) == end)
+            .map(|(tile, rect)| SweepHit { normal, tile, rect }),
+    );
+    end
 }
@@ -0,0 +1,62 @@
+// Components shared by the games' entities
+use crate::animation::{Animation, AnimationState};
+use crate::collision::{Body, LAYER_ALL, LAYER_ENEMY, LAYER_PLAYER};
+use crate::ecs::{Entity, World};
+use crate::resources::Handle;
+use crate::texture::Texture;
+use crate::types::{Player, Vec2f};
+use std::rc::Rc;
+
+/// Top-left corner in world pixels
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Position(pub Vec2f);
+
+/// Pixels moved per frame
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Velocity(pub Vec2f);
+
+/// Width and height of the collision box
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Size(pub usize, pub usize);
+
+/// What to draw for an entity
+pub struct Sprite {
+    pub texture: Handle<Texture>,
+    pub anim: AnimationState,
+}
+
+/// Marks the entity driven by a (local) player, with their network id.
+/// A guest on the same keyboard plays under the host's.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PlayerId(pub i32);
+
+/// Spawns the local player's entity from their saved/networked record
+pub fn spawn_player(
+    world: &mut World,
+    player: &Player,
+    texture: &Handle<Texture>,
+    anim: &Rc<Animation>,
+) -> Entity {
+    let e = world.spawn();
+    world.insert(e, Position(player.pos));
+    world.insert(e, Velocity(player.vel));
+    world.insert(e, Size(32, 32));
+    world.insert(
+        e,
+        Sprite {
+            texture: texture.clone(),
+            anim: anim.start(),
+        },
+    );
+    // Enemies hurt the player rather than shoving them, so the two pass through each other
+    world.insert(
+        e,
+        Body {
+            layers: LAYER_PLAYER,
+            mask: LAYER_ALL & !LAYER_ENEMY,
+            ..Body::default()
+        },
+    );
+    world.insert(e, PlayerId(player.id));
+    e
+}
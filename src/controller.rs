@@ -0,0 +1,197 @@
+// A reusable platformer character controller.
+// It works out a sub-pixel velocity from input each frame, and learns whether it's
+// standing on anything from where tile collision puts it afterwards.
+use crate::types::Vec2f;
+
+/// Tuning for a platformer controller. Speeds are in pixels per frame,
+/// accelerations in pixels per frame per frame, and timers in frames.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PlatformerConfig {
+    pub gravity: f32,
+    pub max_fall: f32,
+    pub max_speed: f32,
+    pub accel: f32,
+    pub air_accel: f32,
+    // How quickly we slow down on the ground with no input
+    pub friction: f32,
+    pub jump_speed: f32,
+    // Upward speed is multiplied by this when jump is let go early
+    pub jump_cut: f32,
+    pub max_jumps: usize,
+    // How long after walking off a ledge we can still jump as if grounded
+    pub coyote_frames: usize,
+    // How long a jump press is remembered before we're able to jump
+    pub jump_buffer_frames: usize,
+    pub climb_speed: f32,
+}
+
+impl Default for PlatformerConfig {
+    fn default() -> Self {
+        Self {
+            gravity: 1.0,
+            max_fall: 10.0,
+            max_speed: 8.0,
+            accel: 1.5,
+            air_accel: 1.0,
+            friction: 1.5,
+            jump_speed: 15.0,
+            jump_cut: 0.5,
+            max_jumps: 2,
+            coyote_frames: 6,
+            jump_buffer_frames: 6,
+            climb_speed: 3.0,
+        }
+    }
+}
+
+/// What the player is asking the controller to do this frame
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct PlatformerInput {
+    pub left: bool,
+    pub right: bool,
+    pub jump_pressed: bool,
+    pub jump_held: bool,
+    // Climbing
+    pub up: bool,
+    pub down: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct PlatformerController {
+    pub config: PlatformerConfig,
+    vel: Vec2f,
+    grounded: bool,
+    coyote: usize,
+    buffer: usize,
+    jumping: bool,
+    on_ladder: bool,
+    climbing: bool,
+}
+
+impl PlatformerController {
+    pub fn new(config: PlatformerConfig) -> Self {
+        Self {
+            config,
+            vel: Vec2f(0.0, 0.0),
+            grounded: false,
+            coyote: 0,
+            buffer: 0,
+            jumping: false,
+            on_ladder: false,
+            climbing: false,
+        }
+    }
+    pub fn grounded(&self) -> bool {
+        self.grounded
+    }
+    pub fn velocity(&self) -> Vec2f {
+        self.vel
+    }
+    pub fn climbing(&self) -> bool {
+        self.climbing
+    }
+    /// Tell the controller whether it's in front of something it can climb
+    pub fn set_on_ladder(&mut self, on_ladder: bool) {
+        self.on_ladder = on_ladder;
+        if !on_ladder {
+            self.climbing = false;
+        }
+    }
+    /// Forget all motion, e.g. after teleporting
+    pub fn stop(&mut self) {
+        self.vel = Vec2f(0.0, 0.0);
+        self.jumping = false;
+        self.buffer = 0;
+    }
+    /// Runs one frame of movement and returns how far to move.
+    /// `jumps` counts jumps used since the jump counter was last reset.
+    pub fn step(&mut self, input: PlatformerInput, jumps: &mut usize) -> Vec2f {
+        let c = self.config;
+        let dir = input.right as i32 - input.left as i32;
+        // Climb: up or down grabs a ladder, and a jump that isn't also up lets go
+        if self.on_ladder && (input.up || input.down) {
+            self.climbing = true;
+        }
+        if self.climbing && input.jump_pressed && !input.up {
+            self.climbing = false;
+        }
+        if self.climbing {
+            let climb = input.down as i32 - input.up as i32;
+            self.vel = Vec2f(dir as f32, climb as f32) * c.climb_speed;
+            *jumps = 0;
+            // Jumping off a ladder works like jumping off the ground
+            self.coyote = c.coyote_frames;
+            self.buffer = 0;
+            self.jumping = false;
+            return self.vel;
+        }
+
+        // Run
+        let accel = if self.grounded { c.accel } else { c.air_accel };
+        if dir != 0 {
+            self.vel.0 = (self.vel.0 + dir as f32 * accel).clamp(-c.max_speed, c.max_speed);
+        } else if self.grounded {
+            self.vel.0 = approach(self.vel.0, 0.0, c.friction);
+        }
+
+        // Jump
+        if input.jump_pressed {
+            self.buffer = c.jump_buffer_frames.max(1);
+        }
+        if !self.grounded && self.coyote == 0 && *jumps == 0 {
+            // Walked off a ledge and waited too long: the first jump is gone
+            *jumps = 1;
+        }
+        if self.buffer > 0 && *jumps < c.max_jumps {
+            self.vel.1 = -c.jump_speed;
+            *jumps += 1;
+            self.buffer = 0;
+            self.coyote = 0;
+            self.grounded = false;
+            self.jumping = true;
+        }
+        if self.jumping && !input.jump_held && self.vel.1 < 0.0 {
+            self.vel.1 *= c.jump_cut;
+            self.jumping = false;
+        }
+        self.buffer = self.buffer.saturating_sub(1);
+        self.coyote = self.coyote.saturating_sub(1);
+
+        // Fall
+        self.vel.1 = (self.vel.1 + c.gravity).min(c.max_fall);
+        if self.vel.1 >= 0.0 {
+            self.jumping = false;
+        }
+        self.vel
+    }
+    /// Tell the controller where it wanted to end up and where collision put it
+    pub fn resolve(&mut self, wanted: Vec2f, actual: Vec2f) {
+        if actual.0 != wanted.0 {
+            self.vel.0 = 0.0;
+        }
+        if actual.1 != wanted.1 {
+            // Landed or bumped our head
+            self.vel.1 = 0.0;
+            self.jumping = false;
+        }
+        // Pushed up means something's underfoot; once we leave it the coyote timer runs down
+        self.grounded = actual.1 < wanted.1;
+        if self.grounded {
+            self.coyote = self.config.coyote_frames;
+        }
+    }
+}
+
+impl Default for PlatformerController {
+    fn default() -> Self {
+        Self::new(PlatformerConfig::default())
+    }
+}
+
+fn approach(from: f32, to: f32, by: f32) -> f32 {
+    if from < to {
+        (from + by).min(to)
+    } else {
+        (from - by).max(to)
+    }
+}
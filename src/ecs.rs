@@ -0,0 +1,237 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// A handle to something in the world.
+/// The generation lets us tell a live entity from a despawned one that reused its slot.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+impl Entity {
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+}
+
+// One component type's data, indexed by entity index.
+// A Vec of Options is plenty for the number of entities we have.
+struct Storage<T> {
+    items: Vec<Option<T>>,
+}
+
+// Lets the world drop components on despawn without knowing their types
+trait AnyStorage {
+    fn remove_index(&mut self, index: usize);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> AnyStorage for Storage<T> {
+    fn remove_index(&mut self, index: usize) {
+        if let Some(slot) = self.items.get_mut(index) {
+            *slot = None;
+        }
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Holds every entity and all of their components
+#[derive(Default)]
+pub struct World {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+    storages: HashMap<TypeId, Box<dyn AnyStorage>>,
+}
+
+/// Anything that can be run over the world, like a physics step or an AI update
+pub trait System {
+    fn run(&mut self, world: &mut World);
+}
+
+impl<F: FnMut(&mut World)> System for F {
+    fn run(&mut self, world: &mut World) {
+        self(world)
+    }
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn spawn(&mut self) -> Entity {
+        match self.free.pop() {
+            Some(index) => {
+                self.alive[index as usize] = true;
+                Entity {
+                    index,
+                    generation: self.generations[index as usize],
+                }
+            }
+            None => {
+                self.generations.push(0);
+                self.alive.push(true);
+                Entity {
+                    index: (self.generations.len() - 1) as u32,
+                    generation: 0,
+                }
+            }
+        }
+    }
+    /// Removes an entity and all its components; returns false if it was already gone
+    pub fn despawn(&mut self, e: Entity) -> bool {
+        if !self.is_alive(e) {
+            return false;
+        }
+        for storage in self.storages.values_mut() {
+            storage.remove_index(e.index());
+        }
+        self.alive[e.index()] = false;
+        // Bump the generation so old handles to this slot stop working
+        self.generations[e.index()] += 1;
+        self.free.push(e.index);
+        true
+    }
+    pub fn is_alive(&self, e: Entity) -> bool {
+        e.index() < self.alive.len()
+            && self.alive[e.index()]
+            && self.generations[e.index()] == e.generation
+    }
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.alive
+            .iter()
+            .enumerate()
+            .filter(|(_, alive)| **alive)
+            .map(move |(i, _)| self.entity_at(i))
+    }
+    pub fn len(&self) -> usize {
+        self.alive.iter().filter(|a| **a).count()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Adds a component to an entity, replacing any it already had of that type
+    pub fn insert<T: 'static>(&mut self, e: Entity, component: T) {
+        assert!(self.is_alive(e), "Inserting a component on a dead entity");
+        let storage = self.storage_or_insert::<T>();
+        if storage.items.len() <= e.index() {
+            storage.items.resize_with(e.index() + 1, || None);
+        }
+        storage.items[e.index()] = Some(component);
+    }
+    pub fn remove<T: 'static>(&mut self, e: Entity) -> Option<T> {
+        if !self.is_alive(e) {
+            return None;
+        }
+        self.storage_mut::<T>()?.items.get_mut(e.index())?.take()
+    }
+    pub fn get<T: 'static>(&self, e: Entity) -> Option<&T> {
+        if !self.is_alive(e) {
+            return None;
+        }
+        self.storage::<T>()?.items.get(e.index())?.as_ref()
+    }
+    pub fn get_mut<T: 'static>(&mut self, e: Entity) -> Option<&mut T> {
+        if !self.is_alive(e) {
+            return None;
+        }
+        self.storage_mut::<T>()?.items.get_mut(e.index())?.as_mut()
+    }
+    pub fn has<T: 'static>(&self, e: Entity) -> bool {
+        self.get::<T>(e).is_some()
+    }
+    /// Every entity with a T, along with its T
+    pub fn query<T: 'static>(&self) -> impl Iterator<Item = (Entity, &T)> + '_ {
+        self.storage::<T>().into_iter().flat_map(move |s| {
+            s.items
+                .iter()
+                .enumerate()
+                .filter_map(move |(i, c)| c.as_ref().map(|c| (self.entity_at(i), c)))
+        })
+    }
+    pub fn query_mut<T: 'static>(&mut self) -> impl Iterator<Item = (Entity, &mut T)> + '_ {
+        let generations = &self.generations;
+        self.storages
+            .get_mut(&TypeId::of::<T>())
+            .map(|s| s.as_any_mut().downcast_mut::<Storage<T>>().unwrap())
+            .into_iter()
+            .flat_map(move |s| {
+                s.items.iter_mut().enumerate().filter_map(move |(i, c)| {
+                    c.as_mut().map(|c| {
+                        (
+                            Entity {
+                                index: i as u32,
+                                generation: generations[i],
+                            },
+                            c,
+                        )
+                    })
+                })
+            })
+    }
+    /// Every entity with both an A and a B
+    pub fn query2<A: 'static, B: 'static>(&self) -> impl Iterator<Item = (Entity, &A, &B)> + '_ {
+        self.query::<A>()
+            .filter_map(move |(e, a)| self.get::<B>(e).map(|b| (e, a, b)))
+    }
+    /// Calls f with mutable access to both components of every entity that has an A and a B
+    pub fn query2_mut<A: 'static, B: 'static>(
+        &mut self,
+        mut f: impl FnMut(Entity, &mut A, &mut B),
+    ) {
+        assert_ne!(
+            TypeId::of::<A>(),
+            TypeId::of::<B>(),
+            "query2_mut needs two different component types"
+        );
+        // Take B's storage out of the map for a moment so we can borrow both at once
+        let mut b_storage = match self.storages.remove(&TypeId::of::<B>()) {
+            Some(s) => s,
+            None => return,
+        };
+        {
+            let bs = b_storage.as_any_mut().downcast_mut::<Storage<B>>().unwrap();
+            for (e, a) in self.query_mut::<A>() {
+                if let Some(Some(b)) = bs.items.get_mut(e.index()) {
+                    f(e, a, b);
+                }
+            }
+        }
+        self.storages.insert(TypeId::of::<B>(), b_storage);
+    }
+    pub fn run(&mut self, system: &mut dyn System) {
+        system.run(self);
+    }
+
+    fn entity_at(&self, index: usize) -> Entity {
+        Entity {
+            index: index as u32,
+            generation: self.generations[index],
+        }
+    }
+    fn storage<T: 'static>(&self) -> Option<&Storage<T>> {
+        self.storages
+            .get(&TypeId::of::<T>())
+            .map(|s| s.as_any().downcast_ref::<Storage<T>>().unwrap())
+    }
+    fn storage_mut<T: 'static>(&mut self) -> Option<&mut Storage<T>> {
+        self.storages
+            .get_mut(&TypeId::of::<T>())
+            .map(|s| s.as_any_mut().downcast_mut::<Storage<T>>().unwrap())
+    }
+    fn storage_or_insert<T: 'static>(&mut self) -> &mut Storage<T> {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Storage::<T> { items: vec![] }))
+            .as_any_mut()
+            .downcast_mut::<Storage<T>>()
+            .unwrap()
+    }
+}
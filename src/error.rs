@@ -0,0 +1,81 @@
+// What can go wrong loading things from disk (textures, sounds, fonts and saves),
+// or writing them back out.
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum EngineError {
+    /// There's no file at the path
+    NotFound(PathBuf),
+    /// The file is there, but couldn't be read or doesn't make sense
+    DecodeFailed { path: PathBuf, reason: String },
+    /// An image that should be a grid of cells, like a tilesheet, isn't a whole number of them
+    WrongDimensions {
+        path: PathBuf,
+        size: (usize, usize),
+        cell: (usize, usize),
+    },
+    /// The file is in a format the engine doesn't read
+    UnsupportedFormat(PathBuf),
+    /// Saving to the file didn't work
+    WriteFailed { path: PathBuf, reason: String },
+}
+
+impl EngineError {
+    pub fn decode_failed(path: &Path, reason: impl ToString) -> Self {
+        Self::DecodeFailed {
+            path: path.to_path_buf(),
+            reason: reason.to_string(),
+        }
+    }
+    pub fn write_failed(path: &Path, reason: impl ToString) -> Self {
+        Self::WriteFailed {
+            path: path.to_path_buf(),
+            reason: reason.to_string(),
+        }
+    }
+    /// A missing file is NotFound, and anything else that stops it being read is DecodeFailed
+    pub fn from_io(path: &Path, err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::NotFound(path.to_path_buf()),
+            _ => Self::decode_failed(path, err),
+        }
+    }
+    /// The file the error is about
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::NotFound(path) | Self::UnsupportedFormat(path) => path,
+            Self::DecodeFailed { path, .. } | Self::WrongDimensions { path, .. } => path,
+            Self::WriteFailed { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotFound(path) => write!(f, "{} not found", path.display()),
+            Self::DecodeFailed { path, reason } => {
+                write!(f, "couldn't read {}: {}", path.display(), reason)
+            }
+            Self::WrongDimensions { path, size, cell } => write!(
+                f,
+                "{} is {}x{}, which isn't a whole number of {}x{} cells",
+                path.display(),
+                size.0,
+                size.1,
+                cell.0,
+                cell.1
+            ),
+            Self::UnsupportedFormat(path) => {
+                write!(f, "{} is in a format we can't read", path.display())
+            }
+            Self::WriteFailed { path, reason } => {
+                write!(f, "couldn't write {}: {}", path.display(), reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
@@ -0,0 +1,180 @@
+// Vector arithmetic and rectangle helpers for the types in types.rs.
+// Rects are half-open: a rect at x with width w covers x..x+w.
+use crate::types::{Rect, Vec2f, Vec2i};
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+// Both vector types get the same component-wise operators
+macro_rules! vector_ops {
+    ($v:ident, $s:ty) => {
+        impl Add for $v {
+            type Output = $v;
+            fn add(self, other: $v) -> $v {
+                $v(self.0 + other.0, self.1 + other.1)
+            }
+        }
+
+        impl Sub for $v {
+            type Output = $v;
+            fn sub(self, other: $v) -> $v {
+                $v(self.0 - other.0, self.1 - other.1)
+            }
+        }
+
+        impl AddAssign for $v {
+            fn add_assign(&mut self, other: $v) {
+                *self = *self + other;
+            }
+        }
+
+        impl SubAssign for $v {
+            fn sub_assign(&mut self, other: $v) {
+                *self = *self - other;
+            }
+        }
+
+        impl Mul<$s> for $v {
+            type Output = $v;
+            fn mul(self, k: $s) -> $v {
+                $v(self.0 * k, self.1 * k)
+            }
+        }
+
+        impl Div<$s> for $v {
+            type Output = $v;
+            fn div(self, k: $s) -> $v {
+                $v(self.0 / k, self.1 / k)
+            }
+        }
+
+        impl Neg for $v {
+            type Output = $v;
+            fn neg(self) -> $v {
+                $v(-self.0, -self.1)
+            }
+        }
+    };
+}
+
+vector_ops!(Vec2i, i32);
+vector_ops!(Vec2f, f32);
+
+impl Vec2i {
+    pub fn dot(self, other: Vec2i) -> i32 {
+        self.0 * other.0 + self.1 * other.1
+    }
+    pub fn length_squared(self) -> i32 {
+        self.dot(self)
+    }
+}
+
+impl Vec2f {
+    /// Rounds to the nearest whole pixel
+    pub fn snap(self) -> Vec2i {
+        Vec2i(self.0.round() as i32, self.1.round() as i32)
+    }
+    pub fn dot(self, other: Vec2f) -> f32 {
+        self.0 * other.0 + self.1 * other.1
+    }
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+}
+
+impl From<(i32, i32)> for Vec2i {
+    fn from((x, y): (i32, i32)) -> Self {
+        Vec2i(x, y)
+    }
+}
+
+impl From<Vec2i> for (i32, i32) {
+    fn from(v: Vec2i) -> Self {
+        (v.0, v.1)
+    }
+}
+
+impl From<Vec2i> for Vec2f {
+    fn from(v: Vec2i) -> Self {
+        Vec2f(v.0 as f32, v.1 as f32)
+    }
+}
+
+impl Rect {
+    /// The rect with its top-left corner at pos, sized like our entities' (width, height)
+    pub fn from_pos_size(pos: Vec2i, size: (usize, usize)) -> Rect {
+        Rect {
+            x: pos.0,
+            y: pos.1,
+            w: size.0 as u16,
+            h: size.1 as u16,
+        }
+    }
+    pub fn pos(&self) -> Vec2i {
+        Vec2i(self.x, self.y)
+    }
+    pub fn size(&self) -> (usize, usize) {
+        (self.w as usize, self.h as usize)
+    }
+    // One past the last column and row inside the rect
+    pub fn right(&self) -> i32 {
+        self.x + self.w as i32
+    }
+    pub fn bottom(&self) -> i32 {
+        self.y + self.h as i32
+    }
+    pub fn is_empty(&self) -> bool {
+        self.w == 0 || self.h == 0
+    }
+    pub fn center(&self) -> Vec2i {
+        Vec2i(self.x + self.w as i32 / 2, self.y + self.h as i32 / 2)
+    }
+    pub fn contains(&self, p: Vec2i) -> bool {
+        self.x <= p.0 && p.0 < self.right() && self.y <= p.1 && p.1 < self.bottom()
+    }
+    /// Do the rects share any area? Rects that only touch along an edge don't.
+    pub fn intersects(&self, other: Rect) -> bool {
+        self.x.max(other.x) < self.right().min(other.right())
+            && self.y.max(other.y) < self.bottom().min(other.bottom())
+    }
+    pub fn intersection(&self, other: Rect) -> Option<Rect> {
+        if !self.intersects(other) {
+            return None;
+        }
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        Some(Rect {
+            x,
+            y,
+            w: (self.right().min(other.right()) - x) as u16,
+            h: (self.bottom().min(other.bottom()) - y) as u16,
+        })
+    }
+    /// The smallest rect covering both
+    pub fn union(&self, other: Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect {
+            x,
+            y,
+            w: (self.right().max(other.right()) - x) as u16,
+            h: (self.bottom().max(other.bottom()) - y) as u16,
+        }
+    }
+    /// Grows the rect by `by` pixels on every side; negative amounts shrink it around its center
+    pub fn expand(&self, by: i32) -> Rect {
+        let w = (self.w as i32 + 2 * by).max(0);
+        let h = (self.h as i32 + 2 * by).max(0);
+        Rect {
+            x: self.x - by + (self.w as i32 + 2 * by - w) / 2,
+            y: self.y - by + (self.h as i32 + 2 * by - h) / 2,
+            w: w as u16,
+            h: h as u16,
+        }
+    }
+    pub fn translate(&self, by: Vec2i) -> Rect {
+        Rect {
+            x: self.x + by.0,
+            y: self.y + by.1,
+            ..*self
+        }
+    }
+}
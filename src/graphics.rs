@@ -8,6 +8,8 @@ pub struct Screen<'fb> {
     height: usize,
     depth: usize,
     position: Vec2i,
+    // Pixels from one row of the framebuffer to the next; wider than the screen for viewports
+    pitch: usize,
 }
 impl<'fb> Screen<'fb> {
     // Call =wrap= every frame; that means the camera position will need to be stored in the game state
@@ -24,21 +26,66 @@ impl<'fb> Screen<'fb> {
             height,
             depth,
             position,
+            pitch: width,
+        }
+    }
+    /// A screen drawing into just the part of this one covered by r (in this screen's pixels),
+    /// scrolled to its own position. Drawing is clipped to r.
+    /// Good for split-screen, minimaps, and picture-in-picture.
+    pub fn viewport(&mut self, r: Rect, position: Vec2i) -> Screen<'_> {
+        let whole = Rect::from_pos_size(Vec2i(0, 0), (self.width, self.height));
+        let r = whole.intersection(r).unwrap_or(Rect {
+            x: 0,
+            y: 0,
+            w: 0,
+            h: 0,
+        });
+        // Start the framebuffer at the viewport's top-left pixel; rows still step by our pitch
+        let start = (r.y as usize * self.pitch + r.x as usize) * self.depth;
+        Screen {
+            framebuffer: &mut self.framebuffer[start..],
+            width: r.w as usize,
+            height: r.h as usize,
+            depth: self.depth,
+            position,
+            pitch: self.pitch,
         }
     }
     pub fn size(&self) -> (usize, usize) {
         (self.width, self.height)
     }
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+    // Raw access to the pixels, for effects that work on the whole frame at once.
+    // Only whole screens have rows exactly `width` pixels long; viewports don't.
+    pub fn buffer(&self) -> &[u8] {
+        self.framebuffer
+    }
+    pub fn buffer_mut(&mut self) -> &mut [u8] {
+        self.framebuffer
+    }
+    // Blow the top-left 1/factor of the frame up to fill all of it, for zooming in by whole pixels.
+    // Draw the world first, then upscale, then draw anything that shouldn't be zoomed.
+    pub fn upscale(&mut self, factor: usize) {
+        if factor <= 1 {
+            return;
+        }
+        let depth = self.depth;
+        // Working backwards, each pixel's source is somewhere we haven't written to yet
+        for y in (0..self.height).rev() {
+            for x in (0..self.width).rev() {
+                let from = ((y / factor) * self.pitch + x / factor) * depth;
+                let to = (y * self.pitch + x) * depth;
+                self.framebuffer.copy_within(from..from + depth, to);
+            }
+        }
+    }
     pub fn set_scroll(&mut self, posn: Vec2i) {
         self.position = posn;
     }
     pub fn bounds(&self) -> Rect {
-        Rect {
-            x: self.position.0,
-            y: self.position.1,
-            w: self.width as u16,
-            h: self.height as u16,
-        }
+        Rect::from_pos_size(self.position, (self.width, self.height))
     }
     // Our old, slow friend draw_at, now with super scrolling powers!
     #[inline(always)]
@@ -52,7 +99,7 @@ impl<'fb> Screen<'fb> {
         assert_eq!(self.depth, 4);
         // Now x and y are within framebuffer bounds so go ahead and draw
         let c = [col.0, col.1, col.2, col.3];
-        let idx = y * self.width as i32 * self.depth as i32 + x * self.depth as i32;
+        let idx = y * self.pitch as i32 * self.depth as i32 + x * self.depth as i32;
         assert!(idx >= 0);
         let idx = idx as usize;
         self.framebuffer[idx..(idx + self.depth)].copy_from_slice(&c);
@@ -60,32 +107,55 @@ impl<'fb> Screen<'fb> {
     // Clear's the same...
     pub fn clear(&mut self, col: Rgba) {
         let c = [col.0, col.1, col.2, col.3];
-        for px in self.framebuffer.chunks_exact_mut(4) {
-            px.copy_from_slice(&c);
+        let pitch = self.pitch * self.depth;
+        for y in 0..self.height {
+            let row = &mut self.framebuffer[(y * pitch)..(y * pitch + self.width * self.depth)];
+            for px in row.chunks_exact_mut(4) {
+                px.copy_from_slice(&c);
+            }
         }
     }
     // Rect needs a translation to start
     pub fn rect(&mut self, r: Rect, col: Rgba) {
         let c = [col.0, col.1, col.2, col.3];
         // Here's the translation
-        let r = Rect {
-            x: r.x - self.position.0,
-            y: r.y - self.position.1,
-            ..r
-        };
+        let r = r.translate(-self.position);
         // And the rest is just the same
         let x0 = r.x.max(0).min(self.width as i32) as usize;
-        let x1 = (r.x + r.w as i32).max(0).min(self.width as i32) as usize;
+        let x1 = r.right().max(0).min(self.width as i32) as usize;
         let y0 = r.y.max(0).min(self.height as i32) as usize;
-        let y1 = (r.y + r.h as i32).max(0).min(self.height as i32) as usize;
+        let y1 = r.bottom().max(0).min(self.height as i32) as usize;
         let depth = self.depth;
-        let pitch = self.width * depth;
-        for row in self.framebuffer[(y0 * pitch)..(y1 * pitch)].chunks_exact_mut(pitch) {
-            for p in row[(x0 * depth)..(x1 * depth)].chunks_exact_mut(depth) {
+        let pitch = self.pitch * depth;
+        for y in y0..y1 {
+            let row = &mut self.framebuffer[(y * pitch + x0 * depth)..(y * pitch + x1 * depth)];
+            for p in row.chunks_exact_mut(depth) {
                 p.copy_from_slice(&c);
             }
         }
     }
+    // Like rect, but see-through: col is composited over what's there.
+    // Unlike textures, col isn't premultiplied.
+    pub fn blend_rect(&mut self, r: Rect, col: Rgba) {
+        let a = col.3 as f32 / 255.0;
+        let c = [col.0 as f32 * a, col.1 as f32 * a, col.2 as f32 * a];
+        let r = r.translate(-self.position);
+        let x0 = r.x.max(0).min(self.width as i32) as usize;
+        let x1 = r.right().max(0).min(self.width as i32) as usize;
+        let y0 = r.y.max(0).min(self.height as i32) as usize;
+        let y1 = r.bottom().max(0).min(self.height as i32) as usize;
+        let depth = self.depth;
+        let pitch = self.pitch * depth;
+        for y in y0..y1 {
+            let row = &mut self.framebuffer[(y * pitch + x0 * depth)..(y * pitch + x1 * depth)];
+            for p in row.chunks_exact_mut(depth) {
+                for i in 0..3 {
+                    p[i] = (c[i] + p[i] as f32 * (1.0 - a)).round() as u8;
+                }
+                p[3] = (col.3 as f32 + p[3] as f32 * (1.0 - a)).round() as u8;
+            }
+        }
+    }
     // Ditto line
     pub fn line(&mut self, Vec2i(x0, y0): Vec2i, Vec2i(x1, y1): Vec2i, col: Rgba) {
         let col = [col.0, col.1, col.2, col.3];
@@ -109,8 +179,8 @@ impl<'fb> Screen<'fb> {
             if 0 <= x && x < width && 0 <= y && y < height {
                 // TODO this bounds check could in theory be avoided with
                 // the unsafe get_unchecked, but maybe better not...
-                self.framebuffer[(y as usize * self.width * self.depth + x as usize * self.depth)
-                    ..(y as usize * self.width * self.depth + (x as usize + 1) * self.depth)]
+                self.framebuffer[(y as usize * self.pitch * self.depth + x as usize * self.depth)
+                    ..(y as usize * self.pitch * self.depth + (x as usize + 1) * self.depth)]
                     .copy_from_slice(&col);
                 // We couldn't just clamp x0/y0 and x1/y1 into bounds, because then
                 // we might change the slope of the line.
@@ -127,7 +197,17 @@ impl<'fb> Screen<'fb> {
         }
     }
     // Bitblt too begins with a translation
-    pub fn bitblt(&mut self, src: &Texture, from: Rect, Vec2i(to_x, to_y): Vec2i) {
+    pub fn bitblt(&mut self, src: &Texture, from: Rect, to: Vec2i) {
+        self.bitblt_faded(src, from, to, 255);
+    }
+    // Bitblt with the whole image made more see-through, from 255 (as is) down to 0 (invisible)
+    pub fn bitblt_faded(
+        &mut self,
+        src: &Texture,
+        from: Rect,
+        Vec2i(to_x, to_y): Vec2i,
+        alpha: u8,
+    ) {
         let (tw, th) = src.size();
         // println!("src: {:?}", src.size());
         // println!("text: {:?}", from);
@@ -147,7 +227,7 @@ impl<'fb> Screen<'fb> {
         let depth = self.depth;
         assert_eq!(depth, src.depth());
         let src_pitch = src.pitch();
-        let dst_pitch = self.width * depth;
+        let dst_pitch = self.pitch * depth;
         // All this rigmarole is just to avoid bounds checks on each pixel of the blit.
         // We want to calculate which row/col of the src image to start at and which to end at.
         // This way there's no need to even check for out of bounds draws.
@@ -156,30 +236,43 @@ impl<'fb> Screen<'fb> {
         let y_count = (to_y + from.h as i32).min(self.height as i32) - to_y;
         let x_count = (to_x + from.w as i32).min(self.width as i32) - to_x;
         let src_buf = src.buffer();
-        for (row_a, row_b) in src_buf[(src_pitch * ((from.y + y_skip) as usize))
+        // Premultiplied, so fading scales every channel alike
+        let fade = alpha as f32 / 255.0;
+        for (i, row_a) in src_buf[(src_pitch * ((from.y + y_skip) as usize))
             ..(src_pitch * ((from.y + y_count) as usize))]
             .chunks_exact(src_pitch)
-            .zip(
-                self.framebuffer[(dst_pitch * ((to_y + y_skip) as usize))
-                    ..(dst_pitch * ((to_y + y_count) as usize))]
-                    .chunks_exact_mut(dst_pitch),
-            )
+            .enumerate()
         {
-            let to_cols = row_b
-                [(depth * (to_x + x_skip) as usize)..(depth * (to_x + x_count) as usize)]
+            // Rows are sliced one at a time, since a viewport's last row
+            // can run off the end of the buffer
+            let row_b = dst_pitch * ((to_y + y_skip) as usize + i);
+            let to_cols = self.framebuffer[(row_b + depth * (to_x + x_skip) as usize)
+                ..(row_b + depth * (to_x + x_count) as usize)]
                 .chunks_exact_mut(depth);
             let from_cols = row_a
                 [(depth * (from.x + x_skip) as usize)..(depth * (from.x + x_count) as usize)]
                 .chunks_exact(depth);
-            // Composite over, assume premultiplied rgba8888
             for (to, from) in to_cols.zip(from_cols) {
-                let ta = to[3] as f32 / 255.0;
                 let fa = from[3] as f32 / 255.0;
-                for i in 0..3 {
-                    to[i] = from[i].saturating_add((to[i] as f32 * (1.0 - fa)).round() as u8);
+                if alpha == 255 {
+                    // Nothing to fade, which is most blits, so skip scaling the source
+                    composite_over(to, [from[0], from[1], from[2]], fa);
+                } else {
+                    let faded = |c: u8| (c as f32 * fade).round() as u8;
+                    let color = [faded(from[0]), faded(from[1]), faded(from[2])];
+                    composite_over(to, color, fa * fade);
                 }
-                to[3] = ((fa + ta * (1.0 - fa)) * 255.0).round() as u8;
             }
         }
     }
 }
+
+// Composite over, assume premultiplied rgba8888: color is the source pixel's, and fa its alpha
+// from 0 to 1
+fn composite_over(to: &mut [u8], color: [u8; 3], fa: f32) {
+    let ta = to[3] as f32 / 255.0;
+    for i in 0..3 {
+        to[i] = color[i].saturating_add((to[i] as f32 * (1.0 - fa)).round() as u8);
+    }
+    to[3] = ((fa + ta * (1.0 - fa)) * 255.0).round() as u8;
+}
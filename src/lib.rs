@@ -14,18 +14,37 @@ use graphics::Screen;
 pub mod resources;
 use resources::Resources;
 
+pub mod ai;
 pub mod animation;
+pub mod atlas;
+pub mod audio;
+pub mod camera;
 pub mod collision;
+pub mod components;
+pub mod controller;
+pub mod ecs;
+pub mod error;
+pub mod geometry;
+pub mod minimap;
+pub mod particles;
 pub mod server;
 pub mod states;
 pub mod texture;
 pub mod tiles;
 pub mod save;
+pub mod transition;
 
 
 const DEPTH: usize = 4;
 const DT: f64 = 1.0 / 60.0;
 
+/// Returned by the update callback to tell run whether to keep going
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Control {
+    Continue,
+    Quit,
+}
+
 pub fn run<Rule, State>(
     width: usize,
     height: usize,
@@ -34,7 +53,7 @@ pub fn run<Rule, State>(
     rules: Rule,
     mut state: State,
     draw: impl Fn(&Resources, &Rule, &State, &mut Screen, usize) + 'static,
-    update: impl Fn(&Resources, &Rule, &mut State, &WinitInputHelper, usize) + 'static,
+    update: impl Fn(&Resources, &Rule, &mut State, &WinitInputHelper, usize) -> Control + 'static,
 ) -> State {
     use std::time::Instant;
 
@@ -87,10 +106,15 @@ pub fn run<Rule, State>(
             // Eat up one frame worth of time
             available_time -= DT;
 
-            update(&rsrc, &rules, &mut state, &input, frame_count);
+            let control = update(&rsrc, &rules, &mut state, &input, frame_count);
 
             // Increment the frame counter
             frame_count += 1;
+
+            if control == Control::Quit {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
         }
         // Request redraw
         window.request_redraw();
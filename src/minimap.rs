@@ -0,0 +1,79 @@
+// A small map of the overworld for a corner of the screen, one block of color per tile.
+// It only shows the parts of the world someone has already seen, so it fills in as you explore.
+use crate::graphics::Screen;
+use crate::tiles::{Tilemap, TILE_SZ};
+use crate::types::{Rect, Rgba, Vec2i};
+use std::collections::HashMap;
+
+pub struct Minimap {
+    /// Screen pixels across each tile
+    pub scale: usize,
+    /// What places nobody has seen yet look like
+    pub fog: Rgba,
+    // Every tile seen so far, by its column and row in the world, and the color it showed
+    explored: HashMap<Vec2i, Rgba>,
+}
+
+impl Minimap {
+    pub fn new(scale: usize) -> Self {
+        Self {
+            scale,
+            fog: Rgba(40, 40, 40, 255),
+            explored: HashMap::new(),
+        }
+    }
+    /// Remembers every tile of the maps inside area, in world pixels
+    pub fn explore(&mut self, maps: &[Tilemap], area: Rect) {
+        for map in maps {
+            for (col, bounds) in map.colors_in(area) {
+                self.explored.insert(tile_of(bounds.pos()), col);
+            }
+        }
+    }
+    /// Has the tile at this world position been seen?
+    pub fn explored(&self, pos: Vec2i) -> bool {
+        self.explored.contains_key(&tile_of(pos))
+    }
+    /// Draws what's been explored around center (in world pixels) over the whole screen,
+    /// which is usually a viewport in a corner, then a dot at each marker's world position.
+    pub fn draw(&self, screen: &mut Screen, center: Vec2i, markers: &[(Vec2i, Rgba)]) {
+        screen.set_scroll(Vec2i(0, 0));
+        screen.clear(self.fog);
+        let (w, h) = screen.size();
+        let scale = self.scale.max(1) as i32;
+        let sz = TILE_SZ as i32;
+        // World pixels to screen pixels, with center in the middle
+        let to_screen = |p: Vec2i| {
+            Vec2i(
+                ((p.0 - center.0) * scale).div_euclid(sz) + w as i32 / 2,
+                ((p.1 - center.1) * scale).div_euclid(sz) + h as i32 / 2,
+            )
+        };
+        // Only look up the tiles that can land on screen
+        let first = tile_of(center) - Vec2i(w as i32 / 2 / scale + 1, h as i32 / 2 / scale + 1);
+        let last = tile_of(center) + Vec2i(w as i32 / 2 / scale + 1, h as i32 / 2 / scale + 1);
+        for row in first.1..=last.1 {
+            for col in first.0..=last.0 {
+                if let Some(c) = self.explored.get(&Vec2i(col, row)) {
+                    let at = to_screen(Vec2i(col * sz, row * sz));
+                    // Tiles end where the next one starts, so nothing's left between them
+                    let end = to_screen(Vec2i((col + 1) * sz, (row + 1) * sz));
+                    let size = ((end.0 - at.0) as usize, (end.1 - at.1) as usize);
+                    screen.rect(Rect::from_pos_size(at, size), *c);
+                }
+            }
+        }
+        // Markers stay big enough to spot however small the tiles are
+        let dot = self.scale.max(3);
+        for (pos, c) in markers {
+            let at = to_screen(*pos) - Vec2i(dot as i32 / 2, dot as i32 / 2);
+            screen.rect(Rect::from_pos_size(at, (dot, dot)), *c);
+        }
+    }
+}
+
+// The column and row of the tile a world position is in
+fn tile_of(pos: Vec2i) -> Vec2i {
+    let sz = TILE_SZ as i32;
+    Vec2i(pos.0.div_euclid(sz), pos.1.div_euclid(sz))
+}
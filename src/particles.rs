@@ -0,0 +1,212 @@
+// Particles for little effects like dust, sparks and smoke.
+// Every particle lives in one pool that's allocated up front, so spawning, moving and
+// retiring them never allocates; when the pool is full, new particles are just dropped.
+use crate::animation::{Animation, AnimationState};
+use crate::graphics::Screen;
+use crate::resources::Handle;
+use crate::texture::Texture;
+use crate::types::{Rect, Rgba, Vec2f, Vec2i};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+/// How a kind of particle looks and moves. Shared between emitters and particles with an Rc.
+#[derive(Clone)]
+pub struct ParticleStyle {
+    /// Shortest and longest a particle lives, in frames
+    pub lifetime: (usize, usize),
+    /// Slowest and fastest a particle starts out, in pixels per frame
+    pub speed: (f32, f32),
+    /// Which way particles head, in radians clockwise from the right (since y points down)
+    pub direction: f32,
+    /// How far either side of direction they can stray; PI goes every which way
+    pub spread: f32,
+    /// Added to the downward speed every frame
+    pub gravity: f32,
+    /// The color particles start and end their lives with, fading in between.
+    /// Colors aren't premultiplied; textured particles only use the alpha.
+    pub start_color: Rgba,
+    pub end_color: Rgba,
+    /// Width and height of an untextured particle
+    pub size: usize,
+    /// Frames to draw instead of a square, played from when each particle starts
+    pub sprite: Option<(Handle<Texture>, Rc<Animation>)>,
+}
+
+impl Default for ParticleStyle {
+    fn default() -> Self {
+        Self {
+            lifetime: (30, 30),
+            speed: (1.0, 1.0),
+            direction: 0.0,
+            spread: PI,
+            gravity: 0.0,
+            start_color: Rgba(255, 255, 255, 255),
+            end_color: Rgba(255, 255, 255, 0),
+            size: 2,
+            sprite: None,
+        }
+    }
+}
+
+/// Lets out particles a few at a time, from one spot
+#[derive(Clone)]
+pub struct Emitter {
+    /// Where the particles start out, in world pixels
+    pub pos: Vec2f,
+    pub style: Rc<ParticleStyle>,
+    /// Particles per frame; fractions add up from one frame to the next
+    pub rate: f32,
+    /// How many more frames to run for, or None to keep going until cleared
+    pub frames_left: Option<usize>,
+    owed: f32,
+}
+
+impl Emitter {
+    /// An emitter that runs until it's cleared
+    pub fn continuous(pos: Vec2f, style: &Rc<ParticleStyle>, rate: f32) -> Self {
+        Self {
+            pos,
+            style: Rc::clone(style),
+            rate,
+            frames_left: None,
+            owed: 0.0,
+        }
+    }
+    /// An emitter that stops by itself after a number of frames
+    pub fn lasting(pos: Vec2f, style: &Rc<ParticleStyle>, rate: f32, frames: usize) -> Self {
+        Self {
+            frames_left: Some(frames),
+            ..Self::continuous(pos, style, rate)
+        }
+    }
+}
+
+struct Particle {
+    pos: Vec2f,
+    vel: Vec2f,
+    age: usize,
+    life: usize,
+    style: Rc<ParticleStyle>,
+    anim: Option<AnimationState>,
+}
+
+pub struct Particles {
+    // Allocated once, and never filled past capacity so it never has to grow
+    pool: Vec<Particle>,
+    capacity: usize,
+    emitters: Vec<Emitter>,
+    rng: StdRng,
+}
+
+impl Particles {
+    /// Room for at most capacity particles at once
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            pool: Vec::with_capacity(capacity),
+            capacity,
+            emitters: vec![],
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+    /// How many particles are alive
+    pub fn len(&self) -> usize {
+        self.pool.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.pool.is_empty()
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    /// Lets out count particles at once, as many as there's room for
+    pub fn burst(&mut self, pos: Vec2f, count: usize, style: &Rc<ParticleStyle>) {
+        for _ in 0..count {
+            if self.pool.len() >= self.capacity {
+                return;
+            }
+            let life = self
+                .rng
+                .gen_range(style.lifetime.0..=style.lifetime.1.max(style.lifetime.0));
+            let speed = if style.speed.0 < style.speed.1 {
+                self.rng.gen_range(style.speed.0..style.speed.1)
+            } else {
+                style.speed.0
+            };
+            let angle = if style.spread > 0.0 {
+                style.direction + self.rng.gen_range(-style.spread..style.spread)
+            } else {
+                style.direction
+            };
+            self.pool.push(Particle {
+                pos,
+                vel: Vec2f(angle.cos(), angle.sin()) * speed,
+                age: 0,
+                life,
+                style: Rc::clone(style),
+                anim: style.sprite.as_ref().map(|(_, anim)| anim.start()),
+            });
+        }
+    }
+    /// Starts an emitter going, from next update on
+    pub fn emit(&mut self, emitter: Emitter) {
+        self.emitters.push(emitter);
+    }
+    /// Gets rid of every particle and emitter, e.g. when leaving a level
+    pub fn clear(&mut self) {
+        self.pool.clear();
+        self.emitters.clear();
+    }
+    /// Runs the emitters, then moves every particle along a frame and retires the ones that are done
+    pub fn update(&mut self) {
+        let mut emitters = std::mem::take(&mut self.emitters);
+        for e in emitters.iter_mut() {
+            e.owed += e.rate;
+            let count = e.owed as usize;
+            e.owed -= count as f32;
+            self.burst(e.pos, count, &e.style);
+            if let Some(frames) = e.frames_left.as_mut() {
+                *frames = frames.saturating_sub(1);
+            }
+        }
+        emitters.retain(|e| e.frames_left != Some(0));
+        self.emitters = emitters;
+
+        for p in self.pool.iter_mut() {
+            p.vel.1 += p.style.gravity;
+            p.pos += p.vel;
+            p.age += 1;
+            if let Some(anim) = p.anim.as_mut() {
+                anim.tick();
+            }
+        }
+        self.pool.retain(|p| p.age < p.life);
+    }
+    /// Draws each particle centered on where it is
+    pub fn draw(&self, screen: &mut Screen) {
+        for p in self.pool.iter() {
+            let t = p.age as f32 / p.life.max(1) as f32;
+            let col = lerp_color(p.style.start_color, p.style.end_color, t);
+            let center = p.pos.snap();
+            match (&p.style.sprite, &p.anim) {
+                (Some((texture, _)), Some(anim)) => {
+                    let frame = anim.frame();
+                    let at = center - Vec2i(frame.w as i32 / 2, frame.h as i32 / 2);
+                    screen.bitblt_faded(&texture.get(), frame, at, col.3);
+                }
+                _ => {
+                    let half = p.style.size as i32 / 2;
+                    let at = center - Vec2i(half, half);
+                    screen.blend_rect(Rect::from_pos_size(at, (p.style.size, p.style.size)), col);
+                }
+            }
+        }
+    }
+}
+
+// Partway from a to b
+fn lerp_color(a: Rgba, b: Rgba, t: f32) -> Rgba {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Rgba(mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2), mix(a.3, b.3))
+}
@@ -1,13 +1,332 @@
+// Everything the games load from disk or build once and share: textures, tilesets, maps,
+// animations, atlases, sounds and fonts. Each asset is kept under a key, which is its path for
+// things read from files, so loading the same file twice hands back the same asset.
+// Files are watched too: reload_changed reads in any that were saved since they were loaded,
+// and swaps the new version in behind the handles everything already has.
+use crate::animation::Animation;
+use crate::atlas::Atlas;
+use crate::audio::Sound;
+use crate::error::EngineError;
 use crate::texture::Texture;
-use std::path::Path;
+use crate::tiles::{TileGrid, Tileset, TILE_SZ};
+use rusttype::Font;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-pub struct Resources();
+use std::time::SystemTime;
+
+/// A shared reference to an asset. Handles are cheap to clone, and every clone
+/// (and every Rc got from one) counts as a user of the asset.
+pub struct Handle<T>(Rc<Slot<T>>);
+
+struct Slot<T> {
+    key: PathBuf,
+    asset: RefCell<Rc<T>>,
+}
+
+impl<T> Handle<T> {
+    /// A handle to an asset that isn't kept in any Resources, e.g. for tests
+    pub fn new(key: impl AsRef<Path>, asset: T) -> Self {
+        Self(Rc::new(Slot {
+            key: key.as_ref().to_path_buf(),
+            asset: RefCell::new(Rc::new(asset)),
+        }))
+    }
+    /// The asset as it is now. Hang on to the handle rather than this to see reloads.
+    pub fn get(&self) -> Rc<T> {
+        Rc::clone(&self.0.asset.borrow())
+    }
+    /// What the asset is kept under
+    pub fn key(&self) -> &Path {
+        &self.0.key
+    }
+    /// Whether two handles are to the same asset
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Rc::ptr_eq(&a.0, &b.0)
+    }
+    // Swaps in a new version of the asset for every handle to see
+    fn replace(&self, asset: T) {
+        self.set(Rc::new(asset));
+    }
+    pub(crate) fn set(&self, asset: Rc<T>) {
+        *self.0.asset.borrow_mut() = asset;
+    }
+    // Every handle, this one included, plus every Rc handed out by get
+    fn users(&self) -> usize {
+        Rc::strong_count(&self.0) + Rc::strong_count(&self.0.asset.borrow()) - 1
+    }
+}
+
+// Deriving would only let assets that are Clone have handles that are
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self(Rc::clone(&self.0))
+    }
+}
+
+// So things that take a handle can be given an asset made in code, which never reloads
+impl<T> From<&Rc<T>> for Handle<T> {
+    fn from(asset: &Rc<T>) -> Self {
+        Self(Rc::new(Slot {
+            key: PathBuf::new(),
+            asset: RefCell::new(Rc::clone(asset)),
+        }))
+    }
+}
+impl<T> From<&Handle<T>> for Handle<T> {
+    fn from(handle: &Handle<T>) -> Self {
+        handle.clone()
+    }
+}
+
+/// The assets of one kind that Resources has loaded
+pub struct Cache<T> {
+    assets: HashMap<PathBuf, Handle<T>>,
+    // The ones that came from files
+    watched: HashMap<PathBuf, Watch<T>>,
+}
+
+// When a file was last changed, as of loading it, and how to read it again
+struct Watch<T> {
+    modified: Option<SystemTime>,
+    read: fn(&Path) -> Result<T, EngineError>,
+}
+
+impl<T> Cache<T> {
+    fn new() -> Self {
+        Self {
+            assets: HashMap::new(),
+            watched: HashMap::new(),
+        }
+    }
+}
+
+/// Kinds of asset that Resources looks after, each in a cache of its own
+pub trait Asset: Sized + 'static {
+    fn cache(resources: &Resources) -> &RefCell<Cache<Self>>;
+    /// Called with a version read in again before it replaces old, for assets that hand out
+    /// things that should see the new version too
+    fn reloaded(_new: &mut Self, _old: &Self) {}
+}
+
+impl Asset for Texture {
+    fn cache(resources: &Resources) -> &RefCell<Cache<Self>> {
+        &resources.textures
+    }
+}
+impl Asset for Tileset {
+    fn cache(resources: &Resources) -> &RefCell<Cache<Self>> {
+        &resources.tilesets
+    }
+}
+impl Asset for TileGrid {
+    fn cache(resources: &Resources) -> &RefCell<Cache<Self>> {
+        &resources.maps
+    }
+}
+impl Asset for Animation {
+    fn cache(resources: &Resources) -> &RefCell<Cache<Self>> {
+        &resources.animations
+    }
+}
+impl Asset for Atlas {
+    fn cache(resources: &Resources) -> &RefCell<Cache<Self>> {
+        &resources.atlases
+    }
+    // Handles to the texture are kept, e.g. by tilesets and sprites made from the atlas
+    fn reloaded(new: &mut Self, old: &Self) {
+        new.keep_texture_handle(old);
+    }
+}
+impl Asset for Sound {
+    fn cache(resources: &Resources) -> &RefCell<Cache<Self>> {
+        &resources.sounds
+    }
+}
+impl Asset for Font<'static> {
+    fn cache(resources: &Resources) -> &RefCell<Cache<Self>> {
+        &resources.fonts
+    }
+}
+
+// The caches are behind RefCells because games only get a shared reference once they're
+// running, and may still want to load things then
+pub struct Resources {
+    textures: RefCell<Cache<Texture>>,
+    tilesets: RefCell<Cache<Tileset>>,
+    maps: RefCell<Cache<TileGrid>>,
+    animations: RefCell<Cache<Animation>>,
+    atlases: RefCell<Cache<Atlas>>,
+    sounds: RefCell<Cache<Sound>>,
+    fonts: RefCell<Cache<Font<'static>>>,
+    /// Whether textures that don't load are swapped for Texture::missing, kept under their
+    /// path, instead of being an error. On by default in debug builds, so a missing file
+    /// shows up as a checkerboard rather than stopping the game.
+    pub placeholders: bool,
+}
+
+impl Default for Resources {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Resources {
     pub fn new() -> Self {
-        Self()
+        Self {
+            textures: RefCell::new(Cache::new()),
+            tilesets: RefCell::new(Cache::new()),
+            maps: RefCell::new(Cache::new()),
+            animations: RefCell::new(Cache::new()),
+            atlases: RefCell::new(Cache::new()),
+            sounds: RefCell::new(Cache::new()),
+            fonts: RefCell::new(Cache::new()),
+            placeholders: cfg!(debug_assertions),
+        }
+    }
+    pub fn load_texture(&self, p: impl AsRef<Path>) -> Result<Handle<Texture>, EngineError> {
+        let p = p.as_ref();
+        match self.load(p, Texture::with_file) {
+            Err(err) if self.placeholders => {
+                eprintln!("Using a placeholder: {}", err);
+                let handle = self.add(p, Texture::missing());
+                // Once the file's there or fixed, it's loaded like it had been all along
+                self.watch(p, Texture::with_file);
+                Ok(handle)
+            }
+            loaded => loaded,
+        }
+    }
+    /// Loads a texture that's a grid of tiles, making sure it's a whole number of them
+    pub fn load_tilesheet(&self, p: impl AsRef<Path>) -> Result<Handle<Texture>, EngineError> {
+        let handle = self.load_texture(p.as_ref())?;
+        handle.get().check_grid(p.as_ref(), (TILE_SZ, TILE_SZ))?;
+        Ok(handle)
+    }
+    /// Loads the tiles of a level's map, for Tilemap::with_grid
+    pub fn load_map(&self, p: impl AsRef<Path>) -> Result<Handle<TileGrid>, EngineError> {
+        self.load(p.as_ref(), TileGrid::with_file)
+    }
+    /// Loads an atlas made by the atlas tool: a PNG with a table next to it
+    pub fn load_atlas(&self, p: impl AsRef<Path>) -> Result<Handle<Atlas>, EngineError> {
+        self.load(p.as_ref(), Atlas::with_file)
     }
-    pub fn load_texture(&self, p: impl AsRef<Path>) -> Rc<Texture> {
-        Rc::new(Texture::with_file(p.as_ref()))
+    pub fn load_sound(&self, p: impl AsRef<Path>) -> Result<Handle<Sound>, EngineError> {
+        self.load(p.as_ref(), Sound::with_file)
     }
+    pub fn load_font(&self, p: impl AsRef<Path>) -> Result<Handle<Font<'static>>, EngineError> {
+        self.load(p.as_ref(), |p| {
+            let data = std::fs::read(p).map_err(|err| EngineError::from_io(p, err))?;
+            Font::try_from_vec(data)
+                .ok_or_else(|| EngineError::decode_failed(p, "not a font we can read"))
+        })
+    }
+    // The asset at p if it's already loaded, otherwise read it in and keep it
+    fn load<T: Asset>(
+        &self,
+        p: &Path,
+        read: fn(&Path) -> Result<T, EngineError>,
+    ) -> Result<Handle<T>, EngineError> {
+        if let Some(handle) = self.get(p) {
+            return Ok(handle);
+        }
+        let handle = self.add(p, read(p)?);
+        self.watch(p, read);
+        Ok(handle)
+    }
+    fn watch<T: Asset>(&self, p: &Path, read: fn(&Path) -> Result<T, EngineError>) {
+        let watch = Watch {
+            modified: modified(p),
+            read,
+        };
+        T::cache(self)
+            .borrow_mut()
+            .watched
+            .insert(p.to_path_buf(), watch);
+    }
+    /// Keeps an asset that was made in code, like a tileset or animation, under key.
+    /// Anything already there is unloaded, though handles to it still work.
+    pub fn add<T: Asset>(&self, key: impl AsRef<Path>, asset: T) -> Handle<T> {
+        let handle = Handle::new(key, asset);
+        let mut cache = T::cache(self).borrow_mut();
+        cache.watched.remove(handle.key());
+        cache
+            .assets
+            .insert(handle.key().to_path_buf(), handle.clone());
+        handle
+    }
+    /// The asset kept under key, if there is one
+    pub fn get<T: Asset>(&self, key: impl AsRef<Path>) -> Option<Handle<T>> {
+        T::cache(self).borrow().assets.get(key.as_ref()).cloned()
+    }
+    /// How many handles and Rcs to the asset under key are around, not counting
+    /// Resources' own
+    pub fn users<T: Asset>(&self, key: impl AsRef<Path>) -> usize {
+        self.get::<T>(key).map_or(0, |handle| handle.users() - 2)
+    }
+    /// Stops keeping the asset under key, so it's freed once nothing's using it.
+    /// Returns whether there was anything to unload.
+    pub fn unload<T: Asset>(&self, key: impl AsRef<Path>) -> bool {
+        let mut cache = T::cache(self).borrow_mut();
+        cache.watched.remove(key.as_ref());
+        cache.assets.remove(key.as_ref()).is_some()
+    }
+    /// Unloads every asset that nothing's using, e.g. between levels, and says how many
+    pub fn unload_unused(&self) -> usize {
+        unload_unused(&self.textures)
+            + unload_unused(&self.tilesets)
+            + unload_unused(&self.maps)
+            + unload_unused(&self.animations)
+            + unload_unused(&self.atlases)
+            + unload_unused(&self.sounds)
+            + unload_unused(&self.fonts)
+    }
+    /// How many assets of one kind are kept
+    pub fn loaded<T: Asset>(&self) -> usize {
+        T::cache(self).borrow().assets.len()
+    }
+    /// Reads in every file that's been changed since it was loaded, and says which.
+    /// Handles to them get the new version; a file that doesn't read keeps the old one.
+    pub fn reload_changed(&self) -> Vec<PathBuf> {
+        let mut reloaded = vec![];
+        reload_changed(&self.textures, &mut reloaded);
+        reload_changed(&self.maps, &mut reloaded);
+        reload_changed(&self.atlases, &mut reloaded);
+        reload_changed(&self.sounds, &mut reloaded);
+        reload_changed(&self.fonts, &mut reloaded);
+        reloaded
+    }
+}
+
+fn unload_unused<T>(cache: &RefCell<Cache<T>>) -> usize {
+    let Cache { assets, watched } = &mut *cache.borrow_mut();
+    let before = assets.len();
+    assets.retain(|_, handle| handle.users() > 1);
+    watched.retain(|key, _| assets.contains_key(key));
+    before - assets.len()
+}
+
+fn reload_changed<T: Asset>(cache: &RefCell<Cache<T>>, reloaded: &mut Vec<PathBuf>) {
+    let Cache { assets, watched } = &mut *cache.borrow_mut();
+    for (path, watch) in watched.iter_mut() {
+        let now = modified(path);
+        if now.is_none() || now == watch.modified {
+            continue;
+        }
+        watch.modified = now;
+        match (watch.read)(path) {
+            Ok(mut asset) => {
+                T::reloaded(&mut asset, &assets[path].get());
+                assets[path].replace(asset);
+                reloaded.push(path.clone());
+            }
+            Err(err) => eprintln!("Keeping the old version: {}", err),
+        }
+    }
+}
+
+// When the file at p was last changed, if it's there
+fn modified(p: &Path) -> Option<SystemTime> {
+    std::fs::metadata(p).and_then(|m| m.modified()).ok()
 }
@@ -1,34 +1,33 @@
+use crate::error::EngineError;
 use crate::types::Player;
 use serde_json;
 use std::io::{Write, Read};
 
-use std::fs::OpenOptions;
 use std::fs::File;
 use std::path::Path;
 
-#[allow(unused_must_use)]
-pub fn save<T: AsRef<Path>>(player: &Player,filename:T) {
-    let s = serde_json::to_string(player).unwrap();
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(filename).unwrap();
-    file.write(s.as_bytes());
-    file.flush();
+// Replaces whatever was saved at filename
+pub fn save<T: AsRef<Path>>(player: &Player, filename: T) -> Result<(), EngineError> {
+    let path = filename.as_ref();
+    let s = serde_json::to_string(player).map_err(|err| EngineError::write_failed(path, err))?;
+    File::create(path)
+        .and_then(|mut f| f.write_all(s.as_bytes()))
+        .map_err(|err| EngineError::write_failed(path, err))
 }
 
-#[allow(unused_must_use)]
-pub fn load<T: AsRef<Path>>(filename:T) -> Player {
-    let file = File::open(filename);
-    match file {
-        Ok(mut f) => {
-            let mut s = String::new();
-            f.read_to_string(&mut s);
-            return serde_json::from_str(s.as_str()).unwrap();
-        }
-        Err(_) => {
-            return Player::new();
-        }
+pub fn load<T: AsRef<Path>>(filename: T) -> Result<Player, EngineError> {
+    let path = filename.as_ref();
+    let mut s = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut s))
+        .map_err(|err| EngineError::from_io(path, err))?;
+    serde_json::from_str(s.as_str()).map_err(|err| EngineError::decode_failed(path, err))
+}
+
+// Like load, but with no save yet it's a new player
+pub fn load_or_new<T: AsRef<Path>>(filename: T) -> Result<Player, EngineError> {
+    match load(filename) {
+        Err(EngineError::NotFound(_)) => Ok(Player::new()),
+        loaded => loaded,
     }
-}
\ No newline at end of file
+}
@@ -1,13 +1,23 @@
-use crate::animation::*;
+use crate::ai::*;
+use crate::animation::Animation;
+use crate::atlas::render_text;
+use crate::audio::{Attenuation, Audio, Falloff, Listener, Sound, VoiceId};
+use crate::camera::{Camera, CameraEvent};
 use crate::collision::*;
+use crate::components::*;
+use crate::controller::*;
+use crate::ecs::*;
+use crate::error::EngineError;
 use crate::graphics::*;
+use crate::minimap::Minimap;
+use crate::particles::{Emitter, ParticleStyle, Particles};
 use crate::resources::*;
 use crate::server::Server;
 use crate::texture::*;
 use crate::tiles::*;
+use crate::transition::*;
 use crate::types::*;
-use imageproc::drawing::draw_text;
-use image::{GenericImage, GenericImageView, ImageBuffer, RgbImage, Rgb, RgbaImage};
+use crate::Control;
 use rusttype::Font;
 use std::collections::HashMap;
 use std::rc::Rc;
@@ -18,13 +28,45 @@ const WIDTH: usize = 320 * 2;
 const HEIGHT: usize = 240 * 2;
 const TILE_MAP_SIZE: usize = 256;
 const TILE_SZ: usize = 32;
+// How much dying and landing hard shake the camera up
+const DEATH_TRAUMA: f32 = 0.6;
+const LANDING_TRAUMA: f32 = 0.25;
+// Falling at least this fast counts as landing hard
+const HARD_LANDING_SPEED: f32 = 9.0;
+// Where the overworld's minimap goes: this far in from the top-right corner, and this big
+const MINIMAP_MARGIN: i32 = 8;
+const MINIMAP_SIZE: (u16, u16) = (128, 96);
+// Landing at least this fast kicks up dust
+const LANDING_DUST_SPEED: f32 = 4.0;
+const MAX_PARTICLES: usize = 1024;
+// How far the side scroller's sounds carry. Things that keep going, like lava, fade out
+// well before they're off screen so there's not a constant din.
+const EFFECT_FALLOFF: Falloff = Falloff {
+    min_range: 160.0,
+    max_range: 800.0,
+    curve: Attenuation::Linear,
+};
+const AMBIENT_FALLOFF: Falloff = Falloff {
+    min_range: 48.0,
+    max_range: 400.0,
+    curve: Attenuation::Quadratic,
+};
+const FOOTSTEP_FALLOFF: Falloff = Falloff {
+    min_range: 64.0,
+    max_range: 480.0,
+    curve: Attenuation::Inverse,
+};
+// Frames between a running player's footsteps
+const FOOTSTEP_FRAMES: usize = 16;
+// How many stack changes process_input follows in a single frame
+pub const MAX_TRANSITIONS_PER_FRAME: usize = 16;
 
 pub struct GameData {
     pub score: usize,
     pub speed_multiplier: usize,
     pub num_jumps: usize,
+    // Where each portal is, and where it takes you
     pub portals: Vec<(Vec2i, Vec2i)>,
-    pub restart: bool,
 }
 
 pub struct Text {
@@ -35,51 +77,148 @@ pub struct Text {
 
 impl Text {
     pub fn new(map_to: Vec2i, text: &str, font: &Font, scale: f32) -> Self {
-        let mut img: RgbaImage = ImageBuffer::new(512, 512);
-        let sc = rusttype::Scale { x: scale, y: scale };
-        let text_img = draw_text(&mut img, image::Rgba([0, 0, 0, 255]), 0, 0, sc, font, text);
-        let tex = Texture::new(text_img);
+        // Just big enough for the text; see the atlas for packing lots of labels together
+        let img = render_text(text, font, scale, Rgba(0, 0, 0, 255));
+        let (w, h) = img.dimensions();
+        let tex = Texture::new(img);
         Text {
             tex,
-            from: Rect { x: 0, y: 0, w: 512, h: 512 },
+            from: Rect { x: 0, y: 0, w: w as u16, h: h as u16 },
             to: map_to,
         }
     }
 }
 
 pub struct GameState {
-    // Every entity has a position, a size, a texture, and animation state.
-    // Assume entity 0 is the player
-    // pub types: Vec<Player>,
-    // pub positions: Vec<Vec2i>,
-    // pub velocities: Vec<Vec2i>,
-    // pub current_player: Player,
+    // Every simulated thing is an entity with components like Position, Size and Sprite.
+    pub entities: World,
+    // The entity the local player controls
+    pub player: Entity,
     pub server: Server,
+    // Every player's networked record, including ours (copied from our entity by sync_player)
     pub players: HashMap<i32, Player>,
-    // pub positions: Vec<Vec2i>,
-    pub sizes: Vec<(usize, usize)>,
-    pub textures: Vec<Rc<Texture>>,
-    pub anim_state: Vec<AnimationState>,
+    // Enemies are drawn with this texture and animation
+    pub enemy_tex: Handle<Texture>,
+    pub enemy_anim: Handle<Animation>,
+    // What's drawn behind the side scroller
+    pub background_tex: Handle<Texture>,
     // Current level
     pub level: usize,
-    // Camera position
-    pub camera: Vec2i,
+    pub camera: Camera,
+    // Shakes and zooms gameplay wants, applied when the camera next moves
+    pub camera_events: Vec<CameraEvent>,
+    // A second player on the same keyboard, if one has joined
+    pub guest: Option<Guest>,
+    pub effects: Effects,
+    pub audio: Audio,
+    pub sounds: Sounds,
+    // Sounds looping out in the world, like lava and portals, to stop when we leave
+    pub ambience: Vec<VoiceId>,
     // background position
     pub background_pos: Vec2i,
     pub state_stack: Vec<Box<dyn State>>,
     pub game_data: GameData,
     pub map_x_boundary: i32,
     pub map_y_boundary: i32,
-    pub tt_tileset: Rc<Tileset>,
+    pub tt_tileset: Handle<Tileset>,
     pub maps: Vec<Tilemap>,
+    // What's been seen of the overworld's maps
+    pub minimap: Minimap,
     pub side_map: Vec<Tilemap>,
     // pub font: Font<'static>,
     pub game: usize,
+    // Where the player starts when entering the side scroller
+    pub start_point: Vec2i,
+    // Where the player respawns after dying, moved along by portals
     pub spawn_point: Vec2i,
     pub texts_overworld: Vec<Text>,
     pub texts_sidescroll: Vec<Text>,
 }
 
+/// A second local player on the same keyboard, playing the side scroller with WASD
+/// on their own half of the screen
+pub struct Guest {
+    pub entity: Entity,
+    pub camera: Camera,
+    // Like GameData::num_jumps, but for the guest
+    pub jumps: usize,
+}
+
+/// Every particle in the game, and the kinds of particle gameplay lets out
+pub struct Effects {
+    pub particles: Particles,
+    // Kicked up by jumping and landing
+    pub dust: Rc<ParticleStyle>,
+    // Left behind by dying
+    pub embers: Rc<ParticleStyle>,
+    // Around portals, and at both ends when someone goes through
+    pub glitter: Rc<ParticleStyle>,
+}
+
+impl Default for Effects {
+    fn default() -> Self {
+        Self {
+            particles: Particles::new(MAX_PARTICLES),
+            dust: Rc::new(ParticleStyle {
+                lifetime: (12, 24),
+                speed: (0.5, 1.5),
+                direction: -std::f32::consts::FRAC_PI_2,
+                spread: std::f32::consts::FRAC_PI_2,
+                gravity: 0.05,
+                start_color: Rgba(180, 160, 130, 200),
+                end_color: Rgba(180, 160, 130, 0),
+                size: 3,
+                ..ParticleStyle::default()
+            }),
+            embers: Rc::new(ParticleStyle {
+                lifetime: (30, 60),
+                speed: (1.0, 4.0),
+                gravity: 0.15,
+                start_color: Rgba(255, 200, 40, 255),
+                end_color: Rgba(160, 20, 0, 0),
+                size: 3,
+                ..ParticleStyle::default()
+            }),
+            glitter: Rc::new(ParticleStyle {
+                lifetime: (20, 40),
+                speed: (0.2, 1.0),
+                gravity: -0.02,
+                start_color: Rgba(200, 230, 255, 255),
+                end_color: Rgba(120, 80, 255, 0),
+                ..ParticleStyle::default()
+            }),
+        }
+    }
+}
+
+/// The sounds gameplay makes
+pub struct Sounds {
+    pub jump: Handle<Sound>,
+    pub land: Handle<Sound>,
+    pub hurt: Handle<Sound>,
+    pub portal: Handle<Sound>,
+    pub step: Handle<Sound>,
+    // These loop: lava bubbling, portals humming, and music in the side scroller
+    pub lava: Handle<Sound>,
+    pub hum: Handle<Sound>,
+    pub music: Handle<Sound>,
+}
+
+impl Sounds {
+    pub fn load(resources: &Resources) -> Result<Self, EngineError> {
+        Ok(Self {
+            jump: resources.load_sound("content/jump.wav")?,
+            land: resources.load_sound("content/land.wav")?,
+            hurt: resources.load_sound("content/hurt.wav")?,
+            portal: resources.load_sound("content/portal.wav")?,
+            step: resources.load_sound("content/step.wav")?,
+            lava: resources.load_sound("content/lava.wav")?,
+            hum: resources.load_sound("content/hum.wav")?,
+            music: resources.load_sound("content/music.wav")?,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub enum StateResult {
     // Pop this state off the stack, update the one before me
@@ -90,6 +229,13 @@ pub enum StateResult {
     Swap(Box<dyn State>),
     // Push a new state on top of this one, update it too
     Push(Box<dyn State>),
+    // Like Swap, but play a transition from this state to the new one first
+    SwapWith(Box<dyn State>, Transition),
+    // Like Push, but play a transition from this state to the new one first
+    PushWith(Box<dyn State>, Transition),
+    // Swap this state for a new one without running any enter/exit hooks,
+    // for wrapper states (like transitions) that already ran them
+    Replace(Box<dyn State>),
 }
 
 pub trait State: std::fmt::Debug {
@@ -112,6 +258,439 @@ pub trait State: std::fmt::Debug {
         screen: &mut Screen,
         frame: usize,
     );
+    // Lifecycle hooks, called by process_input as states come and go.
+    // on_enter: this state was just pushed or swapped in
+    fn on_enter(&mut self, _game: &mut GameState, _resources: &Resources, _levels: &Vec<Level>) {}
+    // on_exit: this state was just removed or swapped out
+    fn on_exit(&mut self, _game: &mut GameState, _resources: &Resources, _levels: &Vec<Level>) {}
+    // on_pause: another state was pushed on top of this one
+    fn on_pause(&mut self, _game: &mut GameState, _resources: &Resources, _levels: &Vec<Level>) {}
+    // on_resume: the state on top of this one was removed
+    fn on_resume(&mut self, _game: &mut GameState, _resources: &Resources, _levels: &Vec<Level>) {}
+}
+
+impl GameState {
+    pub fn player_pos(&self) -> Vec2f {
+        self.entities.get::<Position>(self.player).unwrap().0
+    }
+    // Copy the local player's entity into their networked/saved record
+    pub fn sync_player(&mut self) {
+        let pos = self.player_pos();
+        let vel = self.entities.get::<Velocity>(self.player).unwrap().0;
+        let record = self.players.get_mut(&self.server.id).unwrap();
+        record.pos = pos;
+        record.vel = vel;
+    }
+    pub fn player_rect(&self) -> Rect {
+        entity_rect(&self.entities, self.player)
+    }
+    // Spawn the enemies a level lists
+    pub fn spawn_enemies(&mut self, level: &Level) {
+        spawn_enemies(
+            &mut self.entities,
+            &level.1,
+            &self.enemy_tex,
+            &self.enemy_anim.get(),
+        );
+    }
+}
+
+// The middle of an entity's bottom edge, where dust comes from
+fn feet(entities: &World, e: Entity) -> Vec2f {
+    let r = entity_rect(entities, e);
+    Vec2i(r.center().0, r.bottom()).into()
+}
+
+// Where an entity with a position and size is, to the nearest pixel
+fn entity_rect(entities: &World, e: Entity) -> Rect {
+    let pos = entities.get::<Position>(e).unwrap().0;
+    let size = entities.get::<Size>(e).unwrap();
+    Rect::from_pos_size(pos.snap(), (size.0, size.1))
+}
+
+// Move every entity with a position, velocity and size out of the tilemaps and bodies it overlaps.
+// Returns what happened to whom.
+fn collide_bodies(
+    entities: &mut World,
+    maps: &Vec<Tilemap>,
+    triggers: &[Rect],
+) -> Vec<(Entity, CollisionEvent)> {
+    let mut ids = vec![];
+    let mut floats = vec![];
+    let mut sizes = vec![];
+    let mut bodies = vec![];
+    for (e, pos) in entities.query::<Position>() {
+        if let (Some(vel), Some(size)) = (entities.get::<Velocity>(e), entities.get::<Size>(e)) {
+            ids.push(e);
+            floats.push((pos.0, vel.0));
+            sizes.push((size.0, size.1));
+            bodies.push(entities.get::<Body>(e).copied().unwrap_or_default());
+        }
+    }
+    // Everything is collided at whole pixels, all at once so bodies can push each other
+    let snapped: Vec<Vec2i> = floats.iter().map(|(pos, _)| pos.snap()).collect();
+    let mut positions = snapped.clone();
+    let mut velocities: Vec<Vec2i> = floats.iter().map(|(_, vel)| vel.snap()).collect();
+    let mut events = vec![];
+    let mut contacts = vec![];
+    gather_contacts(&positions, &sizes, &bodies, maps, &mut contacts, &mut events);
+    restitute(
+        &mut positions,
+        &sizes,
+        &bodies,
+        &mut velocities,
+        maps,
+        &mut contacts,
+        &mut events,
+    );
+    detect_triggers(&positions, &sizes, triggers, &mut events);
+    for (i, (e, (mut pos, mut vel))) in ids.iter().zip(floats).enumerate() {
+        // Only the axes that got pushed lose their sub-pixel part
+        if positions[i].0 != snapped[i].0 {
+            pos.0 = positions[i].0 as f32;
+            vel.0 = 0.0;
+        }
+        if positions[i].1 != snapped[i].1 {
+            pos.1 = positions[i].1 as f32;
+            vel.1 = 0.0;
+        }
+        entities.insert(*e, Position(pos));
+        entities.insert(*e, Velocity(vel));
+    }
+    events.into_iter().map(|ev| (ids[ev.body()], ev)).collect()
+}
+
+// Arrow keys for the player: up jumps, or climbs on ladders; space jumps off them
+fn arrow_keys(key_input: &WinitInputHelper) -> PlatformerInput {
+    PlatformerInput {
+        left: key_input.key_held(VirtualKeyCode::Left),
+        right: key_input.key_held(VirtualKeyCode::Right),
+        jump_pressed: key_input.key_pressed(VirtualKeyCode::Up)
+            || key_input.key_pressed(VirtualKeyCode::Space),
+        jump_held: key_input.key_held(VirtualKeyCode::Up)
+            || key_input.key_held(VirtualKeyCode::Space),
+        up: key_input.key_held(VirtualKeyCode::Up),
+        down: key_input.key_held(VirtualKeyCode::Down),
+    }
+}
+
+// WASD for a guest, with Q to jump off ladders
+fn wasd_keys(key_input: &WinitInputHelper) -> PlatformerInput {
+    PlatformerInput {
+        left: key_input.key_held(VirtualKeyCode::A),
+        right: key_input.key_held(VirtualKeyCode::D),
+        jump_pressed: key_input.key_pressed(VirtualKeyCode::W)
+            || key_input.key_pressed(VirtualKeyCode::Q),
+        jump_held: key_input.key_held(VirtualKeyCode::W) || key_input.key_held(VirtualKeyCode::Q),
+        up: key_input.key_held(VirtualKeyCode::W),
+        down: key_input.key_held(VirtualKeyCode::S),
+    }
+}
+
+// Runs an entity's platformer controller and sweeps the move through the side scroller's tiles.
+// Returns where it wanted to end up and how fast it was going, for settle_platformer.
+fn step_platformer(
+    game: &mut GameState,
+    e: Entity,
+    input: PlatformerInput,
+    jumps: &mut usize,
+) -> (Vec2f, Vec2f) {
+    if !game.entities.has::<PlatformerController>(e) {
+        game.entities.insert(e, PlatformerController::default());
+    }
+    let mut pos = game.entities.get::<Position>(e).unwrap().0;
+    let controller = game.entities.get_mut::<PlatformerController>(e).unwrap();
+    let jumped_from = *jumps;
+    let vel = controller.step(input, jumps);
+    if *jumps > jumped_from {
+        let Effects { particles, dust, .. } = &mut game.effects;
+        let at = feet(&game.entities, e);
+        particles.burst(at, 6, dust);
+        let jump = &game.sounds.jump.get();
+        game.audio.mixer.play_at(jump, 0.5, at.snap(), EFFECT_FALLOFF);
+    }
+    let wanted = pos + vel;
+    // Sweep the whole-pixel part of the move so fast falls and dashes can't skip through walls
+    let size = *game.entities.get::<Size>(e).unwrap();
+    let swept = sweep_tiles(
+        pos.snap(),
+        (size.0, size.1),
+        wanted.snap() - pos.snap(),
+        &game.side_map,
+    );
+    pos = wanted;
+    if swept.blocked_x() {
+        pos.0 = swept.pos.0 as f32;
+    }
+    if swept.blocked_y() {
+        pos.1 = swept.pos.1 as f32;
+    }
+    game.entities.get_mut::<Position>(e).unwrap().0 = pos;
+    game.entities.get_mut::<Velocity>(e).unwrap().0 = vel;
+    (wanted, vel)
+}
+
+// After collision: tells an entity's controller where it ended up,
+// and deals with whatever it ran into. Shakes for its camera go in shakes.
+// Returns whether it respawned or went through a portal.
+fn settle_platformer(
+    game: &mut GameState,
+    e: Entity,
+    (wanted, vel): (Vec2f, Vec2f),
+    events: &[(Entity, CollisionEvent)],
+    jumps: &mut usize,
+    shakes: &mut Vec<CameraEvent>,
+) -> bool {
+    let events: Vec<CollisionEvent> = events
+        .iter()
+        .filter(|(who, _)| *who == e)
+        .map(|(_, ev)| *ev)
+        .collect();
+    let mut pos = game.entities.get::<Position>(e).unwrap().0;
+    let controller = game.entities.get_mut::<PlatformerController>(e).unwrap();
+    controller.resolve(wanted, pos);
+    controller.set_on_ladder(
+        events
+            .iter()
+            .any(|ev| matches!(ev, CollisionEvent::OnLadder { .. })),
+    );
+
+    let mut restart = enemy_touching(&game.entities, entity_rect(&game.entities, e));
+    let mut portal = None;
+    let landed = events
+        .iter()
+        .any(|ev| matches!(ev, CollisionEvent::Landed { .. }));
+    if landed && vel.1 >= HARD_LANDING_SPEED {
+        shakes.push(CameraEvent::Shake(LANDING_TRAUMA));
+    }
+    let Effects {
+        particles,
+        dust,
+        embers,
+        glitter,
+    } = &mut game.effects;
+    if landed && vel.1 >= LANDING_DUST_SPEED {
+        let at = feet(&game.entities, e);
+        particles.burst(at, 10, dust);
+        let land = &game.sounds.land.get();
+        game.audio.mixer.play_at(land, 0.6, at.snap(), EFFECT_FALLOFF);
+    }
+    for event in events {
+        match event {
+            CollisionEvent::Landed { tile, .. } | CollisionEvent::HitWall { tile, .. }
+                if tile.jump_reset =>
+            {
+                *jumps = 0;
+            }
+            CollisionEvent::TouchedHazard { .. } => restart = true,
+            CollisionEvent::EnteredTrigger { trigger, .. } => portal = Some(trigger),
+            _ => {}
+        }
+    }
+    let center = Vec2f::from(entity_rect(&game.entities, e).center());
+    if restart {
+        particles.burst(center, 40, embers);
+        let hurt = &game.sounds.hurt.get();
+        game.audio.mixer.play_at(hurt, 0.7, center.snap(), EFFECT_FALLOFF);
+        pos = game.spawn_point.into();
+        shakes.push(CameraEvent::Shake(DEATH_TRAUMA));
+    } else if let Some(i) = portal {
+        let end = game.game_data.portals[i].1;
+        let arrival = center + Vec2f::from(end) - pos;
+        particles.burst(center, 20, glitter);
+        particles.burst(arrival, 20, glitter);
+        let portal = &game.sounds.portal.get();
+        game.audio.mixer.play_at(portal, 0.6, arrival.snap(), EFFECT_FALLOFF);
+        pos = end.into();
+        game.spawn_point = end;
+    }
+    let teleported = pos != game.entities.get::<Position>(e).unwrap().0;
+    if teleported {
+        game.entities.get_mut::<PlatformerController>(e).unwrap().stop();
+        game.entities.get_mut::<Position>(e).unwrap().0 = pos;
+    }
+    teleported
+}
+
+// Brings a guest into the side scroller next to the player, or sends them away again.
+// While there's a guest the screen is split down the middle, so each camera sees half as much.
+pub fn toggle_guest(game: &mut GameState) {
+    let player_view = match game.guest.take() {
+        Some(guest) => {
+            game.entities.despawn(guest.entity);
+            (WIDTH, HEIGHT)
+        }
+        None => {
+            let e = game.entities.spawn();
+            let sprite = game.entities.get::<Sprite>(game.player).unwrap();
+            let sprite = Sprite {
+                texture: sprite.texture.clone(),
+                anim: sprite.anim.clone(),
+            };
+            let body = game.entities.get::<Body>(game.player).copied();
+            game.entities.insert(e, sprite);
+            game.entities.insert(e, Position(game.player_pos()));
+            game.entities.insert(e, Velocity(Vec2f(0.0, 0.0)));
+            game.entities.insert(e, Size(32, 32));
+            game.entities.insert(e, body.unwrap_or_default());
+            game.entities.insert(e, PlatformerController::default());
+            // They play under our id, so they're framed along with us in our world
+            game.entities.insert(e, PlayerId(game.server.id));
+            let mut camera = game.camera;
+            camera.view = (WIDTH / 2, HEIGHT);
+            camera.look_at(entity_rect(&game.entities, e));
+            game.guest = Some(Guest {
+                entity: e,
+                camera,
+                jumps: 0,
+            });
+            (WIDTH / 2, HEIGHT)
+        }
+    };
+    game.camera.view = player_view;
+    let target = game.player_rect();
+    game.camera.look_at(target);
+}
+
+// Draws the side scroller as seen by one camera
+fn draw_side(game: &GameState, levels: &[Level], screen: &mut Screen, camera: &Camera) {
+    let (w, h) = screen.size();
+    screen.set_scroll(Vec2i(0, 0));
+    screen.bitblt(
+        &game.background_tex.get(),
+        Rect {
+            x: 0,
+            y: 0,
+            w: w as u16,
+            h: h as u16,
+        },
+        game.background_pos,
+    );
+    screen.set_scroll(camera.offset());
+    levels[game.level].0[0].draw(screen);
+    draw_players(game, screen);
+    game.effects.particles.draw(screen);
+    for text_img in &game.texts_sidescroll {
+        screen.bitblt(&text_img.tex, text_img.from, text_img.to);
+    }
+    // Everything so far was drawn at the camera's scale
+    screen.upscale(camera.scale());
+}
+
+// Draw every player in the local player's world, using the local player's sprite
+fn draw_players(game: &GameState, screen: &mut Screen) {
+    let sprite = game.entities.get::<Sprite>(game.player).unwrap();
+    let cur_world = game.players[&game.server.id].world;
+    for player in game.players.iter().filter(|p| p.1.world == cur_world) {
+        screen.bitblt(&sprite.texture.get(), sprite.anim.frame(), player.1.pos.snap());
+    }
+    // Then everything else that has a sprite
+    for (e, pos, sprite) in game.entities.query2::<Position, Sprite>() {
+        if e != game.player {
+            screen.bitblt(&sprite.texture.get(), sprite.anim.frame(), pos.0.snap());
+        }
+    }
+}
+
+// The minimap in the top-right corner, with every player in the overworld on it
+fn draw_minimap(game: &GameState, screen: &mut Screen) {
+    let (w, _) = screen.size();
+    let (mw, mh) = MINIMAP_SIZE;
+    let r = Rect {
+        x: w as i32 - mw as i32 - MINIMAP_MARGIN,
+        y: MINIMAP_MARGIN,
+        w: mw,
+        h: mh,
+    };
+    screen.set_scroll(Vec2i(0, 0));
+    screen.rect(r.expand(1), Rgba(0, 0, 0, 255));
+    let cur_world = game.players[&game.server.id].world;
+    let markers: Vec<(Vec2i, Rgba)> = game
+        .players
+        .iter()
+        .filter(|(_, p)| p.world == cur_world)
+        .map(|(id, p)| {
+            // We're yellow, everyone else is red
+            let col = if *id == game.server.id {
+                Rgba(255, 220, 0, 255)
+            } else {
+                Rgba(220, 30, 30, 255)
+            };
+            (p.pos.snap() + Vec2i(TILE_SZ as i32 / 2, TILE_SZ as i32 / 2), col)
+        })
+        .collect();
+    let center = game.player_rect().center();
+    game.minimap.draw(&mut screen.viewport(r, Vec2i(0, 0)), center, &markers);
+}
+
+// Sounds out in the world are heard from the middle of the (player's) camera
+fn listen_from_camera(game: &mut GameState) {
+    game.audio.mixer.listener = Listener {
+        pos: game.camera.view_rect().center(),
+        pan_range: game.camera.view_size().0 as f32 / 2.0,
+    };
+}
+
+// The middle of each row of hazard tiles, where the lava bubbles
+fn hazard_spots(maps: &[Tilemap]) -> Vec<Vec2i> {
+    let mut spots = vec![];
+    for map in maps {
+        let mut run: Option<Rect> = None;
+        for (tile, r) in map.tiles_in(map.bounds()) {
+            // A run ends at a gap or at the end of a row
+            let continues = run.is_some_and(|run| run.right() == r.x && run.y == r.y);
+            if !(tile.restart && continues) {
+                if let Some(run) = run.take() {
+                    spots.push(run.center());
+                }
+            }
+            if tile.restart {
+                run = Some(run.map_or(r, |run| run.union(r)));
+            }
+        }
+        if let Some(run) = run {
+            spots.push(run.center());
+        }
+    }
+    spots
+}
+
+// The area covered by a set of tilemaps
+fn map_bounds(maps: &[Tilemap]) -> Option<Rect> {
+    maps.iter().map(Tilemap::bounds).reduce(|a, b| a.union(b))
+}
+
+// Move the camera after everything else has, framing every local player in our world.
+// We go first, so we're the one it sticks with if they don't all fit.
+pub fn update_camera(game: &mut GameState) {
+    for event in game.camera_events.drain(..) {
+        game.camera.handle(event);
+    }
+    let world = game.players.get(&game.server.id).map(|p| p.world);
+    let mut targets = vec![game.player_rect()];
+    targets.extend(
+        game.entities
+            .query2::<PlayerId, Position>()
+            .filter(|(e, id, _)| {
+                *e != game.player && game.players.get(&id.0).map(|p| p.world) == world
+            })
+            .filter_map(|(e, _, pos)| {
+                let size = game.entities.get::<Size>(e)?;
+                Some(Rect::from_pos_size(pos.0.snap(), (size.0, size.1)))
+            }),
+    );
+    let vel = game.entities.get::<Velocity>(game.player).unwrap().0;
+    game.camera.frame(&targets, vel);
+}
+
+// + and - zoom in and out
+fn zoom_keys(game: &mut GameState, key_input: &WinitInputHelper) {
+    if key_input.key_pressed(VirtualKeyCode::Equals) {
+        game.camera_events.push(CameraEvent::ZoomIn);
+    }
+    if key_input.key_pressed(VirtualKeyCode::Minus) {
+        game.camera_events.push(CameraEvent::ZoomOut);
+    }
 }
 
 /*
@@ -132,101 +711,79 @@ impl State for Title {
         key_input: &WinitInputHelper,
     ) -> StateResult {
         // _game.positions[0] = Vec2i(levels[1].1[0].1 * 16, levels[1].1[0].2 * 16);
-        let cur_player = _game.players.get_mut(&_game.server.id).unwrap();
+        let mut pos = _game.player_pos();
         if key_input.key_held(VirtualKeyCode::Right) {
-            cur_player.pos.0 += 5;
-            if cur_player.pos.0 > (TILE_MAP_SIZE - TILE_SZ) as i32 {
-                _game.camera.0 += 5;
-            }
-            // generate tile map
-            if _game.camera.0 >= (_game.map_x_boundary - WIDTH as i32) {
-                let mut i: i32 = 0;
-                let mut psn: Vec2i = Vec2i(_game.map_x_boundary, 0);
-                while i < _game.map_y_boundary {
-                    _game.maps.push(Tileset::create_map(&_game.tt_tileset, psn, _game.game));
-                    psn.1 += TILE_MAP_SIZE as i32;
-                    i += TILE_MAP_SIZE as i32;
-                }
-                _game.map_x_boundary += TILE_MAP_SIZE as i32;
-            }
+            pos.0 += 5.0;
         }
-        if key_input.key_held(VirtualKeyCode::Left) {
-            if cur_player.pos.0 > 0 {
-                cur_player.pos.0 += -7;
-            }
-            if _game.camera.0 > 0 {
-                _game.camera.0 -= 7;
-            }
+        if key_input.key_held(VirtualKeyCode::Left) && pos.0 > 0.0 {
+            pos.0 += -7.0;
         }
-        if key_input.key_held(VirtualKeyCode::Up) {
-            if cur_player.pos.1 > 0 {
-                cur_player.pos.1 += -7;
-            }
-            if _game.camera.1 > 0 {
-                _game.camera.1 -= 7;
-            }
+        if key_input.key_held(VirtualKeyCode::Up) && pos.1 > 0.0 {
+            pos.1 += -7.0;
         }
         if key_input.key_held(VirtualKeyCode::Down) {
-            cur_player.pos.1 += 7;
-            if cur_player.pos.1 > (TILE_MAP_SIZE - TILE_SZ) as i32 {
-                _game.camera.1 += 7;
+            pos.1 += 7.0;
+        }
+
+        _game.entities.get_mut::<Position>(_game.player).unwrap().0 = pos;
+        collide_bodies(&mut _game.entities, &_game.maps, &[]);
+        zoom_keys(_game, key_input);
+        update_camera(_game);
+        listen_from_camera(_game);
+
+        // generate tile map once the camera reaches the edge of what's there
+        let view = _game.camera.view_rect();
+        if view.right() >= _game.map_x_boundary {
+            let mut i: i32 = 0;
+            let mut psn: Vec2i = Vec2i(_game.map_x_boundary, 0);
+            while i < _game.map_y_boundary {
+                _game.maps.push(Tileset::create_map(&_game.tt_tileset.get(), psn, _game.game));
+                psn.1 += TILE_MAP_SIZE as i32;
+                i += TILE_MAP_SIZE as i32;
             }
-            if _game.camera.1 >= (_game.map_y_boundary - HEIGHT as i32) {
-                let mut i: i32 = 0;
-                let mut psn: Vec2i = Vec2i(0, _game.map_y_boundary);
-                while i < _game.map_x_boundary {
-                    _game.maps.push(Tileset::create_map(&_game.tt_tileset, psn, _game.game));
-                    psn.0 += TILE_MAP_SIZE as i32;
-                    i += TILE_MAP_SIZE as i32;
-                }
-                _game.map_y_boundary += TILE_MAP_SIZE as i32;
+            _game.map_x_boundary += TILE_MAP_SIZE as i32;
+        }
+        if view.bottom() >= _game.map_y_boundary {
+            let mut i: i32 = 0;
+            let mut psn: Vec2i = Vec2i(0, _game.map_y_boundary);
+            while i < _game.map_x_boundary {
+                _game.maps.push(Tileset::create_map(&_game.tt_tileset.get(), psn, _game.game));
+                psn.0 += TILE_MAP_SIZE as i32;
+                i += TILE_MAP_SIZE as i32;
             }
+            _game.map_y_boundary += TILE_MAP_SIZE as i32;
         }
 
-        let mut all_pos: Vec<Vec2i> = vec![cur_player.pos];
-        let mut all_vel: Vec<Vec2i> = vec![cur_player.vel];
-        // reset number of jumps
-        // Detect collisions: Convert positions and sizes to collision bodies, generate contacts
-        // Outline of a possible approach to tile collision:
-        let mut contacts = vec![];
-        gather_contacts(
-            all_pos.as_slice(),
-            &_game.sizes,
-            &_game.maps,
-            &mut contacts,
-            &mut _game.game_data.num_jumps,
-            &mut _game.game_data.restart,
-        );
-        restitute(
-            all_pos.as_mut_slice(),
-            &_game.sizes,
-            all_vel.as_mut_slice(),
-            &mut _game.camera,
-            &_game.maps,
-            &mut contacts,
-        );
-        cur_player.pos = all_pos[0];
-        cur_player.vel = all_vel[0];
-
-        // _game.camera = cur_player.pos;
+        // _game.camera = pos;
+        let view = _game.camera.view_rect();
+        _game.minimap.explore(&_game.maps, view);
 
+        _game.sync_player();
         _game.server.update_players(&mut _game.players);
 
         if key_input.key_held(VirtualKeyCode::P) {
             // println!("hitting p");
-            _game.players.get_mut(&_game.server.id).unwrap().vel = Vec2i(0, 0);
-            _game.players.get_mut(&_game.server.id).unwrap().world = 1;
-            if _game.game == 2 {
-                _game.players.get_mut(&_game.server.id).unwrap().pos.0 = 3 * 32;
-                _game.players.get_mut(&_game.server.id).unwrap().pos.1 = 37 * 32;
-            } else {
-                _game.players.get_mut(&_game.server.id).unwrap().pos = Vec2i(50, 50);
-            }
-            StateResult::Swap(Box::new(Scroll()))
+            StateResult::SwapWith(Box::new(Scroll()), Transition::new(Effect::Iris, 40))
         } else {
             StateResult::Keep
         }
     }
+    fn on_enter(&mut self, _game: &mut GameState, resources: &Resources, levels: &Vec<Level>) {
+        _game.entities.get_mut::<Velocity>(_game.player).unwrap().0 = Vec2f(0.0, 0.0);
+        _game.players.get_mut(&_game.server.id).unwrap().world = 0;
+        // The overworld is roomy, so the camera lags a little behind
+        _game.camera.dead_zone = (64, 48);
+        _game.camera.smoothing = 0.25;
+        _game.camera.look_ahead = Vec2f(0.0, 0.0);
+        // The generated maps start at the origin and grow right and down as far as we go,
+        // generated as the view reaches their edge
+        _game.camera.bounds = None;
+        _game.camera.top_left = Some(Vec2i(0, 0));
+        let target = _game.player_rect();
+        _game.camera.look_at(target);
+        let view = _game.camera.view_rect();
+        _game.minimap.explore(&_game.maps, view);
+    }
     #[allow(unused_variables)]
     fn display(
         &self,
@@ -238,28 +795,13 @@ impl State for Title {
     ) {
         // println!("Title: p to play");
         screen.clear(Rgba(211, 211, 211, 255));
-        screen.set_scroll(_game.camera);
+        screen.set_scroll(_game.camera.offset());
         // levels[_game.level].0.draw(screen);
         // let maps = &levels[1].0;
         for map in _game.maps.iter() {
             map.draw(screen);
         }
-        // draw main player
-        // let curpos = _game.players[&_game.server.id].pos;
-        // println!("player pos {:?}", &_game.textures[1].image);
-        // screen.bitblt(&_game.textures[0], _game.anim_state[0].frame(), curpos);
-        // draw positions of other players
-        let cur_world = _game.players[&_game.server.id].world;
-        for player in _game.players.iter()
-            .filter(|p| p.1.world == cur_world)
-        {
-            // println!("drawing character {}", player.0);
-            screen.bitblt(
-                &_game.textures[0],
-                _game.anim_state[0].frame(),
-                player.1.pos,
-            );
-        }
+        draw_players(_game, screen);
         for text_img in &_game.texts_overworld {
             screen.bitblt(
                 &text_img.tex,
@@ -267,6 +809,9 @@ impl State for Title {
                 text_img.to,
             );
         }
+        // Everything so far was drawn at the camera's scale
+        screen.upscale(_game.camera.scale());
+        draw_minimap(_game, screen);
     }
 }
 
@@ -284,150 +829,141 @@ impl State for Scroll {
         frame: usize,
         key_input: &WinitInputHelper,
     ) -> StateResult {
-        _game.level = 0;
-        let cur_player = _game.players.get_mut(&_game.server.id).unwrap();
-        // StateResult::Keep
-        let max_vel = 10;
-        let min_vel = -10;
-        let mut held_up = false;
-        let mut vert_moving: bool = false;
-        let mut horiz_moving = false;
-        // println!("before {:}", _game.velocities[0].0);
+        let player = _game.player;
         if key_input.key_held(VirtualKeyCode::Right) {
-            // _game.velocities[0].0 = 7;
-            // println!("right vel {:}", _game.velocities[0].0);
-            if cur_player.vel.0 < max_vel {
-                // println!("inside");
-                cur_player.vel.0 += 2;
-                // println!("inside {:}", _game.velocities[0].0);
-            }
-            horiz_moving = true;
-            _game.anim_state[0].tick();
-        } else if key_input.key_released(VirtualKeyCode::Right) {
-            //cur_player.vel.0 = (cur_player.vel.0 as f32 * 0.25) as i32; 
-            // println!("after {:}", _game.velocities[0].0);
-        }
-        if key_input.key_held(VirtualKeyCode::Left) {
-            if cur_player.vel.0 > min_vel {
-                // println!("inside");
-                cur_player.vel.0 -= 2;
-                // println!("inside {:}", _game.velocities[0].0);
-            }
-            horiz_moving = true;
-        } else if key_input.key_released(VirtualKeyCode::Left) {
-            //cur_player.vel.0 = (cur_player.vel.0 as f32 * 0.25) as i32;
-        }
-        if key_input.key_held(VirtualKeyCode::Up) {
-            if _game.game_data.num_jumps < 2 && cur_player.vel.1 >= 0 && cur_player.vel.1 >= min_vel {
-                cur_player.vel.1 = -18;
-                _game.game_data.num_jumps += 1;
-            } else if _game.game_data.num_jumps < 2 && cur_player.vel.1 < 0 {
-                cur_player.vel.1 += -6;
-            }
-            vert_moving = true;
-            held_up = true;
-            if cur_player.vel.1 == 0 {
-                held_up = false;
-            }
-        } else if key_input.key_released(VirtualKeyCode::Up) {}
-        if key_input.key_held(VirtualKeyCode::Down) {
-            if cur_player.vel.1 < max_vel {
-                cur_player.vel.1 += 2;
-                vert_moving = true;
-                // println!("inside {:}", _game.velocities[0].0);
+            _game.entities.get_mut::<Sprite>(player).unwrap().anim.tick();
+        }
+        if key_input.key_pressed(VirtualKeyCode::G) {
+            toggle_guest(_game);
+        }
+        let mut jumps = _game.game_data.num_jumps;
+        let moved = step_platformer(_game, player, arrow_keys(key_input), &mut jumps);
+        let mut guest = _game.guest.as_ref().map(|g| (g.entity, g.jumps));
+        let guest_moved = guest.as_mut().map(|(e, jumps)| {
+            if key_input.key_held(VirtualKeyCode::D) {
+                _game.entities.get_mut::<Sprite>(*e).unwrap().anim.tick();
             }
-        } else if key_input.key_released(VirtualKeyCode::Down) {
-            // cur_player.vel.1 = (cur_player.vel.1 as f32 * 0.25) as i32;
-        }
-
-        if !vert_moving {
-            cur_player.vel.1 = (cur_player.vel.1 as f32 * 0.5) as i32;
-        }
-        if !horiz_moving {
-            cur_player.vel.0 = (cur_player.vel.0 as f32 * 0.5) as i32;
-        }
-
-        //if player is not holding the up key, start falling. 
-        if !held_up {
-            cur_player.vel.1 = 4;
-        }
-        // Update all entities' positions
-        // update current player
-        // println!("{}", cur_player.vel.1);
-        cur_player.pos.0 += cur_player.vel.0;
-        cur_player.pos.1 += cur_player.vel.1;
-
-        if cur_player.vel.1 < 0 {
-            cur_player.vel.1 /= 2;
-        }
-        if cur_player.vel.1 == -6 {
-            cur_player.vel.1 = 2;
-        }
-
-        // for (posn, vel) in _game.positions.iter_mut().zip(_game.velocities.iter()) {
-        //     posn.0 += vel.0;
-        //     posn.1 += vel.1;
-        // }
-        let mut all_pos: Vec<Vec2i> = vec![cur_player.pos];
-        let mut all_vel: Vec<Vec2i> = vec![cur_player.vel];
-        // reset number of jumps
-        // Detect collisions: Convert positions and sizes to collision bodies, generate contacts
-        // Outline of a possible approach to tile collision:
-        let mut contacts = vec![];
-        gather_contacts(
-            all_pos.as_slice(),
-            &_game.sizes,
-            &_game.side_map,
-            &mut contacts,
-            &mut _game.game_data.num_jumps,
-            &mut _game.game_data.restart,
-        );
-        restitute(
-            all_pos.as_mut_slice(),
-            &_game.sizes,
-            all_vel.as_mut_slice(),
-            &mut _game.camera,
-            &_game.side_map,
-            &mut contacts,
-        );
-        cur_player.pos = all_pos[0];
-        cur_player.vel = all_vel[0];
+            step_platformer(_game, *e, wasd_keys(key_input), jumps)
+        });
+
+        let pos = _game.player_pos();
+        update_enemies(&mut _game.entities, &_game.side_map, pos.snap(), frame);
+        let portals: Vec<Rect> = _game
+            .game_data
+            .portals
+            .iter()
+            .map(|(start, _)| Rect::from_pos_size(*start, (TILE_SZ, TILE_SZ)))
+            .collect();
+        let events = collide_bodies(&mut _game.entities, &_game.side_map, &portals);
 
-        if _game.game_data.restart {
-            cur_player.pos = _game.spawn_point;
-            _game.game_data.restart = false;
+        let mut shakes = vec![];
+        let teleported = settle_platformer(_game, player, moved, &events, &mut jumps, &mut shakes);
+        _game.game_data.num_jumps = jumps;
+        _game.camera_events.append(&mut shakes);
+        if _game.player_pos().snap() == Vec2i(114*32, 33*32) {
+            println!("win!");
+        }
+        _game.camera.bounds = map_bounds(&_game.side_map);
+        if teleported {
+            let target = _game.player_rect();
+            _game.camera.look_at(target);
+        } else {
+            zoom_keys(_game, key_input);
+            update_camera(_game);
         }
+        listen_from_camera(_game);
 
-        // println!("player pos {:?}", cur_player.pos);
-        for (start, end) in _game.game_data.portals.iter(){
-            if *start == cur_player.pos && _game.game == 1{
-                cur_player.pos = *end;
-                _game.spawn_point = *end;
+        if let (Some((e, mut jumps)), Some(moved)) = (guest, guest_moved) {
+            let teleported = settle_platformer(_game, e, moved, &events, &mut jumps, &mut shakes);
+            let target = entity_rect(&_game.entities, e);
+            let vel = _game.entities.get::<Velocity>(e).unwrap().0;
+            let bounds = map_bounds(&_game.side_map);
+            let guest = _game.guest.as_mut().unwrap();
+            guest.jumps = jumps;
+            guest.camera.bounds = bounds;
+            for shake in shakes {
+                guest.camera.handle(shake);
             }
-            if *start == cur_player.pos && _game.game == 2{
-                cur_player.pos = *end;
-                _game.spawn_point = *end;
+            if teleported {
+                guest.camera.look_at(target);
+            } else {
+                guest.camera.follow(target, vel);
             }
         }
 
-        if cur_player.pos == Vec2i(114*32, 33*32) {
-            println!("win!");
-        }
-
-        _game.camera.0 = _game.players[&_game.server.id].pos.0 - (WIDTH / 2) as i32;
-        _game.camera.1 = _game.players[&_game.server.id].pos.1 - (HEIGHT / 2) as i32;
+        _game.effects.particles.update();
 
+        _game.sync_player();
         _game.server.update_players(&mut _game.players);
+        // Other players' footsteps, while they're running along the ground
+        if frame.is_multiple_of(FOOTSTEP_FRAMES) {
+            let me = _game.server.id;
+            for (id, p) in _game.players.iter() {
+                let running = p.vel.0.abs() > 0.5 && p.vel.1.abs() < 0.5;
+                if *id != me && p.world == 1 && running {
+                    let feet = p.pos.snap() + Vec2i(TILE_SZ as i32 / 2, TILE_SZ as i32);
+                    let step = &_game.sounds.step.get();
+                    _game.audio.mixer.play_at(step, 0.5, feet, FOOTSTEP_FALLOFF);
+                }
+            }
+        }
 
         if key_input.key_held(VirtualKeyCode::X) {
             // StateResult::Remove
-            _game.players.get_mut(&_game.server.id).unwrap().vel = Vec2i(0, 0);
-            _game.players.get_mut(&_game.server.id).unwrap().world = 0;
-            StateResult::Swap(Box::new(Title()))
+            StateResult::SwapWith(Box::new(Title()), Transition::new(Effect::FadeToBlack, 30))
         } else {
             StateResult::Keep
         }
     }
+    fn on_enter(&mut self, _game: &mut GameState, resources: &Resources, levels: &Vec<Level>) {
+        _game.level = 0;
+        let player = _game.player;
+        _game.entities.get_mut::<Velocity>(player).unwrap().0 = Vec2f(0.0, 0.0);
+        _game.entities.get_mut::<Position>(player).unwrap().0 = _game.start_point.into();
+        _game.entities.insert(player, PlatformerController::default());
+        _game.players.get_mut(&_game.server.id).unwrap().world = 1;
+        _game.game_data.num_jumps = 0;
+        // Lead the player in the direction they're running so they can see what's coming
+        _game.camera.dead_zone = (32, 64);
+        _game.camera.smoothing = 0.15;
+        _game.camera.look_ahead = Vec2f(12.0, 0.0);
+        _game.camera.bounds = map_bounds(&_game.side_map);
+        _game.camera.top_left = None;
+        let target = _game.player_rect();
+        _game.camera.look_at(target);
+        despawn_enemies(&mut _game.entities);
+        _game.spawn_enemies(&levels[0]);
+        // Portals sparkle a little all the time so they're easy to spot
+        let half = Vec2i(TILE_SZ as i32 / 2, TILE_SZ as i32 / 2);
+        for (start, _) in _game.game_data.portals.iter() {
+            let sparkle = Emitter::continuous((*start + half).into(), &_game.effects.glitter, 0.3);
+            _game.effects.particles.emit(sparkle);
+        }
+        _game.audio.mixer.play_music(&_game.sounds.music.get(), 0.4, 1.0);
+        // Lava bubbles and portals hum, louder the closer you get
+        listen_from_camera(_game);
+        for spot in hazard_spots(&_game.side_map) {
+            let lava = &_game.sounds.lava.get();
+            let id = _game.audio.mixer.play_looping_at(lava, 0.5, spot, AMBIENT_FALLOFF);
+            _game.ambience.push(id);
+        }
+        for (start, _) in _game.game_data.portals.iter() {
+            let hum = &_game.sounds.hum.get();
+            let id = _game.audio.mixer.play_looping_at(hum, 0.4, *start + half, AMBIENT_FALLOFF);
+            _game.ambience.push(id);
+        }
+    }
+    fn on_exit(&mut self, _game: &mut GameState, resources: &Resources, levels: &Vec<Level>) {
+        despawn_enemies(&mut _game.entities);
+        _game.effects.particles.clear();
+        _game.audio.mixer.stop_music(1.0);
+        for id in _game.ambience.drain(..) {
+            _game.audio.mixer.stop(id, 0.5);
+        }
+        if _game.guest.is_some() {
+            toggle_guest(_game);
+        }
+    }
     fn display(
         &self,
         _game: &GameState,
@@ -436,41 +972,54 @@ impl State for Scroll {
         screen: &mut Screen,
         frame: usize,
     ) {
-        // println!("Title: p to play");
-        // screen.clear(Rgba(80, 80, 80, 255));
-        screen.bitblt(
-            &_game.textures[2],
-            Rect {
-                x: 0,
-                y: 0,
-                w: WIDTH as u16,
-                h: HEIGHT as u16,
-            },
-            _game.background_pos,
-        );
-        screen.set_scroll(_game.camera);
-        // let x = levels[0].0[0];
-        levels[_game.level].0[0].draw(screen); //levels[0].0
-        let cur_world = _game.players[&_game.server.id].world;
-        for player in _game.players.iter()
-            .filter(|p| p.1.world == cur_world)
-        {
-            screen.bitblt(
-                &_game.textures[0],
-                _game.anim_state[0].frame(),
-                player.1.pos,
-            );
+        match &_game.guest {
+            None => draw_side(_game, levels, screen, &_game.camera),
+            Some(guest) => {
+                // Side by side, with the player on the left
+                let (w, h) = screen.size();
+                let left = Rect {
+                    x: 0,
+                    y: 0,
+                    w: (w / 2) as u16,
+                    h: h as u16,
+                };
+                let right = left.translate(Vec2i(left.w as i32, 0));
+                draw_side(_game, levels, &mut screen.viewport(left, Vec2i(0, 0)), &_game.camera);
+                draw_side(_game, levels, &mut screen.viewport(right, Vec2i(0, 0)), &guest.camera);
+                screen.set_scroll(Vec2i(0, 0));
+                screen.rect(
+                    Rect {
+                        x: right.x - 1,
+                        y: 0,
+                        w: 2,
+                        h: h as u16,
+                    },
+                    Rgba(0, 0, 0, 255),
+                );
+            }
         }
-        for text_img in &_game.texts_sidescroll {
-            screen.bitblt(
-                &text_img.tex,
-                text_img.from,
-                text_img.to,
-            );
+    }
+}
+
+/// Runs the enter hooks of the states a game starts with, bottom first, as if each had been
+/// pushed onto the one below it
+pub fn enter_stack(game: &mut GameState, resources: &Resources, levels: &Vec<Level>) {
+    let starting = std::mem::take(&mut game.state_stack);
+    for mut state in starting {
+        if let Some(mut below) = game.state_stack.pop() {
+            below.on_pause(game, resources, levels);
+            game.state_stack.push(below);
         }
+        state.on_enter(game, resources, levels);
+        game.state_stack.push(state);
     }
 }
 
+/// Runs the state stack for one frame.
+/// On the first frame, the states the game started with are entered before anything's updated.
+/// The top state is updated; if it asks to change the stack, the new top is updated too,
+/// up to MAX_TRANSITIONS_PER_FRAME times so a state that keeps swapping can't hang the game.
+/// Returns Control::Quit once the stack is empty.
 pub fn process_input(
     game: &mut GameState,
     // input: &Input,
@@ -478,20 +1027,59 @@ pub fn process_input(
     levels: &Vec<Level>,
     frame: usize,
     key_input: &WinitInputHelper,
-) {
-    let mut this_state = game.state_stack.pop().unwrap();
-    // println!("input {:?} on state {:?}", this_state);
-    match this_state.update(game, resources, levels, frame, key_input) {
-        StateResult::Remove => process_input(game, resources, levels, frame, key_input),
-        StateResult::Keep => game.state_stack.push(this_state),
-        StateResult::Push(new_state) => {
-            game.state_stack.push(this_state);
-            game.state_stack.push(new_state);
-            process_input(game, resources, levels, frame, key_input);
-        }
-        StateResult::Swap(new_state) => {
-            game.state_stack.push(new_state);
-            process_input(game, resources, levels, frame, key_input);
+) -> Control {
+    if frame == 0 {
+        enter_stack(game, resources, levels);
+    }
+    for _ in 0..MAX_TRANSITIONS_PER_FRAME {
+        let mut this_state = match game.state_stack.pop() {
+            Some(state) => state,
+            None => return Control::Quit,
+        };
+        // println!("input {:?} on state {:?}", this_state);
+        match this_state.update(game, resources, levels, frame, key_input) {
+            StateResult::Remove => {
+                this_state.on_exit(game, resources, levels);
+                if let Some(mut below) = game.state_stack.pop() {
+                    below.on_resume(game, resources, levels);
+                    game.state_stack.push(below);
+                }
+            }
+            StateResult::Keep => {
+                game.state_stack.push(this_state);
+                return Control::Continue;
+            }
+            StateResult::Push(mut new_state) => {
+                this_state.on_pause(game, resources, levels);
+                game.state_stack.push(this_state);
+                new_state.on_enter(game, resources, levels);
+                game.state_stack.push(new_state);
+            }
+            StateResult::Swap(mut new_state) => {
+                this_state.on_exit(game, resources, levels);
+                new_state.on_enter(game, resources, levels);
+                game.state_stack.push(new_state);
+            }
+            StateResult::PushWith(new_state, transition) => {
+                this_state.on_pause(game, resources, levels);
+                game.state_stack.push(this_state);
+                game.state_stack
+                    .push(Box::new(Transitioning::push(new_state, transition)));
+            }
+            StateResult::SwapWith(new_state, transition) => {
+                game.state_stack.push(Box::new(Transitioning::swap(
+                    this_state, new_state, transition,
+                )));
+            }
+            StateResult::Replace(new_state) => {
+                game.state_stack.push(new_state);
+            }
         }
     }
+    // Out of budget: whatever is on top now gets its update next frame
+    if game.state_stack.is_empty() {
+        Control::Quit
+    } else {
+        Control::Continue
+    }
 }
@@ -1,7 +1,12 @@
+use crate::error::EngineError;
 use crate::types::Rect;
-use image::{self, RgbaImage};
+use image::{self, ImageError, ImageFormat, Rgba, RgbaImage};
 use std::path::Path;
 
+// The placeholder's squares, and how many there are each way
+const MISSING_SQUARE: u32 = 8;
+const MISSING_SQUARES: u32 = 4;
+
 pub struct Texture {
     pub image: Vec<u8>,
     width: usize,
@@ -15,8 +20,19 @@ enum AlphaChannel {
     Last,
 }
 impl Texture {
-    pub fn with_file(path: &Path) -> Self {
-        Self::new(image::open(path).expect("Couldn't load image").into_rgba8())
+    pub fn with_file(path: &Path) -> Result<Self, EngineError> {
+        Ok(Self::new(open_image(path)?))
+    }
+    /// A loud magenta and black checkerboard to stand in for textures that didn't load
+    pub fn missing() -> Self {
+        let side = MISSING_SQUARE * MISSING_SQUARES;
+        Self::new(RgbaImage::from_fn(side, side, |x, y| {
+            if (x / MISSING_SQUARE + y / MISSING_SQUARE).is_multiple_of(2) {
+                Rgba([255, 0, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        }))
     }
     pub fn new(image: RgbaImage) -> Self {
         let (width, height) = image.dimensions();
@@ -41,12 +57,38 @@ impl Texture {
     pub fn buffer(&self) -> &[u8] {
         &self.image
     }
+    /// Makes sure the texture is a whole number of cells each way, e.g. tiles in a tilesheet.
+    /// path is what to blame if it isn't.
+    pub fn check_grid(&self, path: &Path, cell: (usize, usize)) -> Result<(), EngineError> {
+        if self.width.is_multiple_of(cell.0) && self.height.is_multiple_of(cell.1) {
+            Ok(())
+        } else {
+            Err(EngineError::WrongDimensions {
+                path: path.to_path_buf(),
+                size: self.size(),
+                cell,
+            })
+        }
+    }
     pub fn valid_frame(&self, frame: Rect) -> bool {
         0 <= frame.x
-            && (frame.x + frame.w as i32) <= (self.width as i32)
+            && frame.right() <= (self.width as i32)
             && 0 <= frame.y
-            && (frame.y + frame.h as i32) <= (self.height as i32)
+            && frame.bottom() <= (self.height as i32)
+    }
+}
+
+/// Reads an image file as it is, without premultiplying
+pub fn open_image(path: &Path) -> Result<RgbaImage, EngineError> {
+    if ImageFormat::from_path(path).is_err() {
+        return Err(EngineError::UnsupportedFormat(path.to_path_buf()));
     }
+    let image = image::open(path).map_err(|err| match err {
+        ImageError::IoError(err) => EngineError::from_io(path, err),
+        ImageError::Unsupported(_) => EngineError::UnsupportedFormat(path.to_path_buf()),
+        err => EngineError::decode_failed(path, err),
+    })?;
+    Ok(image.into_rgba8())
 }
 
 fn premultiply(img: &mut [u8], depth: usize, alpha: AlphaChannel) {
@@ -1,7 +1,11 @@
+use crate::error::EngineError;
 use crate::graphics::Screen;
+use crate::resources::Handle;
 use crate::texture::Texture;
 use crate::types::*;
 use rand::Rng;
+use std::cell::RefCell;
+use std::path::Path;
 use std::{rc::Rc, usize};
 
 pub const TILE_SZ: usize = 32;
@@ -11,14 +15,94 @@ pub struct Tile {
     pub solid: bool, // ... any extra data like collision flags or other properties
     pub jump_reset: bool,
     pub restart: bool,
+    // Which part of a solid tile blocks movement
+    pub shape: TileShape,
+}
+
+/// The collision shape of a solid tile
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TileShape {
+    /// The whole square
+    Full,
+    /// Just the bottom or top half of the square
+    HalfBottom,
+    HalfTop,
+    /// A platform you can jump up through and land on from above
+    OneWay,
+    /// A floor rising from `left` pixels high at the tile's left edge to `right` pixels at its right edge.
+    /// A 45° slope goes from 0 to 32; a 22.5° slope takes two tiles, 0 to 16 and then 16 to 32.
+    Slope { left: u8, right: u8 },
+    /// Something to climb. Ladders never block anything, solid or not.
+    Ladder,
+}
+
+impl TileShape {
+    /// For shapes that block like a box, the box within a tile at `tile`
+    pub fn boxed(self, tile: Rect) -> Option<Rect> {
+        let half = tile.h / 2;
+        match self {
+            TileShape::Full => Some(tile),
+            TileShape::HalfBottom => Some(Rect {
+                y: tile.y + half as i32,
+                h: tile.h - half,
+                ..tile
+            }),
+            TileShape::HalfTop => Some(Rect { h: half, ..tile }),
+            TileShape::OneWay | TileShape::Slope { .. } | TileShape::Ladder => None,
+        }
+    }
+    /// How high a slope's floor is above the bottom of its tile, x pixels in from the left edge
+    pub fn floor_height(self, x: i32) -> i32 {
+        match self {
+            TileShape::Slope { left, right } => {
+                let x = x.clamp(0, TILE_SZ as i32);
+                left as i32 + (right as i32 - left as i32) * x / TILE_SZ as i32
+            }
+            _ => 0,
+        }
+    }
+    /// Does the shape cover this pixel, given relative to the tile's top-left corner?
+    /// One-way platforms only cover their top row.
+    pub fn covers(self, p: Vec2i) -> bool {
+        let sz = TILE_SZ as i32;
+        if p.0 < 0 || p.0 >= sz || p.1 < 0 || p.1 >= sz {
+            return false;
+        }
+        match self {
+            TileShape::Full => true,
+            TileShape::HalfBottom => p.1 >= sz / 2,
+            TileShape::HalfTop => p.1 < sz / 2,
+            TileShape::OneWay => p.1 == 0,
+            // Sample the middle of the pixel's column
+            TileShape::Slope { .. } => {
+                p.1 >= sz - (self.floor_height(p.0) + self.floor_height(p.0 + 1) + 1) / 2
+            }
+            TileShape::Ladder => false,
+        }
+    }
+}
+
+impl Tile {
+    /// Does this tile block movement at a pixel relative to its top-left corner?
+    pub fn blocks(&self, p: Vec2i) -> bool {
+        self.solid && self.shape.covers(p)
+    }
+    pub fn climbable(&self) -> bool {
+        self.shape == TileShape::Ladder
+    }
 }
 /// A set of tiles used in multiple Tilemaps
 pub struct Tileset {
     // Tile size is a constant, so we can find the tile in the texture using math
     // (assuming the texture is a grid of tiles).
     pub tiles: Vec<Tile>,
-    // Maybe a reference to a texture in a real program
-    texture: Rc<Texture>,
+    // A handle, so the tiles change along with the picture when it's reloaded
+    texture: Handle<Texture>,
+    // One color standing in for each tile, for things like the minimap, along with the
+    // version of the texture they were worked out from
+    colors: RefCell<(Rc<Texture>, Vec<Rgba>)>,
+    // Colors picked by hand, which stay put when the texture's reloaded
+    picked: Vec<Option<Rgba>>,
     // In this design, each tileset is a distinct image.
     // Maybe not always the best choice if there aren't many tiles in a tileset!
 }
@@ -33,15 +117,48 @@ impl std::ops::Index<TileID> for Tileset {
     }
 }
 impl Tileset {
-    pub fn new(tiles: Vec<Tile>, texture: &Rc<Texture>) -> Self {
-        Self {
+    pub fn new(tiles: Vec<Tile>, texture: impl Into<Handle<Texture>>) -> Self {
+        let texture: Handle<Texture> = texture.into();
+        let current = texture.get();
+        let mut tileset = Self {
+            picked: vec![None; tiles.len()],
             tiles,
-            texture: Rc::clone(texture),
+            texture,
+            colors: RefCell::new((Rc::clone(&current), vec![])),
+        };
+        tileset.colors.get_mut().1 = tileset.average_colors(&current);
+        tileset
+    }
+    /// The color a tile shows up as when it's too small to draw, like on the minimap.
+    /// Unless it's been picked, it's the average color of the tile's picture, worked out
+    /// again whenever the picture's reloaded.
+    pub fn color(&self, id: TileID) -> Rgba {
+        if let Some(col) = self.picked[id.0] {
+            return col;
+        }
+        let texture = self.texture.get();
+        let mut colors = self.colors.borrow_mut();
+        if !Rc::ptr_eq(&colors.0, &texture) {
+            *colors = (Rc::clone(&texture), self.average_colors(&texture));
         }
+        colors.1[id.0]
+    }
+    /// Picks a tile's color by hand, for tiles whose average color doesn't say much
+    pub fn set_color(&mut self, tile: usize, col: Rgba) {
+        self.picked[tile] = Some(col);
+    }
+    fn average_colors(&self, texture: &Texture) -> Vec<Rgba> {
+        (0..self.tiles.len())
+            .map(|i| average_color(texture, self.get_rect(TileID(i))))
+            .collect()
+    }
+    /// The picture the tiles come from
+    pub fn texture(&self) -> &Handle<Texture> {
+        &self.texture
     }
     fn get_rect(&self, id: TileID) -> Rect {
         let idx = id.0;
-        let (w, _h) = self.texture.size();
+        let (w, _h) = self.texture.get().size();
         let tw = w / TILE_SZ;
         let row = idx / tw;
         let col = idx - (row * tw);
@@ -99,17 +216,99 @@ impl Tileset {
     }
 }
 
-pub type Level = (Vec<Tilemap>, Vec<(Player, i32, i32)>);
+// The average of every pixel in a frame of the texture, or nothing at all if the frame isn't there.
+// Textures are premultiplied, so transparent pixels count for less.
+fn average_color(texture: &Texture, frame: Rect) -> Rgba {
+    if frame.is_empty() || !texture.valid_frame(frame) {
+        return Rgba(0, 0, 0, 0);
+    }
+    let depth = texture.depth();
+    let mut sums = [0_usize; 4];
+    for row in texture.buffer()[(frame.y as usize * texture.pitch())..]
+        .chunks_exact(texture.pitch())
+        .take(frame.h as usize)
+    {
+        let cols = &row[(frame.x as usize * depth)..(frame.right() as usize * depth)];
+        for px in cols.chunks_exact(depth) {
+            for (sum, c) in sums.iter_mut().zip(px) {
+                *sum += *c as usize;
+            }
+        }
+    }
+    let n = frame.w as usize * frame.h as usize;
+    let [r, g, b, a] = sums.map(|sum| ((sum + n / 2) / n) as u8);
+    Rgba(r, g, b, a)
+}
+
+/// What a level's entity list can ask for
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EntityType {
+    // Players come from the save file and the server, so these are just markers
+    Player,
+    Enemy(EnemyKind),
+}
+
+/// The built-in enemy types a level can ask for; ai gives each one its behavior
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EnemyKind {
+    Patroller,
+    Chaser,
+    Jumper,
+    Flyer,
+}
+
+/// A level's tilemaps, plus the entities on it and their positions in tiles
+pub type Level = (Vec<Tilemap>, Vec<(EntityType, i32, i32)>);
+
+/// The tiles of a map, without saying which tileset they're from or where the map is.
+/// Maps read from a file share one, so they change along with the file when it's reloaded.
+pub struct TileGrid {
+    /// How big it is
+    dims: (usize, usize),
+    /// A row-major grid of tile IDs
+    map: Vec<TileID>,
+}
+impl TileGrid {
+    /// Reads a text file with a line for each row of tiles, each line a list of tile IDs
+    /// separated by commas
+    pub fn with_file(path: &Path) -> Result<Self, EngineError> {
+        let text = std::fs::read_to_string(path).map_err(|err| EngineError::from_io(path, err))?;
+        let mut rows = vec![];
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let row = line
+                .split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(str::parse::<usize>)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| EngineError::decode_failed(path, format!("bad row {:?}", line)))?;
+            rows.push(row);
+        }
+        let w = rows.first().map_or(0, Vec::len);
+        if w == 0 {
+            return Err(EngineError::decode_failed(path, "there are no tiles"));
+        }
+        if rows.iter().any(|row| row.len() != w) {
+            return Err(EngineError::decode_failed(path, "rows aren't all the same length"));
+        }
+        Ok(Self {
+            dims: (w, rows.len()),
+            map: rows.concat().into_iter().map(TileID).collect(),
+        })
+    }
+    pub fn size(&self) -> (usize, usize) {
+        self.dims
+    }
+}
 
 pub struct Tilemap {
     /// Where the tilemap is in space, use your favorite number type here
     pub position: Vec2i,
-    /// How big it is
-    dims: (usize, usize),
     /// Which tileset is used for this tilemap
     tileset: Rc<Tileset>,
-    /// A row-major grid of tile IDs in tileset
-    map: Vec<TileID>,
+    /// The tile IDs in tileset. A reloaded grid may use IDs tileset doesn't have; those
+    /// tiles are left empty.
+    grid: Handle<TileGrid>,
 }
 impl Tilemap {
     pub fn new(
@@ -123,33 +322,112 @@ impl Tilemap {
             map.iter().all(|tid| tileset.contains(TileID(*tid))),
             "Tilemap refers to nonexistent tiles"
         );
+        let grid = TileGrid {
+            dims,
+            map: map.into_iter().map(TileID).collect(),
+        };
         Self {
             position,
-            dims,
             tileset: Rc::clone(tileset),
-            map: map.into_iter().map(TileID).collect(),
+            grid: Handle::from(&Rc::new(grid)),
         }
     }
+    /// A map of a grid loaded elsewhere, e.g. by Resources::load_map so it's reloaded when
+    /// the file changes
+    pub fn with_grid(
+        position: Vec2i,
+        grid: Handle<TileGrid>,
+        tileset: &Rc<Tileset>,
+    ) -> Result<Self, EngineError> {
+        let tiles = grid.get();
+        if let Some(id) = tiles.map.iter().find(|id| !tileset.contains(**id)) {
+            let reason = format!("there's no tile {}", id.0);
+            return Err(EngineError::decode_failed(grid.key(), reason));
+        }
+        Ok(Self {
+            position,
+            tileset: Rc::clone(tileset),
+            grid,
+        })
+    }
+    /// Reads a map from a file the way TileGrid::with_file does, without watching it
+    pub fn with_file(
+        path: &Path,
+        position: Vec2i,
+        tileset: &Rc<Tileset>,
+    ) -> Result<Self, EngineError> {
+        let grid = Handle::new(path, TileGrid::with_file(path)?);
+        Self::with_grid(position, grid, tileset)
+    }
+    // The tile an ID stands for, if tileset has it
+    fn tile(&self, id: TileID) -> Option<Tile> {
+        self.tileset.tiles.get(id.0).copied()
+    }
 
     pub fn tile_id_at(&self, Vec2i(x, y): Vec2i) -> Option<TileID> {
         // Translate into map coordinates
-        let x = (x - self.position.0) / TILE_SZ as i32; // invert operation to get world coordinates
-        let y = (y - self.position.1) / TILE_SZ as i32;
+        // div_euclid so points just left of or above the map don't round into it
+        let x = (x - self.position.0).div_euclid(TILE_SZ as i32); // invert operation to get world coordinates
+        let y = (y - self.position.1).div_euclid(TILE_SZ as i32);
+        let grid = self.grid.get();
         // println!("x {} and y {}", x, y);
-        // println!("map x {} map y {}", self.dims.0, self.dims.1);
-        if (x >= 0 && x < self.dims.0 as i32) && (y >= 0 && y < self.dims.1 as i32) {
-            Some(self.map[y as usize * self.dims.0 + x as usize])
+        // println!("map x {} map y {}", grid.dims.0, grid.dims.1);
+        if (x >= 0 && x < grid.dims.0 as i32) && (y >= 0 && y < grid.dims.1 as i32) {
+            Some(grid.map[y as usize * grid.dims.0 + x as usize])
         } else {
             // println!("within a map");
             None
         }
     }
     pub fn size(&self) -> (usize, usize) {
-        self.dims
+        self.grid.get().dims
+    }
+    /// The area the map covers in world space
+    pub fn bounds(&self) -> Rect {
+        let (w, h) = self.size();
+        Rect::from_pos_size(self.position, (w * TILE_SZ, h * TILE_SZ))
+    }
+    /// Every tile overlapping r, along with its bounds in world space
+    pub fn tiles_in(&self, r: Rect) -> impl Iterator<Item = (Tile, Rect)> + '_ {
+        self.ids_in(r)
+            .filter_map(move |(id, bounds)| Some((self.tile(id)?, bounds)))
+    }
+    /// The stand-in color of every tile overlapping r, along with its bounds in world space
+    pub fn colors_in(&self, r: Rect) -> impl Iterator<Item = (Rgba, Rect)> + '_ {
+        self.ids_in(r)
+            .filter(move |(id, _)| self.tileset.contains(*id))
+            .map(move |(id, bounds)| (self.tileset.color(id), bounds))
+    }
+    fn ids_in(&self, r: Rect) -> impl Iterator<Item = (TileID, Rect)> + '_ {
+        let tsz = TILE_SZ as i32;
+        // Which columns and rows r covers, clamped to the map (half-open, like Rect)
+        let (c0, c1, r0, r1) = match self.bounds().intersection(r) {
+            Some(i) => (
+                (i.x - self.position.0).div_euclid(tsz),
+                (i.right() - 1 - self.position.0).div_euclid(tsz) + 1,
+                (i.y - self.position.1).div_euclid(tsz),
+                (i.bottom() - 1 - self.position.1).div_euclid(tsz) + 1,
+            ),
+            None => (0, 0, 0, 0),
+        };
+        let grid = self.grid.get();
+        (r0..r1).flat_map(move |row| {
+            let grid = Rc::clone(&grid);
+            (c0..c1).map(move |col| {
+                let id = grid.map[row as usize * grid.dims.0 + col as usize];
+                let bounds = Rect {
+                    x: self.position.0 + col * tsz,
+                    y: self.position.1 + row * tsz,
+                    w: TILE_SZ as u16,
+                    h: TILE_SZ as u16,
+                };
+                (id, bounds)
+            })
+        })
     }
     pub fn tile_at(&self, posn: Vec2i) -> Option<Tile> {
         match self.tile_id_at(posn) {
-            Some(tileid) => Some(self.tileset[tileid]),
+            Some(tileid) => self.tile(tileid),
             _ => None,
         }
     }
@@ -192,36 +470,41 @@ impl Tilemap {
             w: sw,
             h: sh,
         } = screen.bounds();
+        let grid = self.grid.get();
         // We'll draw from the topmost/leftmost visible tile to the bottommost/rightmost visible tile.
         // The camera combined with out position and size tell us what's visible.
         // leftmost tile: get camera.x into our frame of reference, then divide down to tile units
         // Note that it's also forced inside of 0..self.size.0
         let left = ((sx - self.position.0) / TILE_SZ as i32)
             .max(0)
-            .min(self.dims.0 as i32) as usize;
+            .min(grid.dims.0 as i32) as usize;
         // rightmost tile: same deal, but with screen.x + screen.w.
         let right = ((sx + (sw as i32) - self.position.0) / TILE_SZ as i32)
             .max(0)
-            .min(self.dims.0 as i32) as usize;
+            .min(grid.dims.0 as i32) as usize;
         // ditto top and bot
         let top = ((sy - self.position.1) / TILE_SZ as i32)
             .max(0)
-            .min(self.dims.1 as i32) as usize;
+            .min(grid.dims.1 as i32) as usize;
         let bot = ((sy + (sh as i32) - self.position.1) / TILE_SZ as i32)
             .max(0)
-            .min(self.dims.1 as i32) as usize;
+            .min(grid.dims.1 as i32) as usize;
+        let texture = self.tileset.texture.get();
         // Now draw the tiles we need to draw where we need to draw them.
         // Note that we're zipping up the row index (y) with a slice of the map grid containing the necessary rows so we can avoid making a bounds check for each tile.
         for (y, row) in (top..bot)
-            .zip(self.map[(top * self.dims.0)..(bot * self.dims.0)].chunks_exact(self.dims.0))
+            .zip(grid.map[(top * grid.dims.0)..(bot * grid.dims.0)].chunks_exact(grid.dims.0))
         {
             // We are in tile coordinates at this point so we'll need to translate back to pixel units and world coordinates to draw.
             let ypx = (y * TILE_SZ) as i32 + self.position.1;
             // Here we can iterate through the column index and the relevant slice of the row in parallel
             for (x, id) in (left..right).zip(row[left..right].iter()) {
+                if !self.tileset.contains(*id) {
+                    continue;
+                }
                 let xpx = (x * TILE_SZ) as i32 + self.position.0;
                 let frame = self.tileset.get_rect(*id);
-                screen.bitblt(&self.tileset.texture, frame, Vec2i(xpx, ypx));
+                screen.bitblt(&texture, frame, Vec2i(xpx, ypx));
             }
         }
     }
@@ -0,0 +1,271 @@
+use crate::components::{Position, Size};
+use crate::graphics::Screen;
+use crate::resources::Resources;
+use crate::states::{GameState, State, StateResult};
+use crate::tiles::Level;
+use crate::types::{Rect, Vec2i};
+use std::cell::RefCell;
+use std::fmt;
+use winit_input_helper::WinitInputHelper;
+
+/// How the outgoing and incoming states are combined on screen
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Effect {
+    // Outgoing state fades to black, then the incoming one fades in
+    FadeToBlack,
+    // Incoming state is revealed left to right over the outgoing one
+    Wipe,
+    // A circle around the player closes on the outgoing state, then opens on the incoming one
+    Iris,
+    // Both states are blended together
+    Crossfade,
+}
+
+/// An effect plus how many frames it should last
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Transition {
+    pub effect: Effect,
+    pub frames: usize,
+}
+
+impl Transition {
+    pub fn new(effect: Effect, frames: usize) -> Self {
+        Self {
+            effect,
+            frames: frames.max(1),
+        }
+    }
+}
+
+/// A state that plays a transition between two other states.
+/// While it is running neither state is updated; once it is done it swaps itself for the incoming state.
+/// The outgoing state's on_exit and the incoming state's on_enter run halfway through.
+#[derive(Debug)]
+pub struct Transitioning {
+    // None when pushing: the outgoing state is still on the stack underneath us
+    from: Option<Box<dyn State>>,
+    to: Option<Box<dyn State>>,
+    transition: Transition,
+    frame: usize,
+    // Reused every frame, since display only gets &self
+    buffers: RefCell<Buffers>,
+}
+
+// Offscreen framebuffers the outgoing and incoming states draw into
+#[derive(Default)]
+struct Buffers {
+    from: Vec<u8>,
+    to: Vec<u8>,
+}
+
+impl fmt::Debug for Buffers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Buffers({} bytes each)", self.from.len())
+    }
+}
+
+impl Transitioning {
+    pub fn swap(from: Box<dyn State>, to: Box<dyn State>, transition: Transition) -> Self {
+        Self {
+            from: Some(from),
+            to: Some(to),
+            transition,
+            frame: 0,
+            buffers: RefCell::default(),
+        }
+    }
+    pub fn push(to: Box<dyn State>, transition: Transition) -> Self {
+        Self {
+            from: None,
+            to: Some(to),
+            transition,
+            frame: 0,
+            buffers: RefCell::default(),
+        }
+    }
+    // How far along we are, from 0.0 to 1.0
+    fn progress(&self) -> f32 {
+        (self.frame as f32 / self.transition.frames as f32).min(1.0)
+    }
+    fn outgoing<'a>(&'a self, game: &'a GameState) -> Option<&'a dyn State> {
+        match &self.from {
+            Some(from) => Some(from.as_ref()),
+            // We're on top of the stack, so the state we were pushed over is just below us
+            None => game.state_stack.iter().rev().nth(1).map(|s| s.as_ref()),
+        }
+    }
+}
+
+#[allow(unused_variables)]
+impl State for Transitioning {
+    fn update(
+        &mut self,
+        game: &mut GameState,
+        resources: &Resources,
+        levels: &Vec<Level>,
+        frame: usize,
+        key_input: &WinitInputHelper,
+    ) -> StateResult {
+        self.frame += 1;
+        // Hand over at the halfway point, when the screen is most covered
+        if self.frame == self.transition.frames.div_ceil(2) {
+            if let Some(from) = self.from.as_mut() {
+                from.on_exit(game, resources, levels);
+            }
+            if let Some(to) = self.to.as_mut() {
+                to.on_enter(game, resources, levels);
+            }
+        }
+        if self.frame >= self.transition.frames {
+            // The hooks have already run, so skip the ones a Swap would do
+            StateResult::Replace(self.to.take().unwrap())
+        } else {
+            StateResult::Keep
+        }
+    }
+    fn display(
+        &self,
+        game: &GameState,
+        resources: &Resources,
+        levels: &Vec<Level>,
+        screen: &mut Screen,
+        frame: usize,
+    ) {
+        let (w, h) = screen.size();
+        let depth = screen.depth();
+        // Draw each state into its own offscreen buffer, then combine them into the real one
+        let mut buffers = self.buffers.borrow_mut();
+        let Buffers { from: from_buf, to: to_buf } = &mut *buffers;
+        for buf in [&mut *from_buf, &mut *to_buf] {
+            // Blank, without giving back the memory
+            buf.clear();
+            buf.resize(w * h * depth, 0);
+        }
+        if let Some(from) = self.outgoing(game) {
+            let mut from_screen = Screen::wrap(from_buf, w, h, depth, Vec2i(0, 0));
+            from.display(game, resources, levels, &mut from_screen, frame);
+        }
+        if let Some(to) = &self.to {
+            let mut to_screen = Screen::wrap(to_buf, w, h, depth, Vec2i(0, 0));
+            to.display(game, resources, levels, &mut to_screen, frame);
+        }
+        let t = self.progress();
+        match self.transition.effect {
+            Effect::FadeToBlack => {
+                if t < 0.5 {
+                    fade(screen, from_buf, 1.0 - t * 2.0);
+                } else {
+                    fade(screen, to_buf, t * 2.0 - 1.0);
+                }
+            }
+            Effect::Wipe => wipe(screen, from_buf, to_buf, (t * w as f32) as usize),
+            Effect::Iris => {
+                let center = iris_center(game, w, h);
+                // Big enough to uncover the far corners of the screen
+                let max_r = (w as f32).hypot(h as f32);
+                if t < 0.5 {
+                    iris(screen, from_buf, center, (1.0 - t * 2.0) * max_r);
+                } else {
+                    iris(screen, to_buf, center, (t * 2.0 - 1.0) * max_r);
+                }
+            }
+            Effect::Crossfade => crossfade(screen, from_buf, to_buf, t),
+        }
+    }
+}
+
+// Where the local player is on screen, or the middle of the screen if there isn't one
+fn iris_center(game: &GameState, w: usize, h: usize) -> Vec2i {
+    match (
+        game.entities.get::<Position>(game.player),
+        game.entities.get::<Size>(game.player),
+    ) {
+        (Some(Position(pos)), Some(Size(pw, ph))) => {
+            Rect::from_pos_size(pos.snap(), (*pw, *ph)).center() - game.camera.offset()
+        }
+        _ => Vec2i(w as i32 / 2, h as i32 / 2),
+    }
+}
+
+// Copy src into the screen scaled towards black; amount 1.0 is unchanged
+fn fade(screen: &mut Screen, src: &[u8], amount: f32) {
+    let depth = screen.depth();
+    for (to, from) in screen
+        .buffer_mut()
+        .chunks_exact_mut(depth)
+        .zip(src.chunks_exact(depth))
+    {
+        for (c, f) in to.iter_mut().zip(from.iter()).take(3) {
+            *c = lerp(0, *f, amount);
+        }
+        if depth > 3 {
+            to[3] = 255;
+        }
+    }
+}
+
+fn crossfade(screen: &mut Screen, from_buf: &[u8], to_buf: &[u8], t: f32) {
+    let depth = screen.depth();
+    for ((px, a), b) in screen
+        .buffer_mut()
+        .chunks_exact_mut(depth)
+        .zip(from_buf.chunks_exact(depth))
+        .zip(to_buf.chunks_exact(depth))
+    {
+        for ((c, a), b) in px.iter_mut().zip(a.iter()).zip(b.iter()) {
+            *c = lerp(*a, *b, t);
+        }
+    }
+}
+
+// Columns left of edge come from to_buf, the rest from from_buf
+fn wipe(screen: &mut Screen, from_buf: &[u8], to_buf: &[u8], edge: usize) {
+    let (w, _h) = screen.size();
+    let depth = screen.depth();
+    let pitch = w * depth;
+    let edge = edge.min(w) * depth;
+    for ((row, a), b) in screen
+        .buffer_mut()
+        .chunks_exact_mut(pitch)
+        .zip(from_buf.chunks_exact(pitch))
+        .zip(to_buf.chunks_exact(pitch))
+    {
+        row[..edge].copy_from_slice(&b[..edge]);
+        row[edge..].copy_from_slice(&a[edge..]);
+    }
+}
+
+// Pixels within radius of center come from src, everything else is black
+fn iris(screen: &mut Screen, src: &[u8], Vec2i(cx, cy): Vec2i, radius: f32) {
+    let (w, _h) = screen.size();
+    let depth = screen.depth();
+    let pitch = w * depth;
+    let r2 = radius * radius;
+    for (y, (row, src_row)) in screen
+        .buffer_mut()
+        .chunks_exact_mut(pitch)
+        .zip(src.chunks_exact(pitch))
+        .enumerate()
+    {
+        let dy = (y as i32 - cy) as f32;
+        for (x, (to, from)) in row
+            .chunks_exact_mut(depth)
+            .zip(src_row.chunks_exact(depth))
+            .enumerate()
+        {
+            let dx = (x as i32 - cx) as f32;
+            if dx * dx + dy * dy <= r2 {
+                to.copy_from_slice(from);
+            } else {
+                to.fill(0);
+                if depth > 3 {
+                    to[3] = 255;
+                }
+            }
+        }
+    }
+}
+
+fn lerp(a: u8, b: u8, t: f32) -> u8 {
+    (a as f32 + (b as f32 - a as f32) * t).round() as u8
+}
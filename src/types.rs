@@ -1,10 +1,10 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Player {
     pub id: i32,
-    pub pos: Vec2i,
-    pub vel: Vec2i,
+    pub pos: Vec2f,
+    pub vel: Vec2f,
     pub world: i32,
 }
 
@@ -12,8 +12,8 @@ impl Player {
     pub fn new() -> Player {
         Player {
             id: -1,
-            pos: Vec2i(0, 0),
-            vel: Vec2i(0, 0),
+            pos: Vec2f(0.0, 0.0),
+            vel: Vec2f(0.0, 0.0),
             world: 0,
         }
     }
@@ -30,6 +30,30 @@ pub struct Rect {
 #[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Deserialize, Serialize)]
 pub struct Vec2i(pub i32, pub i32);
 
+/// A sub-pixel position or velocity, for simulation.
+/// Snap it to a Vec2i when it's time to draw or collide with tiles.
+/// Arithmetic on vectors and rects lives in geometry.
+///
+/// Serializing rounds to whole pixels, the `[x, y]` integers positions used to be saved as,
+/// so saves and network messages can still be read by builds that expect a Vec2i.
+/// All that's lost is part of a pixel.
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub struct Vec2f(pub f32, pub f32);
+
+// We write integers, but we'll read back either integers or floats
+impl Serialize for Vec2f {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.snap().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Vec2f {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (x, y) = <(f32, f32)>::deserialize(deserializer)?;
+        Ok(Vec2f(x, y))
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
 pub struct Rgba(pub u8, pub u8, pub u8, pub u8);
 
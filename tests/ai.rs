@@ -0,0 +1,168 @@
+use std::rc::Rc;
+
+use image::RgbaImage;
+
+use Game2DEngine::ai::*;
+use Game2DEngine::animation::Animation;
+use Game2DEngine::collision::{Body, LAYER_ENEMY};
+use Game2DEngine::components::*;
+use Game2DEngine::ecs::World;
+use Game2DEngine::resources::Handle;
+use Game2DEngine::texture::Texture;
+use Game2DEngine::tiles::*;
+use Game2DEngine::types::*;
+
+const EMPTY: Tile = Tile {
+    solid: false,
+    jump_reset: false,
+    restart: false,
+    shape: TileShape::Full,
+};
+const WALL: Tile = Tile {
+    solid: true,
+    jump_reset: false,
+    restart: false,
+    shape: TileShape::Full,
+};
+
+// Builds a map at the origin from rows of '.' (empty) and '#' (solid)
+fn map(rows: &[&str]) -> Vec<Tilemap> {
+    let tex = Rc::new(Texture::new(RgbaImage::new(64, 32)));
+    let tileset = Rc::new(Tileset::new(vec![EMPTY, WALL], &tex));
+    let tiles = rows
+        .iter()
+        .flat_map(|r| r.chars().map(|c| if c == '#' { 1 } else { 0 }))
+        .collect();
+    vec![Tilemap::new(
+        Vec2i(0, 0),
+        (rows[0].len(), rows.len()),
+        &tileset,
+        tiles,
+    )]
+}
+
+// How fast a 32x32 enemy at pos wants to move this frame
+fn think(behavior: &mut dyn Behavior, maps: &[Tilemap], pos: Vec2i, player: Vec2i) -> Vec2f {
+    let senses = Senses {
+        pos,
+        size: (32, 32),
+        player,
+        maps,
+        frame: 0,
+    };
+    let mut vel = Vec2f(0.0, 0.0);
+    behavior.think(&senses, &mut vel);
+    vel
+}
+
+#[test]
+fn patrols_turn_at_walls() {
+    let maps = map(&["......", "....#.", "######"]);
+    let far = Vec2i(1000, 1000);
+    let mut patrol = Patrol::new(2.0);
+    assert_eq!(think(&mut patrol, &maps, Vec2i(32, 32), far).0, 2.0);
+    // The next step would put us in the wall
+    assert_eq!(think(&mut patrol, &maps, Vec2i(96, 32), far).0, -2.0);
+    // And it keeps going the new way
+    assert_eq!(think(&mut patrol, &maps, Vec2i(64, 32), far).0, -2.0);
+}
+
+#[test]
+fn patrols_turn_at_ledges_but_not_in_the_air() {
+    let maps = map(&["......", "......", "###..."]);
+    let far = Vec2i(1000, 1000);
+    let mut patrol = Patrol::new(2.0);
+    assert_eq!(think(&mut patrol, &maps, Vec2i(32, 32), far).0, 2.0);
+    assert_eq!(think(&mut patrol, &maps, Vec2i(64, 32), far).0, -2.0);
+    // Falling past a ledge isn't standing at one
+    let mut patrol = Patrol::new(2.0);
+    assert_eq!(think(&mut patrol, &maps, Vec2i(64, 10), far).0, 2.0);
+}
+
+#[test]
+fn chasers_only_chase_what_they_can_see() {
+    let maps = map(&["......", "......"]);
+    let mut chase = Chase {
+        speed: 2.5,
+        range: 100.0,
+    };
+    let at = Vec2i(64, 0);
+    assert_eq!(think(&mut chase, &maps, at, Vec2i(164, 0)).0, 2.5);
+    assert_eq!(think(&mut chase, &maps, at, Vec2i(4, 0)).0, -2.5);
+    // Close enough to catch this frame, so no overshooting
+    assert_eq!(think(&mut chase, &maps, at, Vec2i(63, 0)).0, -1.0);
+    // Out of sight, even if only just
+    assert_eq!(think(&mut chase, &maps, at, Vec2i(165, 0)).0, 0.0);
+    assert_eq!(think(&mut chase, &maps, at, Vec2i(64, 101)).0, 0.0);
+}
+
+#[test]
+fn enemies_spawn_from_the_levels_markers() {
+    let mut world = World::new();
+    let texture = Handle::new("enemy", Texture::new(RgbaImage::new(32, 32)));
+    let anim = Rc::new(Animation::freeze(Rect {
+        x: 0,
+        y: 0,
+        w: 32,
+        h: 32,
+    }));
+    let markers = [
+        (EntityType::Player, 1, 1),
+        (EntityType::Enemy(EnemyKind::Patroller), 2, 3),
+        (EntityType::Enemy(EnemyKind::Flyer), 5, 0),
+    ];
+    spawn_enemies(&mut world, &markers, &texture, &anim);
+    // Players come from elsewhere
+    assert_eq!(world.len(), 2);
+    let spawned: Vec<_> = world
+        .query2::<Enemy, Position>()
+        .map(|(_, _, pos)| pos.0)
+        .collect();
+    assert_eq!(spawned, vec![Vec2f(64.0, 96.0), Vec2f(160.0, 0.0)]);
+    for e in world.entities() {
+        assert!(world.has::<Brain>(e));
+        assert!(world.has::<Sprite>(e));
+        assert_eq!(world.get::<Size>(e), Some(&Size(32, 32)));
+        assert_eq!(world.get::<Body>(e).unwrap().layers, LAYER_ENEMY);
+    }
+
+    let touching = |x, y| Rect { x, y, w: 1, h: 1 };
+    assert!(enemy_touching(&world, touching(64, 96)));
+    assert!(enemy_touching(&world, touching(191, 31)));
+    assert!(!enemy_touching(&world, touching(63, 96)));
+
+    // Patrollers fall and flyers don't
+    update_enemies(&mut world, &[], Vec2i(1000, 1000), 0);
+    let fallen: Vec<_> = world
+        .query2::<Enemy, Velocity>()
+        .map(|(_, _, vel)| vel.0 .1)
+        .collect();
+    assert_eq!(fallen[0], 1.0);
+    assert!(fallen[1] > 1.0);
+
+    despawn_enemies(&mut world);
+    assert!(world.is_empty());
+}
+
+#[test]
+fn enemies_stop_at_walls_and_land_on_floors() {
+    let maps = map(&["......", ".#....", "######"]);
+    let mut world = World::new();
+    let texture = Handle::new("enemy", Texture::new(RgbaImage::new(32, 32)));
+    let anim = Rc::new(Animation::freeze(Rect {
+        x: 0,
+        y: 0,
+        w: 32,
+        h: 32,
+    }));
+    // A chaser just right of the wall, with the player just left of it
+    let markers = [(EntityType::Enemy(EnemyKind::Chaser), 2, 1)];
+    spawn_enemies(&mut world, &markers, &texture, &anim);
+    for frame in 0..60 {
+        update_enemies(&mut world, &maps, Vec2i(0, 32), frame);
+    }
+    let (e, _) = world.query::<Enemy>().next().unwrap();
+    assert_eq!(world.get::<Position>(e).unwrap().0, Vec2f(64.0, 32.0));
+    // Standing still, rather than falling faster and faster into the floor
+    assert_eq!(world.get::<Velocity>(e).unwrap().0 .1, 0.0);
+}
@@ -0,0 +1,223 @@
+use std::fs::File;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+use image::{Rgba as Pixel, RgbaImage};
+use rusttype::Font;
+
+use Game2DEngine::animation::Animation;
+use Game2DEngine::atlas::*;
+use Game2DEngine::error::EngineError;
+use Game2DEngine::graphics::Screen;
+use Game2DEngine::resources::{Handle, Resources};
+use Game2DEngine::types::*;
+
+fn solid(w: u32, h: u32, red: u8) -> RgbaImage {
+    RgbaImage::from_pixel(w, h, Pixel([red, 0, 0, 255]))
+}
+
+fn rect(x: i32, y: i32, w: u16, h: u16) -> Rect {
+    Rect { x, y, w, h }
+}
+
+fn font() -> Font<'static> {
+    let data = std::fs::read("content/helvetica.ttf").unwrap();
+    Font::try_from_vec(data).unwrap()
+}
+
+// Red of every pixel of the atlas' texture in r
+fn reds(atlas: &Atlas, r: Rect) -> Vec<u8> {
+    let texture = atlas.texture().get();
+    let (w, _) = texture.size();
+    let mut reds = vec![];
+    for y in r.y..r.bottom() {
+        for x in r.x..r.right() {
+            reds.push(texture.buffer()[(y as usize * w + x as usize) * 4]);
+        }
+    }
+    reds
+}
+
+#[test]
+fn pictures_are_packed_apart_and_copied_in() {
+    let mut builder = AtlasBuilder::new();
+    builder.width = 20;
+    builder
+        .add("a", solid(8, 4, 10))
+        .add("b", solid(8, 8, 20))
+        .add("c", solid(8, 2, 30))
+        .add("wide", solid(30, 3, 40));
+    let atlas = builder.build();
+    assert_eq!(atlas.len(), 4);
+    // The wide one won't fit, so the atlas grows to hold it
+    let (w, h) = atlas.texture().get().size();
+    assert_eq!(w, 32);
+    let names = ["a", "b", "c", "wide"];
+    let bounds = rect(0, 0, w as u16, h as u16);
+    for (i, name) in names.iter().enumerate() {
+        let r = atlas.rect(name).unwrap();
+        assert_eq!(r.union(bounds), bounds);
+        assert!(reds(&atlas, r).iter().all(|red| *red == 10 * (i as u8 + 1)));
+        for other in names[i + 1..].iter() {
+            // Padding keeps them from touching, too
+            assert!(!r.expand(1).intersects(atlas.rect(other).unwrap()));
+        }
+    }
+    // Tallest first
+    assert_eq!(atlas.rect("b").unwrap(), rect(1, 1, 8, 8));
+    assert_eq!(atlas.rect("a").unwrap(), rect(10, 1, 8, 4));
+    assert!(atlas.rect("nothing").is_none());
+
+    // Adding a name again replaces the picture, and the same pictures pack the same way
+    builder.add("a", solid(8, 4, 99));
+    let again = builder.build();
+    assert_eq!(again.rect("a"), atlas.rect("a"));
+    let a = again.rect("a").unwrap();
+    assert!(reds(&again, a).iter().all(|red| *red == 99));
+}
+
+#[test]
+fn atlases_draw_with_bitblt_and_animate() {
+    // A two frame sheet, 4 wide and 2 tall, red then green
+    let sheet = RgbaImage::from_fn(4, 2, |x, _| {
+        if x < 2 {
+            Pixel([200, 0, 0, 255])
+        } else {
+            Pixel([0, 200, 0, 255])
+        }
+    });
+    let mut builder = AtlasBuilder::new();
+    builder
+        .add("tall", solid(1, 6, 5))
+        .add("sheet", sheet)
+        .add("icon", solid(2, 2, 77));
+    let atlas = builder.build();
+
+    let mut fb = vec![0; 4 * 4 * 4];
+    let mut screen = Screen::wrap(&mut fb, 4, 4, 4, Vec2i(0, 0));
+    atlas.draw(&mut screen, "icon", Vec2i(1, 1));
+    atlas.draw(&mut screen, "nothing", Vec2i(0, 0));
+    assert_eq!(fb[(4 + 1) * 4], 77);
+    assert_eq!(fb[(2 * 4 + 2) * 4], 77);
+    assert_eq!(fb[0], 0);
+
+    // Frames made for the sheet on its own play from where it went
+    let walk = Animation::new(vec![(rect(0, 0, 2, 2), 2), (rect(2, 0, 2, 2), 2)], true);
+    let at = atlas.rect("sheet").unwrap().pos();
+    let mut state = Rc::new(atlas.animation("sheet", &walk).unwrap()).start();
+    assert_eq!(state.frame(), rect(at.0, at.1, 2, 2));
+    assert_eq!(reds(&atlas, state.frame()), vec![200; 4]);
+    for _ in 0..3 {
+        state.tick();
+    }
+    assert_eq!(state.frame(), rect(at.0 + 2, at.1, 2, 2));
+    assert_eq!(reds(&atlas, state.frame()), vec![0; 4]);
+    assert!(atlas.animation("nothing", &walk).is_none());
+
+    let blink = atlas.sequence(&["icon", "tall"], 3, false).unwrap();
+    let state = Rc::new(blink).start();
+    assert_eq!(state.frame(), atlas.rect("icon").unwrap());
+    assert!(atlas.sequence(&["icon", "nothing"], 3, false).is_none());
+}
+
+#[test]
+fn text_is_just_big_enough_and_glyphs_line_up() {
+    let font = font();
+    let label = render_text("Walls are nice", &font, 25.0, Rgba(0, 0, 0, 255));
+    assert!(label.width() < 200 && label.height() < 40);
+    assert!(label.pixels().any(|p| p[3] == 255));
+
+    let mut builder = AtlasBuilder::new();
+    builder
+        .add_glyphs("helvetica", &font, 16.0, "il ", Rgba(255, 255, 255, 255))
+        .add_text("label", "Walls are nice", &font, 25.0, Rgba(0, 0, 0, 255));
+    let atlas = builder.build();
+    assert_eq!(atlas.len(), 4);
+    let size = (label.width() as usize, label.height() as usize);
+    assert_eq!(atlas.rect("label").unwrap().size(), size);
+    let i = atlas.rect(&glyph_name("helvetica", 'i')).unwrap();
+    let space = atlas.rect(&glyph_name("helvetica", ' ')).unwrap();
+
+    // Drawing text puts each glyph right after the last, skipping ones that aren't there
+    let (w, h) = (40, 20);
+    let mut fb = vec![0; w * h * 4];
+    let mut screen = Screen::wrap(&mut fb, w, h, 4, Vec2i(0, 0));
+    atlas.draw_text(&mut screen, "helvetica", "i?i", Vec2i(0, 0));
+    let lit: Vec<usize> = (0..w)
+        .filter(|x| (0..h).any(|y| fb[(y * w + x) * 4 + 3] != 0))
+        .collect();
+    assert!(lit.iter().all(|x| *x < 2 * i.w as usize));
+    assert!(lit.iter().any(|x| *x >= i.w as usize));
+    assert!(space.w > 0 && reds(&atlas, space).iter().all(|red| *red == 0));
+}
+
+#[test]
+fn atlases_are_made_from_directories_and_loaded_back() {
+    let dir = std::env::temp_dir().join("game2dengine_atlas_test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir(&dir).unwrap();
+    solid(3, 3, 50).save(dir.join("heart.png")).unwrap();
+    solid(5, 2, 60).save(dir.join("coin.PNG")).unwrap();
+    std::fs::write(dir.join("notes.txt"), "not a picture").unwrap();
+
+    let mut builder = AtlasBuilder::new();
+    builder.add_dir(&dir).unwrap();
+    assert_eq!(builder.len(), 2);
+    let atlas = builder.build();
+    let out = dir.join("icons.png");
+    atlas.save(&out).unwrap();
+    assert!(dir.join("icons.atlas").exists());
+
+    let rsrc = Resources::new();
+    let loaded = rsrc.load_atlas(&out).unwrap().get();
+    assert_eq!(loaded.len(), 2);
+    for name in ["heart", "coin"].iter() {
+        let r = loaded.rect(name).unwrap();
+        assert_eq!(Some(r), atlas.rect(name));
+        assert_eq!(reds(&loaded, r), reds(&atlas, r));
+    }
+
+    // Saving over it reloads the picture behind the texture handle already handed out
+    let texture = loaded.texture().clone();
+    solid(3, 3, 70).save(dir.join("heart.png")).unwrap();
+    let mut builder = AtlasBuilder::new();
+    builder.add_dir(&dir).unwrap();
+    builder.build().save(&out).unwrap();
+    let later = SystemTime::now() + Duration::from_secs(10);
+    let file = File::options().write(true).open(&out).unwrap();
+    file.set_modified(later).unwrap();
+    assert_eq!(rsrc.reload_changed(), vec![out.clone()]);
+    let reloaded = rsrc.load_atlas(&out).unwrap().get();
+    assert!(Handle::ptr_eq(reloaded.texture(), &texture));
+    let heart = reloaded.rect("heart").unwrap();
+    assert_eq!(reds(&reloaded, heart), vec![70; 9]);
+
+    // Tables that don't make sense are errors
+    std::fs::write(dir.join("icons.atlas"), "1 1 3 3 heart\n1 1 three 3 coin\n").unwrap();
+    assert!(matches!(
+        Atlas::with_file(&out),
+        Err(EngineError::DecodeFailed { .. })
+    ));
+    std::fs::write(dir.join("icons.atlas"), "1 1 3000 3 heart\n").unwrap();
+    assert!(matches!(
+        Atlas::with_file(&out),
+        Err(EngineError::DecodeFailed { .. })
+    ));
+    // Too wide to be a Rect at all, rather than wrapping around to 1
+    std::fs::write(dir.join("icons.atlas"), "1 1 65537 1 heart\n").unwrap();
+    assert!(matches!(
+        Atlas::with_file(&out),
+        Err(EngineError::DecodeFailed { .. })
+    ));
+    std::fs::remove_file(dir.join("icons.atlas")).unwrap();
+    match Atlas::with_file(&out) {
+        Err(EngineError::NotFound(path)) => assert_eq!(path, dir.join("icons.atlas")),
+        _ => panic!("a missing table should be NotFound"),
+    }
+    assert!(matches!(
+        AtlasBuilder::new().add_dir(Path::new("content/nothing")),
+        Err(EngineError::NotFound(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,234 @@
+use std::rc::Rc;
+
+use Game2DEngine::audio::*;
+use Game2DEngine::error::EngineError;
+use Game2DEngine::resources::Resources;
+use Game2DEngine::types::Vec2i;
+
+// A sound that holds the same sample on both sides for some frames
+fn flat(level: f32, frames: usize) -> Rc<Sound> {
+    Rc::new(Sound::new(vec![level; frames * 2]))
+}
+
+fn mix(mixer: &mut Mixer, frames: usize) -> Vec<(f32, f32)> {
+    let mut out = vec![0.0; frames * 2];
+    mixer.mix(&mut out);
+    out.chunks_exact(2).map(|f| (f[0], f[1])).collect()
+}
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-4
+}
+
+#[test]
+fn one_shots_play_once_and_add_up() {
+    let mut mixer = Mixer::new();
+    let a = mixer.play(&flat(0.25, 3), 1.0, 0.0);
+    mixer.play(&flat(0.5, 2), 0.5, 0.0);
+    assert_eq!(mixer.playing(), 2);
+    assert_eq!(
+        mix(&mut mixer, 4),
+        vec![(0.5, 0.5), (0.5, 0.5), (0.25, 0.25), (0.0, 0.0)]
+    );
+    assert!(!mixer.is_playing(a));
+    assert_eq!(mixer.playing(), 0);
+
+    // Too loud gets clipped rather than wrapping around
+    for _ in 0..5 {
+        mixer.play(&flat(0.5, 1), 1.0, 0.0);
+    }
+    mixer.master = 0.5;
+    assert_eq!(mix(&mut mixer, 1), vec![(1.0, 1.0)]);
+}
+
+#[test]
+fn looping_sounds_go_round_until_stopped() {
+    let mut mixer = Mixer::new();
+    let sound = Rc::new(Sound::new(vec![0.1, 0.1, 0.2, 0.2]));
+    let id = mixer.play_looping(&sound, 1.0, 0.0);
+    let left: Vec<f32> = mix(&mut mixer, 5).iter().map(|f| f.0).collect();
+    assert_eq!(left, vec![0.1, 0.2, 0.1, 0.2, 0.1]);
+    assert!(mixer.is_playing(id));
+    mixer.stop(id, 0.0);
+    assert!(!mixer.is_playing(id));
+}
+
+#[test]
+fn panning_turns_down_the_far_side() {
+    assert_eq!(pan_gains(0.0), (1.0, 1.0));
+    assert_eq!(pan_gains(-1.0), (1.0, 0.0));
+    assert_eq!(pan_gains(0.5), (0.5, 1.0));
+    assert_eq!(pan_gains(7.0), (0.0, 1.0));
+    let mut mixer = Mixer::new();
+    let id = mixer.play(&flat(0.5, 10), 1.0, 1.0);
+    assert_eq!(mix(&mut mixer, 1), vec![(0.0, 0.5)]);
+    mixer.set_pan(id, -0.5);
+    mixer.set_volume(id, 0.5);
+    assert_eq!(mix(&mut mixer, 1), vec![(0.25, 0.125)]);
+}
+
+#[test]
+fn fades_change_volume_smoothly() {
+    let mut mixer = Mixer::new();
+    let id = mixer.play_looping(&flat(1.0, 100), 0.0, 0.0);
+    // Up to full over 4 sample frames
+    mixer.fade_to(id, 1.0, 4.0 / SAMPLE_RATE as f32);
+    let left: Vec<f32> = mix(&mut mixer, 6).iter().map(|f| f.0).collect();
+    assert!(close(left[0], 0.0) && close(left[2], 0.5) && close(left[4], 1.0));
+    assert!(close(left[5], 1.0));
+    // Stopping with a fade plays it out first
+    mixer.stop(id, 2.0 / SAMPLE_RATE as f32);
+    let left: Vec<f32> = mix(&mut mixer, 4).iter().map(|f| f.0).collect();
+    assert!(close(left[0], 1.0) && close(left[1], 0.5));
+    assert_eq!(&left[2..], &[0.0, 0.0]);
+    assert!(!mixer.is_playing(id));
+}
+
+#[test]
+fn new_music_crossfades_with_the_old() {
+    let mut mixer = Mixer::new();
+    let fade = 10.0 / SAMPLE_RATE as f32;
+    let first = mixer.play_music(&flat(0.5, 1000), 1.0, fade);
+    mix(&mut mixer, 20);
+    let second = mixer.play_music(&flat(-0.5, 1000), 1.0, fade);
+    assert_eq!(mixer.music(), Some(second));
+    let halfway = mix(&mut mixer, 6)[5];
+    assert!(close(halfway.0, 0.0));
+    mix(&mut mixer, 5);
+    assert!(!mixer.is_playing(first));
+    assert!(close(mix(&mut mixer, 1)[0].0, -0.5));
+    mixer.stop_music(0.0);
+    assert_eq!(mixer.music(), None);
+    assert_eq!(mixer.playing(), 0);
+}
+
+#[test]
+fn sounds_load_through_resources_and_record_to_wav() {
+    // The effects are mono at 22050Hz, so they come out twice as long in stereo at SAMPLE_RATE
+    let jump = Resources::new().load_sound("content/jump.wav").unwrap().get();
+    let source = (22050.0 * 0.15) as usize;
+    assert!((jump.frames() as i64 - 2 * source as i64).abs() <= 2);
+
+    let mut audio = Audio::new(Output::Buffer(vec![]));
+    audio.mixer.play(&jump, 1.0, 1.0);
+    for _ in 0..3 {
+        audio.update();
+    }
+    let recorded = audio.recorded().to_vec();
+    assert_eq!(recorded.len(), 3 * FRAMES_PER_UPDATE * 2);
+    // Panned all the way right
+    assert!(recorded.iter().step_by(2).all(|l| *l == 0.0));
+    assert!(recorded.iter().skip(1).step_by(2).any(|r| *r != 0.0));
+
+    let path = std::env::temp_dir().join("game2dengine_audio_test.wav");
+    audio.save_wav(&path).unwrap();
+    let back = Resources::new().load_sound(&path).unwrap().get();
+    assert_eq!(back.frames(), 3 * FRAMES_PER_UPDATE);
+    std::fs::remove_file(&path).unwrap();
+
+    // Silent games throw it all away
+    let mut silent = Audio::new(Output::Null);
+    silent.mixer.play(&jump, 1.0, 0.0);
+    silent.update();
+    assert!(silent.recorded().is_empty());
+}
+
+#[test]
+fn recordings_go_to_disk_as_they_play_and_buffers_stop_growing() {
+    let path = std::env::temp_dir().join("game2dengine_record_test.wav");
+    let mut audio = Audio::new(Output::wav(&path).unwrap());
+    let sound = flat(0.5, 10 * FRAMES_PER_UPDATE);
+    audio.mixer.play(&sound, 1.0, 0.0);
+    for _ in 0..5 {
+        audio.update();
+    }
+    // Nothing's kept in memory on the way
+    assert!(audio.recorded().is_empty());
+    audio.close().unwrap();
+    let back = Resources::new().load_sound(&path).unwrap().get();
+    assert_eq!(back.frames(), 5 * FRAMES_PER_UPDATE);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        Output::wav(&std::env::temp_dir().join("nothing/here.wav")),
+        Err(EngineError::NotFound(_))
+    ));
+
+    let mut audio = Audio::new(Output::Buffer(vec![]));
+    let full = BUFFER_LIMIT * SAMPLE_RATE as usize * 2;
+    for _ in 0..BUFFER_LIMIT * 60 + 3 {
+        audio.update();
+    }
+    assert_eq!(audio.recorded().len(), full);
+}
+
+#[test]
+fn falloff_curves_go_from_full_to_silent() {
+    let falloff = |curve| Falloff {
+        min_range: 100.0,
+        max_range: 300.0,
+        curve,
+    };
+    for curve in [
+        Attenuation::Linear,
+        Attenuation::Quadratic,
+        Attenuation::Inverse,
+    ]
+    .iter()
+    {
+        let f = falloff(*curve);
+        assert_eq!(f.gain(0.0), 1.0);
+        assert_eq!(f.gain(100.0), 1.0);
+        assert_eq!(f.gain(300.0), 0.0);
+        assert_eq!(f.gain(1000.0), 0.0);
+    }
+    assert!(close(falloff(Attenuation::Linear).gain(200.0), 0.5));
+    assert!(close(falloff(Attenuation::Quadratic).gain(200.0), 0.25));
+    assert!(close(falloff(Attenuation::Inverse).gain(200.0), 0.5));
+}
+
+#[test]
+fn listeners_pan_by_how_far_to_the_side_a_sound_is() {
+    let listener = Listener {
+        pos: Vec2i(100, 100),
+        pan_range: 50.0,
+    };
+    let falloff = Falloff {
+        min_range: 10.0,
+        max_range: 1000.0,
+        curve: Attenuation::Linear,
+    };
+    assert_eq!(listener.hear(Vec2i(100, 100), falloff), (1.0, 0.0));
+    assert!(close(listener.hear(Vec2i(75, 100), falloff).1, -0.5));
+    // Straight above is dead center, and far off to the side is all one side
+    assert_eq!(listener.hear(Vec2i(100, 0), falloff).1, 0.0);
+    assert_eq!(listener.hear(Vec2i(400, 100), falloff).1, 1.0);
+}
+
+#[test]
+fn sounds_in_the_world_are_heard_from_the_listener() {
+    let mut mixer = Mixer::new();
+    mixer.listener = Listener {
+        pos: Vec2i(0, 0),
+        pan_range: 100.0,
+    };
+    let falloff = Falloff {
+        min_range: 100.0,
+        max_range: 200.0,
+        curve: Attenuation::Linear,
+    };
+    // Out of range, so silent but still playing
+    let id = mixer.play_looping_at(&flat(0.5, 10), 1.0, Vec2i(300, 0), falloff);
+    assert_eq!(mix(&mut mixer, 1), vec![(0.0, 0.0)]);
+    assert!(mixer.is_playing(id));
+    // Halfway out to the right
+    mixer.move_to(id, Vec2i(150, 0));
+    assert!(close(mix(&mut mixer, 1)[0].1, 0.25));
+    assert!(close(mix(&mut mixer, 1)[0].0, 0.0));
+    // The listener follows the camera over to it
+    mixer.listener.pos = Vec2i(150, 0);
+    assert_eq!(mix(&mut mixer, 1), vec![(0.5, 0.5)]);
+
+    let once = mixer.play_at(&flat(0.5, 1), 1.0, Vec2i(100, 0), falloff);
+    assert_eq!(mix(&mut mixer, 1), vec![(1.0, 0.75)]);
+    assert!(!mixer.is_playing(once));
+}
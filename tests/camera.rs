@@ -0,0 +1,170 @@
+use Game2DEngine::camera::{Camera, CameraEvent};
+use Game2DEngine::graphics::Screen;
+use Game2DEngine::types::*;
+
+// A 32x32 target with its center at c
+fn at(c: Vec2i) -> Rect {
+    Rect {
+        x: c.0 - 16,
+        y: c.1 - 16,
+        w: 32,
+        h: 32,
+    }
+}
+
+fn still() -> Vec2f {
+    Vec2f(0.0, 0.0)
+}
+
+#[test]
+fn cameras_keep_their_target_centered_by_default() {
+    let mut cam = Camera::new(Vec2i(0, 0), (200, 100));
+    cam.follow(at(Vec2i(500, 300)), still());
+    assert_eq!(cam.offset(), Vec2i(400, 250));
+    assert_eq!(cam.view_rect().center(), Vec2i(500, 300));
+}
+
+#[test]
+fn targets_move_freely_inside_the_dead_zone() {
+    let mut cam = Camera::new(Vec2i(0, 0), (200, 100));
+    cam.dead_zone = (40, 20);
+    cam.look_at(at(Vec2i(100, 50)));
+    cam.follow(at(Vec2i(130, 65)), still());
+    assert_eq!(cam.offset(), Vec2i(0, 0));
+    // Leaving it drags the camera just far enough to bring it back to the edge
+    cam.follow(at(Vec2i(150, 20)), still());
+    assert_eq!(cam.offset(), Vec2i(10, -10));
+}
+
+#[test]
+fn smoothing_closes_part_of_the_gap_each_frame() {
+    let mut cam = Camera::new(Vec2i(0, 0), (200, 100));
+    cam.smoothing = 0.5;
+    cam.follow(at(Vec2i(180, 50)), still());
+    assert_eq!(cam.offset(), Vec2i(40, 0));
+    cam.follow(at(Vec2i(180, 50)), still());
+    assert_eq!(cam.offset(), Vec2i(60, 0));
+    for _ in 0..30 {
+        cam.follow(at(Vec2i(180, 50)), still());
+    }
+    assert_eq!(cam.offset(), Vec2i(80, 0));
+}
+
+#[test]
+fn look_ahead_leads_a_moving_target() {
+    let mut cam = Camera::new(Vec2i(0, 0), (200, 100));
+    cam.look_ahead = Vec2f(10.0, 0.0);
+    cam.follow(at(Vec2i(100, 50)), Vec2f(-4.0, 9.0));
+    // 40px further left, and not at all vertically
+    assert_eq!(cam.offset(), Vec2i(-40, 0));
+}
+
+#[test]
+fn bounds_keep_the_view_inside_the_world() {
+    let mut cam = Camera::new(Vec2i(0, 0), (200, 100));
+    cam.bounds = Some(Rect {
+        x: 0,
+        y: 0,
+        w: 1000,
+        h: 500,
+    });
+    cam.look_at(at(Vec2i(10, 10)));
+    assert_eq!(cam.offset(), Vec2i(0, 0));
+    cam.follow(at(Vec2i(990, 490)), still());
+    assert_eq!(cam.offset(), Vec2i(800, 400));
+    // A world narrower than the view sits in the middle of it
+    cam.bounds = Some(Rect {
+        x: 0,
+        y: 0,
+        w: 100,
+        h: 500,
+    });
+    cam.follow(at(Vec2i(990, 100)), still());
+    assert_eq!(cam.offset(), Vec2i(-50, 50));
+
+    // Worlds that go on forever only stop the view at their top-left corner
+    cam.bounds = None;
+    cam.top_left = Some(Vec2i(0, 0));
+    cam.follow(at(Vec2i(-300, -300)), still());
+    assert_eq!(cam.offset(), Vec2i(0, 0));
+    cam.follow(at(Vec2i(100_000, 70_000)), still());
+    assert_eq!(cam.offset(), Vec2i(100_000 - 100, 70_000 - 50));
+}
+
+#[test]
+fn trauma_shakes_the_view_and_wears_off() {
+    let mut cam = Camera::new(Vec2i(0, 0), (200, 100));
+    cam.handle(CameraEvent::Shake(0.7));
+    cam.handle(CameraEvent::Shake(0.7));
+    assert_eq!(cam.trauma(), 1.0);
+    let mut shaken = false;
+    let mut frames = 0;
+    while cam.trauma() > 0.0 {
+        let trauma = cam.trauma();
+        cam.follow(at(Vec2i(100, 50)), still());
+        let off = cam.offset();
+        let most = (cam.max_shake * trauma * trauma).ceil() as i32;
+        assert!(off.0.abs() <= most && off.1.abs() <= most);
+        shaken |= off != Vec2i(0, 0);
+        // Shaking doesn't move what the camera is actually looking at
+        assert_eq!(cam.view_rect().pos(), Vec2i(0, 0));
+        frames += 1;
+    }
+    assert!(shaken);
+    // About 1 / recovery frames, give or take rounding
+    assert!(frames <= (1.0 / cam.recovery).round() as usize + 1);
+    cam.follow(at(Vec2i(100, 50)), still());
+    assert_eq!(cam.offset(), Vec2i(0, 0));
+}
+
+#[test]
+fn zooming_shows_less_of_the_world_around_the_same_spot() {
+    let mut cam = Camera::new(Vec2i(0, 0), (200, 100));
+    cam.max_zoom = 3;
+    cam.handle(CameraEvent::ZoomIn);
+    assert_eq!(cam.scale(), 2);
+    assert_eq!(cam.view_size(), (100, 50));
+    assert_eq!(cam.view_rect().center(), Vec2i(100, 50));
+    cam.handle(CameraEvent::Zoom(10));
+    assert_eq!(cam.scale(), 3);
+    cam.handle(CameraEvent::Zoom(0));
+    assert_eq!(cam.scale(), 1);
+    cam.handle(CameraEvent::ZoomOut);
+    assert_eq!(cam.scale(), 1);
+}
+
+#[test]
+fn framing_fits_every_target_in_view() {
+    let mut cam = Camera::new(Vec2i(0, 0), (400, 200));
+    cam.handle(CameraEvent::Zoom(4));
+    // Close together, so there's no need to zoom out
+    cam.frame(&[at(Vec2i(100, 100)), at(Vec2i(150, 100))], still());
+    assert_eq!(cam.scale(), 4);
+    assert_eq!(cam.view_rect().center(), Vec2i(125, 100));
+    // 232px apart only fits at 1x zoom across 400px
+    let far = [at(Vec2i(100, 100)), at(Vec2i(300, 120))];
+    cam.frame(&far, still());
+    assert_eq!(cam.scale(), 1);
+    let view = cam.view_rect();
+    assert!(far.iter().all(|t| view.union(*t) == view));
+    // Too far apart to fit at all, so the first one's followed on its own
+    cam.frame(&[at(Vec2i(100, 100)), at(Vec2i(600, 100))], still());
+    assert_eq!(cam.scale(), 1);
+    assert_eq!(cam.view_rect().center(), Vec2i(100, 100));
+    // Coming back together zooms in again
+    cam.frame(&[at(Vec2i(100, 100))], still());
+    assert_eq!(cam.scale(), 4);
+}
+
+#[test]
+fn upscaling_repeats_each_pixel() {
+    let mut fb = vec![0; 4 * 4 * 4];
+    let mut screen = Screen::wrap(&mut fb, 4, 4, 4, Vec2i(0, 0));
+    screen.draw_at(Rgba(1, 1, 1, 255), Vec2i(0, 0));
+    screen.draw_at(Rgba(2, 2, 2, 255), Vec2i(1, 0));
+    screen.draw_at(Rgba(3, 3, 3, 255), Vec2i(0, 1));
+    screen.draw_at(Rgba(4, 4, 4, 255), Vec2i(1, 1));
+    screen.upscale(2);
+    let reds: Vec<u8> = fb.chunks_exact(4).map(|p| p[0]).collect();
+    assert_eq!(reds, vec![1, 1, 2, 2, 1, 1, 2, 2, 3, 3, 4, 4, 3, 3, 4, 4]);
+}
@@ -0,0 +1,714 @@
+use std::rc::Rc;
+
+use image::RgbaImage;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use Game2DEngine::collision::*;
+use Game2DEngine::error::EngineError;
+use Game2DEngine::texture::Texture;
+use Game2DEngine::tiles::*;
+use Game2DEngine::types::*;
+
+const EMPTY: Tile = Tile {
+    solid: false,
+    jump_reset: false,
+    restart: false,
+    shape: TileShape::Full,
+};
+const WALL: Tile = Tile {
+    solid: true,
+    jump_reset: false,
+    restart: false,
+    shape: TileShape::Full,
+};
+
+// 0 is empty and 1 is solid
+fn tileset() -> Rc<Tileset> {
+    let tex = Rc::new(Texture::new(RgbaImage::new(64, 32)));
+    Rc::new(Tileset::new(vec![EMPTY, WALL], &tex))
+}
+
+// Builds a map from rows of '.' (empty) and '#' (solid)
+fn map_at(position: Vec2i, rows: &[&str]) -> Tilemap {
+    let tiles = rows
+        .iter()
+        .flat_map(|r| r.chars().map(|c| if c == '#' { 1 } else { 0 }))
+        .collect();
+    Tilemap::new(position, (rows[0].len(), rows.len()), &tileset(), tiles)
+}
+
+#[test]
+fn sweep_stops_at_a_thin_wall_instead_of_tunneling() {
+    let maps = vec![map_at(Vec2i(0, 0), &["........", "....#...", "........"])];
+    // Fast enough to jump clean over the wall in one frame
+    let swept = sweep_tiles(Vec2i(32, 32), (32, 32), Vec2i(100, 0), &maps);
+    assert_eq!(swept.pos, Vec2i(96, 32));
+    assert!(swept.blocked_x());
+    assert_eq!(swept.hits[0].normal, Vec2i(-1, 0));
+    assert_eq!(swept.hits[0].rect.x, 128);
+}
+
+#[test]
+fn sweep_lands_on_the_floor_and_keeps_sliding() {
+    let maps = vec![map_at(Vec2i(0, 0), &["......", "......", "######"])];
+    let swept = sweep_tiles(Vec2i(10, 20), (32, 32), Vec2i(6, 50), &maps);
+    assert_eq!(swept.pos, Vec2i(16, 32));
+    assert!(!swept.blocked_x());
+    assert!(swept.blocked_y());
+    assert!(swept.hits.iter().all(|h| h.normal == Vec2i(0, -1)));
+}
+
+#[test]
+fn sweep_moves_freely_through_empty_space() {
+    let maps = vec![map_at(Vec2i(0, 0), &["....", "....", "...."])];
+    let swept = sweep_tiles(Vec2i(40, 40), (20, 10), Vec2i(-30, -25), &maps);
+    assert_eq!(swept.pos, Vec2i(10, 15));
+    assert!(swept.hits.is_empty());
+}
+
+#[test]
+fn sweep_crosses_between_chunks() {
+    // The wall is in the second of two side-by-side maps
+    let maps = vec![
+        map_at(Vec2i(0, 0), &["....", "...."]),
+        map_at(Vec2i(128, 0), &[".#..", ".#.."]),
+    ];
+    let swept = sweep_tiles(Vec2i(64, 0), (32, 32), Vec2i(200, 0), &maps);
+    assert_eq!(swept.pos, Vec2i(128, 0));
+    assert!(swept.blocked_x());
+}
+
+// Runs one body through contact gathering and restitution.
+// Returns where it ended up and what happened to it.
+fn collide(
+    maps: &Vec<Tilemap>,
+    pos: Vec2i,
+    size: (usize, usize),
+    vel: Vec2i,
+) -> (Vec2i, Vec<CollisionEvent>) {
+    let mut positions = [pos];
+    let mut velocities = [vel];
+    let sizes = [size];
+    let mut contacts = vec![];
+    let mut events = vec![];
+    let bodies = [Body::default()];
+    gather_contacts(
+        &positions,
+        &sizes,
+        &bodies,
+        maps,
+        &mut contacts,
+        &mut events,
+    );
+    restitute(
+        &mut positions,
+        &sizes,
+        &bodies,
+        &mut velocities,
+        maps,
+        &mut contacts,
+        &mut events,
+    );
+    (positions[0], events)
+}
+
+fn special_tileset() -> Rc<Tileset> {
+    let tex = Rc::new(Texture::new(RgbaImage::new(128, 32)));
+    Rc::new(Tileset::new(
+        vec![
+            EMPTY,
+            WALL,
+            Tile {
+                solid: true,
+                jump_reset: true,
+                restart: false,
+                shape: TileShape::Full,
+            },
+            Tile {
+                solid: false,
+                jump_reset: false,
+                restart: true,
+                shape: TileShape::Full,
+            },
+        ],
+        &tex,
+    ))
+}
+
+#[test]
+fn tall_bodies_hit_walls_in_the_middle_of_their_side() {
+    // Only the middle row of the wall is solid, so none of a 96px tall body's corners touch it
+    let maps = vec![map_at(
+        Vec2i(0, 0),
+        &["......", "...#..", "......", "......"],
+    )];
+    let (pos, _) = collide(&maps, Vec2i(70, 0), (32, 96), Vec2i(4, 0));
+    assert_eq!(pos, Vec2i(64, 0));
+}
+
+#[test]
+fn tall_bodies_land_on_their_feet() {
+    // A 32x64 body sunk 4px into the floor; the old corner checks looked 32px down instead of 64
+    let maps = vec![map_at(Vec2i(0, 0), &["....", "....", "####"])];
+    let (pos, _) = collide(&maps, Vec2i(32, 4), (32, 64), Vec2i(0, 4));
+    assert_eq!(pos, Vec2i(32, 0));
+}
+
+#[test]
+fn wide_bodies_land_on_tiles_under_their_middle() {
+    // Only the tile under the middle of a 96px wide body is solid
+    let maps = vec![map_at(Vec2i(0, 0), &["......", "......", "..#..."])];
+    let (pos, _) = collide(&maps, Vec2i(32, 36), (96, 32), Vec2i(0, 4));
+    assert_eq!(pos, Vec2i(32, 32));
+}
+
+#[test]
+fn bodies_straddling_chunks_collide_with_both() {
+    // Two 8x8 chunks side by side; the body's left half is over the first and its right half over the second
+    let mut left = vec!["........"; 8];
+    let mut right = vec!["........"; 8];
+    left[7] = ".......#";
+    right[7] = "#.......";
+    let maps = vec![map_at(Vec2i(0, 0), &left), map_at(Vec2i(256, 0), &right)];
+    // Only the right chunk's floor is under the body when it's mostly over the gap
+    let (pos, _) = collide(&maps, Vec2i(250, 200), (32, 32), Vec2i(0, 4));
+    assert_eq!(pos, Vec2i(250, 192));
+    // And the left chunk's when it's shifted the other way
+    let (pos, _) = collide(&maps, Vec2i(230, 200), (20, 32), Vec2i(0, 4));
+    assert_eq!(pos, Vec2i(230, 192));
+}
+
+#[test]
+fn landing_reports_the_tile_underfoot() {
+    let tiles = special_tileset();
+    let maps = vec![Tilemap::new(
+        Vec2i(0, 0),
+        (4, 2),
+        &tiles,
+        vec![0, 0, 0, 0, 1, 2, 1, 1],
+    )];
+    // Resting right on top of the jump reset tile, and only partly over it
+    let (_, events) = collide(&maps, Vec2i(48, 0), (32, 32), Vec2i(0, 0));
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, CollisionEvent::Landed { body: 0, tile } if tile.jump_reset)));
+    // Over the plain floor next to it
+    let (_, events) = collide(&maps, Vec2i(96, 0), (32, 32), Vec2i(0, 0));
+    assert!(!events.is_empty());
+    assert!(events
+        .iter()
+        .all(|e| matches!(e, CollisionEvent::Landed { tile, .. } if !tile.jump_reset)));
+}
+
+#[test]
+fn contacts_carry_signed_normals_and_depths() {
+    let maps = vec![map_at(Vec2i(0, 0), &["#...", "....", "...."])];
+    let mut contacts = vec![];
+    let mut events = vec![];
+    // 5px into the wall tile from the right, 2px from below
+    gather_contacts(
+        &[Vec2i(27, 30)],
+        &[(32, 32)],
+        &[Body::default()],
+        &maps,
+        &mut contacts,
+        &mut events,
+    );
+    assert_eq!(contacts.len(), 1);
+    assert_eq!(contacts[0].normal, Vec2i(0, 1));
+    assert_eq!(contacts[0].depth, 2);
+    contacts.clear();
+    gather_contacts(
+        &[Vec2i(29, 20)],
+        &[(32, 32)],
+        &[Body::default()],
+        &maps,
+        &mut contacts,
+        &mut events,
+    );
+    assert_eq!(contacts[0].normal, Vec2i(1, 0));
+    assert_eq!(contacts[0].depth, 3);
+}
+
+#[test]
+fn walls_and_ceilings_are_told_apart() {
+    let maps = vec![map_at(Vec2i(0, 0), &["####", "...#", "...."])];
+    // Jumping up into the ceiling
+    let (pos, events) = collide(&maps, Vec2i(10, 28), (32, 32), Vec2i(0, -6));
+    assert_eq!(pos, Vec2i(10, 32));
+    assert!(events
+        .iter()
+        .all(|e| matches!(e, CollisionEvent::HitCeiling { body: 0, .. })));
+    // Running right into the wall
+    let (pos, events) = collide(&maps, Vec2i(70, 40), (32, 16), Vec2i(6, 0));
+    assert_eq!(pos, Vec2i(64, 40));
+    assert_eq!(
+        events,
+        vec![CollisionEvent::HitWall {
+            body: 0,
+            tile: WALL,
+            normal: Vec2i(-1, 0)
+        }]
+    );
+}
+
+#[test]
+fn triggers_fire_for_overlapping_bodies() {
+    let triggers = [
+        Rect {
+            x: 0,
+            y: 0,
+            w: 32,
+            h: 32,
+        },
+        Rect {
+            x: 100,
+            y: 0,
+            w: 32,
+            h: 32,
+        },
+    ];
+    let mut events = vec![];
+    detect_triggers(
+        &[Vec2i(80, 10), Vec2i(300, 0)],
+        &[(32, 32), (32, 32)],
+        &triggers,
+        &mut events,
+    );
+    assert_eq!(
+        events,
+        vec![CollisionEvent::EnteredTrigger {
+            body: 0,
+            trigger: 1
+        }]
+    );
+}
+
+#[test]
+fn hazards_only_count_when_overlapped() {
+    let tiles = special_tileset();
+    let maps = vec![Tilemap::new(
+        Vec2i(0, 0),
+        (4, 2),
+        &tiles,
+        vec![0, 0, 0, 0, 1, 3, 3, 1],
+    )];
+    let hazard = |events: &[CollisionEvent]| {
+        events
+            .iter()
+            .any(|e| matches!(e, CollisionEvent::TouchedHazard { body: 0, .. }))
+    };
+    // Dipping a toe into the lava anywhere along the bottom edge
+    let (_, events) = collide(&maps, Vec2i(70, 8), (40, 32), Vec2i(0, 0));
+    assert!(hazard(&events));
+    // Hovering just above it
+    let (_, events) = collide(&maps, Vec2i(70, 0), (40, 32), Vec2i(0, 0));
+    assert!(!hazard(&events));
+}
+
+// Runs a set of bodies through contact gathering and restitution with no tiles around.
+// Returns where they ended up and how fast they're going.
+fn push_apart(
+    positions: &[Vec2i],
+    velocities: &[Vec2i],
+    bodies: &[Body],
+) -> (Vec<Vec2i>, Vec<Vec2i>) {
+    let mut positions = positions.to_vec();
+    let mut velocities = velocities.to_vec();
+    let sizes = vec![(32, 32); positions.len()];
+    let mut contacts = vec![];
+    let mut events = vec![];
+    gather_contacts(
+        &positions,
+        &sizes,
+        bodies,
+        &vec![],
+        &mut contacts,
+        &mut events,
+    );
+    restitute(
+        &mut positions,
+        &sizes,
+        bodies,
+        &mut velocities,
+        &vec![],
+        &mut contacts,
+        &mut events,
+    );
+    assert!(events.is_empty());
+    (positions, velocities)
+}
+
+fn with_mass(mass: f32) -> Body {
+    Body {
+        kind: BodyKind::Dynamic { mass },
+        ..Body::default()
+    }
+}
+
+#[test]
+fn equal_bodies_split_the_overlap() {
+    // Walking into each other, 10px deep
+    let (pos, vel) = push_apart(
+        &[Vec2i(0, 0), Vec2i(22, 0)],
+        &[Vec2i(3, 1), Vec2i(-3, 1)],
+        &[Body::default(), Body::default()],
+    );
+    assert_eq!(pos, vec![Vec2i(-5, 0), Vec2i(27, 0)]);
+    // Only the movement into each other stops
+    assert_eq!(vel, vec![Vec2i(0, 1), Vec2i(0, 1)]);
+}
+
+#[test]
+fn lighter_bodies_get_pushed_further() {
+    // 12px deep vertically; the body on top weighs twice as much
+    let (pos, _) = push_apart(
+        &[Vec2i(0, 0), Vec2i(4, 20)],
+        &[Vec2i(0, 0), Vec2i(0, 0)],
+        &[with_mass(2.0), with_mass(1.0)],
+    );
+    assert_eq!(pos, vec![Vec2i(0, -4), Vec2i(4, 28)]);
+}
+
+#[test]
+fn kinematic_bodies_are_never_pushed() {
+    let kinematic = Body {
+        kind: BodyKind::Kinematic,
+        ..Body::default()
+    };
+    let (pos, vel) = push_apart(
+        &[Vec2i(0, 0), Vec2i(20, 0)],
+        &[Vec2i(2, 0), Vec2i(-4, 0)],
+        &[kinematic, with_mass(100.0)],
+    );
+    assert_eq!(pos, vec![Vec2i(0, 0), Vec2i(32, 0)]);
+    assert_eq!(vel, vec![Vec2i(2, 0), Vec2i(0, 0)]);
+    // Two kinematic bodies just overlap
+    let (pos, _) = push_apart(
+        &[Vec2i(0, 0), Vec2i(20, 0)],
+        &[Vec2i(0, 0), Vec2i(0, 0)],
+        &[kinematic, kinematic],
+    );
+    assert_eq!(pos, vec![Vec2i(0, 0), Vec2i(20, 0)]);
+}
+
+#[test]
+fn masked_out_layers_pass_through_each_other() {
+    let player = Body {
+        layers: LAYER_PLAYER,
+        mask: LAYER_ALL & !LAYER_ENEMY,
+        ..Body::default()
+    };
+    let enemy = Body {
+        layers: LAYER_ENEMY,
+        mask: LAYER_ALL,
+        ..Body::default()
+    };
+    let overlapping = [Vec2i(0, 0), Vec2i(16, 0)];
+    let still = [Vec2i(0, 0), Vec2i(0, 0)];
+    // The player ignores enemies, so the pair is ignored even though the enemy's mask takes players
+    let (pos, _) = push_apart(&overlapping, &still, &[player, enemy]);
+    assert_eq!(pos, overlapping.to_vec());
+    // Enemies still bump into each other
+    let (pos, _) = push_apart(&overlapping, &still, &[enemy, enemy]);
+    assert_eq!(pos, vec![Vec2i(-8, 0), Vec2i(24, 0)]);
+    assert!(player.collides_with(&Body::default()));
+    assert!(!enemy.collides_with(&player));
+}
+
+#[test]
+fn tiles_win_over_bodies() {
+    // A body shoved towards a wall by a heavier one ends up against the wall, not in it
+    let maps = vec![map_at(Vec2i(0, 0), &["#....", "#...."])];
+    let mut positions = [Vec2i(34, 0), Vec2i(60, 0)];
+    let mut velocities = [Vec2i(0, 0), Vec2i(-4, 0)];
+    let sizes = [(32, 32), (32, 32)];
+    let bodies = [with_mass(1.0), with_mass(1000.0)];
+    let mut contacts = vec![];
+    let mut events = vec![];
+    gather_contacts(
+        &positions,
+        &sizes,
+        &bodies,
+        &maps,
+        &mut contacts,
+        &mut events,
+    );
+    restitute(
+        &mut positions,
+        &sizes,
+        &bodies,
+        &mut velocities,
+        &maps,
+        &mut contacts,
+        &mut events,
+    );
+    assert_eq!(positions[0], Vec2i(32, 0));
+    assert!(events.contains(&CollisionEvent::HitWall {
+        body: 0,
+        tile: WALL,
+        normal: Vec2i(1, 0)
+    }));
+}
+
+// A tile of a given shape, solid unless it's a ladder
+fn shaped(shape: TileShape) -> Tile {
+    Tile {
+        solid: shape != TileShape::Ladder,
+        shape,
+        ..EMPTY
+    }
+}
+
+// Builds a one-row-of-tiles-per-string map like map_at, with shapes given by the characters:
+// '#' full, '_' bottom half, '^' top half, '-' one-way, '/' and '\\' 45° slopes,
+// 'a' 'b' a 22.5° slope rising to the right, and 'H' a ladder
+fn shaped_map(rows: &[&str]) -> Vec<Tilemap> {
+    let kinds = [
+        ('.', EMPTY),
+        ('#', WALL),
+        ('_', shaped(TileShape::HalfBottom)),
+        ('^', shaped(TileShape::HalfTop)),
+        ('-', shaped(TileShape::OneWay)),
+        ('/', shaped(TileShape::Slope { left: 0, right: 32 })),
+        ('\\', shaped(TileShape::Slope { left: 32, right: 0 })),
+        ('a', shaped(TileShape::Slope { left: 0, right: 16 })),
+        (
+            'b',
+            shaped(TileShape::Slope {
+                left: 16,
+                right: 32,
+            }),
+        ),
+        ('H', shaped(TileShape::Ladder)),
+    ];
+    let tex = Rc::new(Texture::new(RgbaImage::new(32 * kinds.len() as u32, 32)));
+    let tiles = Rc::new(Tileset::new(kinds.iter().map(|(_, t)| *t).collect(), &tex));
+    let ids = rows
+        .iter()
+        .flat_map(|r| r.chars())
+        .map(|c| kinds.iter().position(|(k, _)| *k == c).unwrap())
+        .collect();
+    vec![Tilemap::new(
+        Vec2i(0, 0),
+        (rows[0].len(), rows.len()),
+        &tiles,
+        ids,
+    )]
+}
+
+fn landed(events: &[CollisionEvent]) -> bool {
+    events
+        .iter()
+        .any(|e| matches!(e, CollisionEvent::Landed { body: 0, .. }))
+}
+
+#[test]
+fn one_way_platforms_hold_bodies_up_but_let_them_jump_through() {
+    let maps = shaped_map(&["....", ".--.", "...."]);
+    // Falling onto it
+    let (pos, events) = collide(&maps, Vec2i(40, 6), (32, 32), Vec2i(0, 6));
+    assert_eq!(pos, Vec2i(40, 0));
+    assert!(landed(&events));
+    // Jumping up through it
+    let (pos, events) = collide(&maps, Vec2i(40, 6), (32, 32), Vec2i(0, -6));
+    assert_eq!(pos, Vec2i(40, 6));
+    assert!(events.is_empty());
+    // Well past the top already, on the way up from underneath
+    let (pos, _) = collide(&maps, Vec2i(40, 30), (32, 32), Vec2i(0, 6));
+    assert_eq!(pos, Vec2i(40, 30));
+    // Walking sideways into it doesn't bump
+    let (pos, _) = collide(&maps, Vec2i(10, 40), (32, 16), Vec2i(4, 0));
+    assert_eq!(pos, Vec2i(10, 40));
+}
+
+#[test]
+fn sweeps_fall_onto_one_way_platforms_but_rise_through_them() {
+    let maps = shaped_map(&["....", ".--.", "....", "...."]);
+    let swept = sweep_tiles(Vec2i(40, -40), (32, 32), Vec2i(0, 50), &maps);
+    assert_eq!(swept.pos, Vec2i(40, 0));
+    assert!(swept.blocked_y());
+    let swept = sweep_tiles(Vec2i(40, 64), (32, 32), Vec2i(0, -50), &maps);
+    assert_eq!(swept.pos, Vec2i(40, 14));
+    assert!(swept.hits.is_empty());
+    let swept = sweep_tiles(Vec2i(0, 40), (32, 16), Vec2i(100, 0), &maps);
+    assert_eq!(swept.pos, Vec2i(100, 40));
+}
+
+#[test]
+fn half_tiles_block_only_their_half() {
+    let maps = shaped_map(&["......", "._..^.", "......"]);
+    // Standing on the bottom half tile sits 16px lower than on a full one
+    let (pos, events) = collide(&maps, Vec2i(32, 20), (32, 32), Vec2i(0, 4));
+    assert_eq!(pos, Vec2i(32, 16));
+    assert!(landed(&events));
+    // The top half tile can be walked under
+    let (pos, events) = collide(&maps, Vec2i(128, 50), (32, 14), Vec2i(2, 0));
+    assert_eq!(pos, Vec2i(128, 50));
+    assert!(events.is_empty());
+    // And sweeps see the same halves
+    let swept = sweep_tiles(Vec2i(32, -20), (32, 32), Vec2i(0, 40), &maps);
+    assert_eq!(swept.pos, Vec2i(32, 16));
+}
+
+#[test]
+fn bodies_stand_on_slopes_at_the_highest_point_under_them() {
+    let maps = shaped_map(&["....", "./\\.", "####"]);
+    // Half on the rising slope: its right edge is 16px into the tile, where the floor is 16px up
+    let (pos, events) = collide(&maps, Vec2i(16, 20), (32, 32), Vec2i(4, 4));
+    assert_eq!(pos, Vec2i(16, 64 - 16 - 32));
+    assert!(landed(&events));
+    // Right on the peak between the two slopes
+    let (pos, _) = collide(&maps, Vec2i(48, 4), (32, 32), Vec2i(0, 4));
+    assert_eq!(pos, Vec2i(48, 0));
+    // Coming down the far side, with the left edge 6px into the tile where the floor is 26px up
+    let (pos, _) = collide(&maps, Vec2i(70, 10), (32, 32), Vec2i(2, 2));
+    assert_eq!(pos, Vec2i(70, 64 - 26 - 32));
+}
+
+#[test]
+fn shallow_slopes_span_two_tiles() {
+    let maps = shaped_map(&[".....", ".ab..", "#####"]);
+    // Its right edge at 8px into the lower half of the slope, where the floor is 4px up
+    let (pos, _) = collide(&maps, Vec2i(8, 31), (32, 32), Vec2i(0, 4));
+    assert_eq!(pos, Vec2i(8, 64 - 4 - 32));
+    // A narrow body's right edge 24px into the upper half: 16 + 12 = 28px up
+    let (pos, _) = collide(&maps, Vec2i(72, 8), (16, 32), Vec2i(0, 4));
+    assert_eq!(pos, Vec2i(72, 64 - 28 - 32));
+}
+
+#[test]
+fn slopes_are_walls_from_their_tall_side() {
+    // Nothing to the right of the rising slope's tall edge
+    let maps = shaped_map(&["....", "./..", "####"]);
+    let (pos, events) = collide(&maps, Vec2i(60, 32), (32, 32), Vec2i(-4, 0));
+    assert_eq!(pos, Vec2i(64, 32));
+    assert!(events.iter().any(|e| matches!(
+        e,
+        CollisionEvent::HitWall {
+            normal: Vec2i(1, 0),
+            ..
+        }
+    )));
+}
+
+#[test]
+fn ladders_are_climbable_and_never_block() {
+    let maps = shaped_map(&[".H..", ".H..", "####"]);
+    let (pos, events) = collide(&maps, Vec2i(40, 20), (32, 32), Vec2i(0, 3));
+    assert_eq!(pos, Vec2i(40, 20));
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, CollisionEvent::OnLadder { body: 0, .. })));
+    // Just beside it doesn't count
+    let (_, events) = collide(&maps, Vec2i(64, 20), (32, 32), Vec2i(0, 0));
+    assert!(events.is_empty());
+}
+
+#[test]
+fn game2s_side_map_has_bridges_to_jump_through_and_water_to_climb() {
+    // Like game2's side tileset: 0 is water, 8 stone and 9 a bridge
+    let mut tiles = vec![EMPTY; 16];
+    tiles[0] = shaped(TileShape::Ladder);
+    tiles[8] = WALL;
+    tiles[9] = shaped(TileShape::OneWay);
+    let tex = Rc::new(Texture::new(RgbaImage::new(128, 128)));
+    let tileset = Rc::new(Tileset::new(tiles, &tex));
+    let path = std::path::Path::new("content/game2_side.map");
+    let maps = vec![Tilemap::with_file(path, Vec2i(0, 0), &tileset).unwrap()];
+    assert_eq!(maps[0].size(), (130, 54));
+
+    // One of the stepping stones over the lava, next to where the player starts
+    let bridge = Vec2i(13 * 32, 29 * 32);
+    assert_eq!(maps[0].tile_at(bridge).unwrap().shape, TileShape::OneWay);
+    let (pos, events) = collide(&maps, bridge + Vec2i(0, -26), (32, 32), Vec2i(0, 6));
+    assert_eq!(pos, bridge + Vec2i(0, -32));
+    assert!(landed(&events));
+    let (pos, events) = collide(&maps, bridge + Vec2i(0, 6), (32, 32), Vec2i(0, -6));
+    assert_eq!(pos, bridge + Vec2i(0, 6));
+    assert!(events.is_empty());
+
+    let (_, events) = collide(&maps, Vec2i(19 * 32, 8 * 32), (32, 32), Vec2i(0, 0));
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, CollisionEvent::OnLadder { body: 0, .. })));
+}
+
+#[test]
+fn map_files_that_dont_make_sense_are_errors() {
+    let dir = std::env::temp_dir().join("game2dengine_map_test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir(&dir).unwrap();
+    let path = dir.join("test.map");
+    let tileset = tileset();
+    let load = |text: &str| {
+        std::fs::write(&path, text).unwrap();
+        Tilemap::with_file(&path, Vec2i(32, 0), &tileset)
+    };
+
+    let map = load("0, 1, 0,\n1, 1, 1,\n\n").unwrap();
+    assert_eq!(map.size(), (3, 2));
+    assert_eq!(map.tile_at(Vec2i(0, 0)), None);
+    assert_eq!(map.tile_at(Vec2i(32, 0)), Some(EMPTY));
+    assert_eq!(map.tile_at(Vec2i(64, 0)), Some(WALL));
+    assert_eq!(map.tile_at(Vec2i(32, 32)), Some(WALL));
+    for bad in ["0, 1\n1\n", "0, one\n", "0, 2\n", ""].iter() {
+        assert!(matches!(load(bad), Err(EngineError::DecodeFailed { .. })));
+    }
+    assert!(matches!(
+        Tilemap::with_file(&dir.join("nothing.map"), Vec2i(0, 0), &tileset),
+        Err(EngineError::NotFound(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn the_broad_phase_finds_every_touching_pair() {
+    let mut rng = StdRng::seed_from_u64(39);
+    for _ in 0..50 {
+        let n = rng.gen_range(0..60);
+        let positions: Vec<Vec2i> = (0..n)
+            .map(|_| Vec2i(rng.gen_range(-300..300), rng.gen_range(-300..300)))
+            .collect();
+        let sizes: Vec<(usize, usize)> = (0..n)
+            .map(|_| (rng.gen_range(1..100), rng.gen_range(1..100)))
+            .collect();
+        let candidates = broad_phase(&positions, &sizes);
+        let rect = |i: usize| Rect::from_pos_size(positions[i], sizes[i]);
+        for a in 0..n {
+            for b in a + 1..n {
+                if rect_displacement(rect(a), rect(b)).is_some() {
+                    assert!(
+                        candidates.contains(&(a, b)),
+                        "missed {:?} and {:?}",
+                        rect(a),
+                        rect(b)
+                    );
+                }
+            }
+        }
+        // Each pair only once, in order
+        assert!(candidates.windows(2).all(|w| w[0] < w[1]));
+        assert!(candidates.iter().all(|(a, b)| a < b));
+    }
+}
+
+#[test]
+fn spatial_hashes_can_be_reused() {
+    let mut grid = SpatialHash::new(64);
+    let r = Rect {
+        x: 0,
+        y: 0,
+        w: 32,
+        h: 32,
+    };
+    grid.insert(0, r);
+    grid.insert(1, r.translate(Vec2i(32, 0)));
+    grid.insert(2, r.translate(Vec2i(500, 500)));
+    assert_eq!(grid.pairs(), vec![(0, 1)]);
+    grid.clear();
+    grid.insert(2, r);
+    grid.insert(3, r.translate(Vec2i(-20, -20)));
+    assert_eq!(grid.pairs(), vec![(2, 3)]);
+}
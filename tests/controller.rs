@@ -0,0 +1,193 @@
+use Game2DEngine::controller::*;
+use Game2DEngine::types::*;
+
+fn held(up: bool, down: bool) -> PlatformerInput {
+    PlatformerInput {
+        up,
+        down,
+        ..PlatformerInput::default()
+    }
+}
+
+#[test]
+fn ladders_are_climbed_without_gravity() {
+    let mut c = PlatformerController::default();
+    let mut jumps = 1;
+    c.set_on_ladder(true);
+    // Just being in front of a ladder doesn't grab it
+    assert!(c.step(held(false, false), &mut jumps).1 > 0.0);
+    assert!(!c.climbing());
+    let speed = c.config.climb_speed;
+    assert_eq!(c.step(held(true, false), &mut jumps), Vec2f(0.0, -speed));
+    assert!(c.climbing());
+    assert_eq!(jumps, 0);
+    // Letting go of up hangs on
+    assert_eq!(c.step(held(false, false), &mut jumps), Vec2f(0.0, 0.0));
+    assert_eq!(c.step(held(false, true), &mut jumps), Vec2f(0.0, speed));
+}
+
+#[test]
+fn climbing_ends_at_the_top_of_the_ladder_or_with_a_jump() {
+    let mut c = PlatformerController::default();
+    let mut jumps = 0;
+    c.set_on_ladder(true);
+    c.step(held(true, false), &mut jumps);
+    let jump = PlatformerInput {
+        jump_pressed: true,
+        jump_held: true,
+        ..PlatformerInput::default()
+    };
+    let vel = c.step(jump, &mut jumps);
+    assert!(!c.climbing());
+    assert_eq!(vel.1, -c.config.jump_speed + c.config.gravity);
+    assert_eq!(jumps, 1);
+
+    c.set_on_ladder(true);
+    c.step(held(true, false), &mut jumps);
+    c.set_on_ladder(false);
+    assert!(!c.climbing());
+    // Falling again
+    assert!(c.step(held(true, false), &mut jumps).1 > -c.config.climb_speed);
+}
+
+const NOTHING: PlatformerInput = PlatformerInput {
+    left: false,
+    right: false,
+    jump_pressed: false,
+    jump_held: false,
+    up: false,
+    down: false,
+};
+const PRESS: PlatformerInput = PlatformerInput {
+    jump_pressed: true,
+    jump_held: true,
+    ..NOTHING
+};
+const HOLD: PlatformerInput = PlatformerInput {
+    jump_held: true,
+    ..NOTHING
+};
+
+// A controller moving over flat ground at y = 0, or over a pit if there's no floor.
+// Landing resets jumps, like the jump_reset tiles do in the games.
+struct Sim {
+    c: PlatformerController,
+    pos: Vec2f,
+    jumps: usize,
+}
+
+impl Sim {
+    // Only one jump, so a jump in midair shows up as no jump at all
+    fn at(height: f32, buffer: usize) -> Self {
+        Sim {
+            c: PlatformerController::new(PlatformerConfig {
+                max_jumps: 1,
+                jump_buffer_frames: buffer,
+                ..PlatformerConfig::default()
+            }),
+            pos: Vec2f(0.0, -height),
+            jumps: 0,
+        }
+    }
+    fn standing() -> Self {
+        let mut sim = Self::at(0.0, PlatformerConfig::default().jump_buffer_frames);
+        sim.frame(NOTHING, true);
+        assert!(sim.c.grounded());
+        sim
+    }
+    fn frame(&mut self, input: PlatformerInput, floor: bool) -> Vec2f {
+        let vel = self.c.step(input, &mut self.jumps);
+        let wanted = self.pos + vel;
+        let mut actual = wanted;
+        if floor && actual.1 > 0.0 {
+            actual.1 = 0.0;
+        }
+        self.c.resolve(wanted, actual);
+        if self.c.grounded() {
+            self.jumps = 0;
+        }
+        self.pos = actual;
+        vel
+    }
+    // Jumps, holding it for held frames, and returns how high we got
+    fn jump(&mut self, held: usize) -> f32 {
+        self.frame(PRESS, true);
+        let mut peak = self.pos.1;
+        for i in 0..100 {
+            self.frame(if i < held { HOLD } else { NOTHING }, true);
+            peak = peak.min(self.pos.1);
+        }
+        assert!(self.c.grounded());
+        -peak
+    }
+}
+
+#[test]
+fn jumps_work_just_after_walking_off_a_ledge() {
+    let mut sim = Sim::standing();
+    for _ in 0..3 {
+        sim.frame(NOTHING, false);
+    }
+    assert!(!sim.c.grounded());
+    let vel = sim.frame(PRESS, false);
+    assert_eq!(vel.1, -sim.c.config.jump_speed + sim.c.config.gravity);
+
+    // But not once the coyote time is up
+    let mut sim = Sim::standing();
+    for _ in 0..sim.c.config.coyote_frames + 1 {
+        sim.frame(NOTHING, false);
+    }
+    assert!(sim.frame(PRESS, false).1 > 0.0);
+}
+
+#[test]
+fn jumps_pressed_just_before_landing_happen_on_landing() {
+    let mut sim = Sim::at(4.0, 6);
+    // Too early to jump, since we're in the air with no jumps left
+    assert!(sim.frame(PRESS, true).1 > 0.0);
+    sim.frame(HOLD, true);
+    sim.frame(HOLD, true);
+    assert!(sim.c.grounded());
+    // Nothing pressed this frame, but the jump from before goes off now
+    assert!(sim.frame(HOLD, true).1 < 0.0);
+
+    // Without the buffer the early press is lost
+    let mut sim = Sim::at(4.0, 1);
+    sim.frame(PRESS, true);
+    sim.frame(HOLD, true);
+    sim.frame(HOLD, true);
+    assert!(sim.c.grounded());
+    assert!(sim.frame(HOLD, true).1 > 0.0);
+}
+
+#[test]
+fn letting_go_of_jump_early_jumps_lower() {
+    let full = Sim::standing().jump(100);
+    let short = Sim::standing().jump(2);
+    let tap = Sim::standing().jump(0);
+    assert!(full > short && short > tap && tap > 0.0);
+    // Held all the way, we rise until gravity takes all our speed
+    let c = PlatformerConfig::default();
+    let rising = (c.jump_speed / c.gravity) as usize;
+    let speeds = (1..rising).map(|i| c.jump_speed - i as f32 * c.gravity);
+    assert_eq!(full, speeds.sum::<f32>());
+}
+
+#[test]
+fn friction_only_slows_us_on_the_ground() {
+    let run = PlatformerInput {
+        right: true,
+        ..NOTHING
+    };
+    let mut sim = Sim::standing();
+    for _ in 0..20 {
+        sim.frame(run, true);
+    }
+    let c = sim.c.config;
+    assert_eq!(sim.c.velocity().0, c.max_speed);
+    assert_eq!(sim.frame(NOTHING, true).0, c.max_speed - c.friction);
+    // Sliding off into the air keeps whatever speed we had
+    let speed = sim.frame(NOTHING, false).0;
+    assert_eq!(sim.frame(NOTHING, false).0, speed);
+    assert!(speed > 0.0);
+}
@@ -0,0 +1,120 @@
+use Game2DEngine::ecs::*;
+
+#[derive(Debug, PartialEq)]
+struct Pos(i32);
+
+#[derive(Debug, PartialEq)]
+struct Vel(i32);
+
+#[derive(Debug, PartialEq)]
+struct Name(&'static str);
+
+#[test]
+fn despawned_slots_are_reused_with_a_new_generation() {
+    let mut world = World::new();
+    let a = world.spawn();
+    let b = world.spawn();
+    assert_eq!(world.len(), 2);
+    assert!(world.despawn(a));
+    assert!(!world.despawn(a));
+    assert!(!world.is_alive(a));
+    assert_eq!(world.len(), 1);
+
+    let c = world.spawn();
+    assert_eq!(c.index(), a.index());
+    assert_ne!(c, a);
+    assert!(world.is_alive(c));
+    assert!(world.is_alive(b));
+    assert_eq!(world.entities().collect::<Vec<_>>(), vec![c, b]);
+    // Only fresh slots are added once the free ones are used up
+    assert_eq!(world.spawn().index(), 2);
+}
+
+#[test]
+fn stale_handles_find_nothing() {
+    let mut world = World::new();
+    let old = world.spawn();
+    world.insert(old, Pos(1));
+    world.despawn(old);
+    let new = world.spawn();
+    world.insert(new, Pos(2));
+
+    assert_eq!(world.get::<Pos>(old), None);
+    assert_eq!(world.get_mut::<Pos>(old), None);
+    assert_eq!(world.remove::<Pos>(old), None);
+    assert!(!world.has::<Pos>(old));
+    // The new entity in the slot keeps its component
+    assert_eq!(world.get::<Pos>(new), Some(&Pos(2)));
+    assert_eq!(world.remove::<Pos>(new), Some(Pos(2)));
+    assert_eq!(world.get::<Pos>(new), None);
+}
+
+#[test]
+fn despawning_drops_components_of_every_type() {
+    let mut world = World::new();
+    let e = world.spawn();
+    world.insert(e, Pos(1));
+    world.insert(e, Vel(2));
+    world.insert(e, Name("e"));
+    world.despawn(e);
+    // Even through a handle to whatever gets the slot next
+    let next = world.spawn();
+    assert_eq!(next.index(), e.index());
+    assert!(!world.has::<Pos>(next));
+    assert!(!world.has::<Vel>(next));
+    assert!(!world.has::<Name>(next));
+    assert_eq!(world.query::<Pos>().count(), 0);
+    assert_eq!(world.query::<Name>().count(), 0);
+}
+
+#[test]
+fn pair_queries_only_see_entities_with_both() {
+    let mut world = World::new();
+    let both = world.spawn();
+    world.insert(both, Pos(1));
+    world.insert(both, Vel(10));
+    let only_pos = world.spawn();
+    world.insert(only_pos, Pos(2));
+    let only_vel = world.spawn();
+    world.insert(only_vel, Vel(20));
+    let both_too = world.spawn();
+    world.insert(both_too, Vel(30));
+    world.insert(both_too, Pos(3));
+
+    let found: Vec<_> = world.query2::<Pos, Vel>().map(|(e, _, _)| e).collect();
+    assert_eq!(found, vec![both, both_too]);
+
+    let mut visited = vec![];
+    world.query2_mut::<Pos, Vel>(|e, pos, vel| {
+        pos.0 += vel.0;
+        vel.0 = 0;
+        visited.push(e);
+    });
+    assert_eq!(visited, vec![both, both_too]);
+    assert_eq!(world.get::<Pos>(both), Some(&Pos(11)));
+    assert_eq!(world.get::<Pos>(both_too), Some(&Pos(33)));
+    assert_eq!(world.get::<Pos>(only_pos), Some(&Pos(2)));
+    assert_eq!(world.get::<Vel>(only_vel), Some(&Vel(20)));
+
+    // Nobody has a Name, so nothing comes back, and the Vels are still there afterwards
+    let mut called = false;
+    world.query2_mut::<Vel, Name>(|_, _, _| called = true);
+    world.query2_mut::<Name, Vel>(|_, _, _| called = true);
+    assert!(!called);
+    assert_eq!(world.query::<Vel>().count(), 3);
+}
+
+#[test]
+fn systems_run_over_the_world() {
+    let mut world = World::new();
+    let e = world.spawn();
+    world.insert(e, Pos(0));
+    let mut step = |world: &mut World| {
+        for (_, pos) in world.query_mut::<Pos>() {
+            pos.0 += 1;
+        }
+    };
+    world.run(&mut step);
+    world.run(&mut step);
+    assert_eq!(world.get::<Pos>(e), Some(&Pos(2)));
+}
@@ -0,0 +1,195 @@
+// Property tests for the vector and rect helpers, over lots of seeded random inputs
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use Game2DEngine::types::*;
+
+const CASES: usize = 2000;
+
+fn rng() -> StdRng {
+    StdRng::seed_from_u64(181)
+}
+
+fn vec2i(rng: &mut StdRng) -> Vec2i {
+    Vec2i(rng.gen_range(-1000..1000), rng.gen_range(-1000..1000))
+}
+
+fn rect(rng: &mut StdRng) -> Rect {
+    Rect {
+        x: rng.gen_range(-200..200),
+        y: rng.gen_range(-200..200),
+        w: rng.gen_range(0..200),
+        h: rng.gen_range(0..200),
+    }
+}
+
+// Every pixel of a small rect, for checking set-like properties exhaustively
+fn pixels(r: Rect) -> impl Iterator<Item = Vec2i> {
+    (r.y..r.bottom()).flat_map(move |y| (r.x..r.right()).map(move |x| Vec2i(x, y)))
+}
+
+#[test]
+fn vector_ops_are_componentwise() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let (a, b) = (vec2i(&mut rng), vec2i(&mut rng));
+        let k = rng.gen_range(-10..10);
+        assert_eq!(a + b, Vec2i(a.0 + b.0, a.1 + b.1));
+        assert_eq!(a - b, Vec2i(a.0 - b.0, a.1 - b.1));
+        assert_eq!(a * k, Vec2i(a.0 * k, a.1 * k));
+        assert_eq!(-a, Vec2i(-a.0, -a.1));
+        assert_eq!(a + b, b + a);
+        assert_eq!(a - b, a + -b);
+        assert_eq!((a + b) - b, a);
+        let mut c = a;
+        c += b;
+        c -= b;
+        assert_eq!(c, a);
+    }
+}
+
+// Quarter pixels add and scale exactly, so float results can be compared with ==
+fn vec2f(rng: &mut StdRng) -> Vec2f {
+    let quarters = |rng: &mut StdRng| rng.gen_range(-4000..4000) as f32 / 4.0;
+    Vec2f(quarters(rng), quarters(rng))
+}
+
+#[test]
+fn float_vector_ops_are_componentwise() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let (a, b) = (vec2f(&mut rng), vec2f(&mut rng));
+        let k = rng.gen_range(-8..8) as f32 / 2.0;
+        assert_eq!(a + b, Vec2f(a.0 + b.0, a.1 + b.1));
+        assert_eq!(a - b, Vec2f(a.0 - b.0, a.1 - b.1));
+        assert_eq!(a * k, Vec2f(a.0 * k, a.1 * k));
+        assert_eq!(a / 4.0, Vec2f(a.0 / 4.0, a.1 / 4.0));
+        assert_eq!(-a, Vec2f(-a.0, -a.1));
+        assert_eq!(a + b, b + a);
+        assert_eq!(a - b, a + -b);
+        assert_eq!((a + b) - b, a);
+        assert_eq!(a.dot(b), a.0 * b.0 + a.1 * b.1);
+        let mut c = a;
+        c += b;
+        c -= b;
+        assert_eq!(c, a);
+    }
+    assert_eq!(Vec2f(3.0, -4.0).length(), 5.0);
+}
+
+#[test]
+fn float_vectors_snap_to_the_nearest_pixel() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let v = vec2i(&mut rng);
+        let jitter = Vec2f(rng.gen_range(-0.49..0.49), rng.gen_range(-0.49..0.49));
+        assert_eq!((Vec2f::from(v) + jitter).snap(), v);
+        assert_eq!(Vec2f::from(v) * 2.0, Vec2f::from(v * 2));
+    }
+}
+
+#[test]
+fn from_pos_size_round_trips() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let r = rect(&mut rng);
+        assert_eq!(Rect::from_pos_size(r.pos(), r.size()), r);
+        assert_eq!(r.right() - r.x, r.w as i32);
+        assert_eq!(r.bottom() - r.y, r.h as i32);
+    }
+}
+
+#[test]
+fn contains_is_half_open() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let r = rect(&mut rng);
+        let p = Vec2i(rng.gen_range(-250..450), rng.gen_range(-250..450));
+        let inside = r.x <= p.0 && p.0 < r.right() && r.y <= p.1 && p.1 < r.bottom();
+        assert_eq!(r.contains(p), inside);
+        assert!(!r.contains(Vec2i(r.right(), r.y)));
+        assert!(!r.contains(Vec2i(r.x, r.bottom())));
+        assert_eq!(r.contains(r.pos()), !r.is_empty());
+    }
+}
+
+#[test]
+fn intersection_matches_intersects_and_is_symmetric() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let (a, b) = (rect(&mut rng), rect(&mut rng));
+        assert_eq!(a.intersects(b), b.intersects(a));
+        assert_eq!(a.intersection(b), b.intersection(a));
+        assert_eq!(a.intersects(b), a.intersection(b).is_some());
+        if let Some(i) = a.intersection(b) {
+            assert!(!i.is_empty());
+            assert_eq!(a.union(i), a);
+            assert_eq!(b.union(i), b);
+        }
+    }
+}
+
+#[test]
+fn intersection_holds_exactly_the_shared_pixels() {
+    let mut rng = rng();
+    for _ in 0..200 {
+        let small = |rng: &mut StdRng| Rect {
+            x: rng.gen_range(-8..8),
+            y: rng.gen_range(-8..8),
+            w: rng.gen_range(0..10),
+            h: rng.gen_range(0..10),
+        };
+        let (a, b) = (small(&mut rng), small(&mut rng));
+        let shared: Vec<Vec2i> = pixels(a).filter(|p| b.contains(*p)).collect();
+        match a.intersection(b) {
+            Some(i) => assert_eq!(pixels(i).collect::<Vec<_>>(), shared),
+            None => assert!(shared.is_empty()),
+        }
+    }
+}
+
+#[test]
+fn union_covers_both() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let (a, b) = (rect(&mut rng), rect(&mut rng));
+        let u = a.union(b);
+        assert_eq!(u, b.union(a));
+        assert_eq!(u.union(a), u);
+        assert!(u.x <= a.x && u.y <= a.y && a.right() <= u.right() && a.bottom() <= u.bottom());
+        assert!(u.x <= b.x && u.y <= b.y && b.right() <= u.right() && b.bottom() <= u.bottom());
+    }
+}
+
+#[test]
+fn expand_keeps_the_center() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let r = rect(&mut rng);
+        let by = rng.gen_range(0..50);
+        let grown = r.expand(by);
+        assert_eq!(grown.w as i32, r.w as i32 + 2 * by);
+        assert_eq!(grown.h as i32, r.h as i32 + 2 * by);
+        assert_eq!(grown.center(), r.center());
+        assert_eq!(grown.expand(-by), r);
+        assert_eq!(grown.union(r), grown);
+        // Shrinking never goes below nothing
+        let shrunk = r.expand(-by);
+        assert!(shrunk.w <= r.w && shrunk.h <= r.h);
+        if !shrunk.is_empty() {
+            assert_eq!(r.intersection(shrunk), Some(shrunk));
+        }
+    }
+}
+
+#[test]
+fn translate_moves_contained_points_along() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let r = rect(&mut rng);
+        let by = vec2i(&mut rng);
+        let p = Vec2i(rng.gen_range(-250..450), rng.gen_range(-250..450));
+        assert_eq!(r.translate(by).contains(p + by), r.contains(p));
+        assert_eq!(r.translate(by).center(), r.center() + by);
+    }
+}
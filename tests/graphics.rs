@@ -0,0 +1,130 @@
+use image::{Rgba as Pixel, RgbaImage};
+
+use Game2DEngine::graphics::Screen;
+use Game2DEngine::texture::Texture;
+use Game2DEngine::types::*;
+
+const RED: Rgba = Rgba(255, 0, 0, 255);
+
+// The red channel of each pixel, row by row
+fn reds(fb: &[u8], width: usize) -> Vec<Vec<u8>> {
+    fb.chunks_exact(4)
+        .map(|p| p[0])
+        .collect::<Vec<_>>()
+        .chunks(width)
+        .map(|row| row.to_vec())
+        .collect()
+}
+
+fn rect(x: i32, y: i32, w: u16, h: u16) -> Rect {
+    Rect { x, y, w, h }
+}
+
+#[test]
+fn viewports_clip_drawing_to_their_rect() {
+    let mut fb = vec![0; 6 * 4 * 4];
+    let mut screen = Screen::wrap(&mut fb, 6, 4, 4, Vec2i(0, 0));
+    {
+        let mut view = screen.viewport(rect(2, 1, 3, 2), Vec2i(0, 0));
+        assert_eq!(view.size(), (3, 2));
+        view.clear(RED);
+    }
+    assert_eq!(
+        reds(&fb, 6),
+        vec![
+            vec![0, 0, 0, 0, 0, 0],
+            vec![0, 0, 255, 255, 255, 0],
+            vec![0, 0, 255, 255, 255, 0],
+            vec![0, 0, 0, 0, 0, 0],
+        ]
+    );
+
+    let mut fb = vec![0; 6 * 4 * 4];
+    let mut screen = Screen::wrap(&mut fb, 6, 4, 4, Vec2i(0, 0));
+    {
+        let mut view = screen.viewport(rect(2, 1, 3, 2), Vec2i(0, 0));
+        view.rect(rect(-5, -5, 20, 20), RED);
+        view.line(Vec2i(-10, 0), Vec2i(10, 0), Rgba(7, 0, 0, 255));
+    }
+    assert_eq!(reds(&fb, 6)[0], vec![0; 6]);
+    assert_eq!(reds(&fb, 6)[1], vec![0, 0, 7, 7, 7, 0]);
+    assert_eq!(reds(&fb, 6)[2], vec![0, 0, 255, 255, 255, 0]);
+}
+
+#[test]
+fn viewports_scroll_on_their_own() {
+    let mut fb = vec![0; 4 * 4 * 4];
+    let mut screen = Screen::wrap(&mut fb, 4, 4, 4, Vec2i(100, 100));
+    {
+        // World pixel (10, 20) lands at the viewport's top-left corner
+        let mut view = screen.viewport(rect(2, 2, 2, 2), Vec2i(10, 20));
+        view.draw_at(RED, Vec2i(10, 20));
+        view.draw_at(RED, Vec2i(100, 100));
+    }
+    assert_eq!(screen.bounds(), rect(100, 100, 4, 4));
+    assert_eq!(
+        reds(&fb, 4),
+        vec![vec![0; 4], vec![0; 4], vec![0, 0, 255, 0], vec![0; 4]]
+    );
+}
+
+#[test]
+fn viewports_nest_and_stay_inside_the_screen() {
+    let mut fb = vec![0; 4 * 4 * 4];
+    let mut screen = Screen::wrap(&mut fb, 4, 4, 4, Vec2i(0, 0));
+    {
+        // Hanging off the bottom right, so only one pixel of it is left
+        let mut corner = screen.viewport(rect(2, 2, 10, 10), Vec2i(0, 0));
+        assert_eq!(corner.size(), (2, 2));
+        let mut inner = corner.viewport(rect(1, 1, 5, 5), Vec2i(0, 0));
+        assert_eq!(inner.size(), (1, 1));
+        inner.clear(RED);
+    }
+    assert_eq!(reds(&fb, 4)[3], vec![0, 0, 0, 255]);
+    assert_eq!(fb.iter().step_by(4).filter(|r| **r != 0).count(), 1);
+
+    let mut fb = vec![0; 4 * 4 * 4];
+    let mut screen = Screen::wrap(&mut fb, 4, 4, 4, Vec2i(0, 0));
+    let view = screen.viewport(rect(10, 10, 2, 2), Vec2i(0, 0));
+    assert_eq!(view.size(), (0, 0));
+}
+
+#[test]
+fn blitting_into_a_bottom_right_viewport() {
+    let tex = Texture::new(RgbaImage::from_pixel(3, 3, Pixel([9, 0, 0, 255])));
+    let mut fb = vec![0; 5 * 5 * 4];
+    let mut screen = Screen::wrap(&mut fb, 5, 5, 4, Vec2i(0, 0));
+    {
+        // The viewport's last row is the framebuffer's last row, so nothing can spill past it
+        let mut view = screen.viewport(rect(3, 3, 2, 2), Vec2i(0, 0));
+        view.bitblt(&tex, rect(0, 0, 3, 3), Vec2i(-1, 0));
+        view.upscale(2);
+    }
+    let reds = reds(&fb, 5);
+    assert_eq!(reds[2], vec![0; 5]);
+    assert_eq!(reds[3], vec![0, 0, 0, 9, 9]);
+    assert_eq!(reds[4], vec![0, 0, 0, 9, 9]);
+    assert!(reds.iter().all(|row| row[2] == 0));
+}
+
+#[test]
+fn fading_all_the_way_in_is_plain_bitblt() {
+    let tex = Texture::new(RgbaImage::from_pixel(2, 2, Pixel([200, 40, 0, 128])));
+    let grey = vec![60; 2 * 2 * 4];
+    let blit = |alpha: Option<u8>| {
+        let mut fb = grey.clone();
+        let mut screen = Screen::wrap(&mut fb, 2, 2, 4, Vec2i(0, 0));
+        match alpha {
+            Some(alpha) => screen.bitblt_faded(&tex, rect(0, 0, 2, 2), Vec2i(0, 0), alpha),
+            None => screen.bitblt(&tex, rect(0, 0, 2, 2), Vec2i(0, 0)),
+        }
+        fb
+    };
+    let plain = blit(None);
+    assert_ne!(plain, grey);
+    assert_eq!(blit(Some(255)), plain);
+    assert_eq!(blit(Some(0)), grey);
+    // Half faded lands in between
+    let half = blit(Some(128));
+    assert!(grey[0] < half[0] && half[0] < plain[0]);
+}
@@ -0,0 +1,118 @@
+use std::rc::Rc;
+
+use image::{Rgba as Pixel, RgbaImage};
+
+use Game2DEngine::graphics::Screen;
+use Game2DEngine::minimap::Minimap;
+use Game2DEngine::texture::Texture;
+use Game2DEngine::tiles::*;
+use Game2DEngine::types::*;
+
+const GROUND: Tile = Tile {
+    solid: false,
+    jump_reset: false,
+    restart: false,
+    shape: TileShape::Full,
+};
+
+// Tile 0 is a red and blue checkerboard, tile 1 is green, and tile 2 has no picture at all
+fn tileset() -> Tileset {
+    let img = RgbaImage::from_fn(64, 32, |x, y| {
+        if x >= 32 {
+            Pixel([0, 200, 0, 255])
+        } else if (x + y) % 2 == 0 {
+            Pixel([200, 0, 0, 255])
+        } else {
+            Pixel([0, 0, 100, 255])
+        }
+    });
+    Tileset::new(vec![GROUND; 3], &Rc::new(Texture::new(img)))
+}
+
+// A 4x2 map at the origin, green on the left half and checkered on the right
+fn map(tileset: Tileset) -> Tilemap {
+    Tilemap::new(
+        Vec2i(0, 0),
+        (4, 2),
+        &Rc::new(tileset),
+        vec![1, 1, 0, 2, 1, 1, 0, 2],
+    )
+}
+
+fn rect(x: i32, y: i32, w: u16, h: u16) -> Rect {
+    Rect { x, y, w, h }
+}
+
+fn pixel(fb: &[u8], width: usize, x: usize, y: usize) -> Rgba {
+    let p = &fb[(y * width + x) * 4..(y * width + x + 1) * 4];
+    Rgba(p[0], p[1], p[2], p[3])
+}
+
+#[test]
+fn tiles_are_colored_by_their_average_pixel_unless_told_otherwise() {
+    let mut tiles = tileset();
+    let colors: Vec<Rgba> = map(tileset())
+        .colors_in(rect(0, 0, 128, 32))
+        .map(|(c, _)| c)
+        .collect();
+    assert_eq!(
+        colors,
+        vec![
+            Rgba(0, 200, 0, 255),
+            Rgba(0, 200, 0, 255),
+            Rgba(100, 0, 50, 255),
+            // Off the edge of the texture
+            Rgba(0, 0, 0, 0),
+        ]
+    );
+    tiles.set_color(2, Rgba(1, 2, 3, 255));
+    let (c, bounds) = map(tiles).colors_in(rect(100, 40, 1, 1)).next().unwrap();
+    assert_eq!(c, Rgba(1, 2, 3, 255));
+    assert_eq!(bounds, rect(96, 32, 32, 32));
+}
+
+#[test]
+fn exploring_remembers_what_was_seen() {
+    let maps = vec![map(tileset())];
+    let mut minimap = Minimap::new(2);
+    assert!(!minimap.explored(Vec2i(10, 10)));
+    minimap.explore(&maps, rect(0, 0, 40, 10));
+    assert!(minimap.explored(Vec2i(10, 10)));
+    assert!(minimap.explored(Vec2i(63, 31)));
+    assert!(!minimap.explored(Vec2i(64, 0)));
+    assert!(!minimap.explored(Vec2i(10, 40)));
+    // Moving on doesn't forget, and there's nothing to see off the map
+    minimap.explore(&maps, rect(-500, 32, 600, 32));
+    assert!(minimap.explored(Vec2i(10, 10)));
+    assert!(minimap.explored(Vec2i(90, 40)));
+    assert!(!minimap.explored(Vec2i(-40, 40)));
+}
+
+#[test]
+fn drawing_shows_explored_tiles_around_the_center_and_markers_on_top() {
+    let maps = vec![map(tileset())];
+    let mut minimap = Minimap::new(2);
+    minimap.explore(&maps, rect(0, 0, 64, 32));
+    let mut fb = vec![0; 8 * 8 * 4];
+    let mut screen = Screen::wrap(&mut fb, 8, 8, 4, Vec2i(0, 0));
+    // The top-left corner of the map lands in the middle
+    minimap.draw(
+        &mut screen,
+        Vec2i(0, 0),
+        &[(Vec2i(48, 48), Rgba(9, 9, 9, 255))],
+    );
+    let green = Rgba(0, 200, 0, 255);
+    for x in 4..8 {
+        assert_eq!(pixel(&fb, 8, x, 4), green);
+        assert_eq!(pixel(&fb, 8, x, 5), green);
+    }
+    // The second row hasn't been seen
+    assert_eq!(pixel(&fb, 8, 4, 6), minimap.fog);
+    assert_eq!(pixel(&fb, 8, 5, 7), minimap.fog);
+    assert_eq!(pixel(&fb, 8, 3, 4), minimap.fog);
+    assert_eq!(pixel(&fb, 8, 4, 3), minimap.fog);
+    // Markers are at least three pixels across, wherever they are
+    assert_eq!(pixel(&fb, 8, 6, 6), Rgba(9, 9, 9, 255));
+    assert_eq!(pixel(&fb, 8, 7, 7), Rgba(9, 9, 9, 255));
+    assert_eq!(pixel(&fb, 8, 5, 5), green);
+}
@@ -0,0 +1,178 @@
+use std::rc::Rc;
+
+use image::{Rgba as Pixel, RgbaImage};
+
+use Game2DEngine::animation::Animation;
+use Game2DEngine::graphics::Screen;
+use Game2DEngine::particles::*;
+use Game2DEngine::resources::Handle;
+use Game2DEngine::texture::Texture;
+use Game2DEngine::types::*;
+
+// Particles that all head straight right at one pixel a frame and live for ten frames
+fn steady() -> Rc<ParticleStyle> {
+    Rc::new(ParticleStyle {
+        lifetime: (10, 10),
+        spread: 0.0,
+        ..ParticleStyle::default()
+    })
+}
+
+fn red(fb: &[u8], width: usize, x: usize, y: usize) -> u8 {
+    fb[(y * width + x) * 4]
+}
+
+#[test]
+fn bursts_live_out_their_lifetime() {
+    let mut particles = Particles::new(100);
+    let style = steady();
+    particles.burst(Vec2f(0.0, 0.0), 5, &style);
+    assert_eq!(particles.len(), 5);
+    for _ in 0..9 {
+        particles.update();
+    }
+    assert_eq!(particles.len(), 5);
+    particles.update();
+    assert!(particles.is_empty());
+}
+
+#[test]
+fn the_pool_never_grows() {
+    let mut particles = Particles::new(8);
+    let style = steady();
+    particles.burst(Vec2f(0.0, 0.0), 5, &style);
+    particles.burst(Vec2f(0.0, 0.0), 5, &style);
+    assert_eq!(particles.len(), 8);
+    particles.emit(Emitter::continuous(Vec2f(0.0, 0.0), &style, 3.0));
+    for _ in 0..50 {
+        particles.update();
+        assert!(particles.len() <= particles.capacity());
+    }
+    assert_eq!(particles.capacity(), 8);
+    // Once the old ones go, there's room again
+    particles.clear();
+    particles.burst(Vec2f(0.0, 0.0), 3, &style);
+    assert_eq!(particles.len(), 3);
+}
+
+#[test]
+fn emitters_let_out_fractions_over_time_and_stop_when_done() {
+    let mut particles = Particles::new(100);
+    let style = Rc::new(ParticleStyle {
+        lifetime: (1000, 1000),
+        ..ParticleStyle::default()
+    });
+    particles.emit(Emitter::lasting(Vec2f(0.0, 0.0), &style, 0.25, 8));
+    for _ in 0..4 {
+        particles.update();
+    }
+    assert_eq!(particles.len(), 1);
+    for _ in 0..20 {
+        particles.update();
+    }
+    assert_eq!(particles.len(), 2);
+}
+
+#[test]
+fn particles_move_and_fall() {
+    let style = Rc::new(ParticleStyle {
+        spread: 0.0,
+        speed: (2.0, 2.0),
+        gravity: 1.0,
+        start_color: Rgba(255, 0, 0, 255),
+        end_color: Rgba(255, 0, 0, 255),
+        size: 1,
+        ..ParticleStyle::default()
+    });
+    let mut particles = Particles::new(1);
+    particles.burst(Vec2f(1.0, 0.0), 1, &style);
+    // Down 1, then 2 more
+    particles.update();
+    particles.update();
+    let mut fb = vec![0; 8 * 8 * 4];
+    let mut screen = Screen::wrap(&mut fb, 8, 8, 4, Vec2i(0, 0));
+    particles.draw(&mut screen);
+    assert_eq!(red(&fb, 8, 5, 3), 255);
+    assert_eq!(fb.chunks_exact(4).filter(|p| p[0] != 0).count(), 1);
+}
+
+#[test]
+fn colors_fade_over_a_particles_life() {
+    let style = Rc::new(ParticleStyle {
+        lifetime: (4, 4),
+        speed: (0.0, 0.0),
+        start_color: Rgba(200, 0, 0, 255),
+        end_color: Rgba(0, 0, 0, 255),
+        size: 1,
+        ..ParticleStyle::default()
+    });
+    let draw = |particles: &Particles| {
+        let mut fb = vec![0; 4];
+        let mut screen = Screen::wrap(&mut fb, 1, 1, 4, Vec2i(0, 0));
+        particles.draw(&mut screen);
+        fb[0]
+    };
+    let mut particles = Particles::new(1);
+    particles.burst(Vec2f(0.0, 0.0), 1, &style);
+    assert_eq!(draw(&particles), 200);
+    particles.update();
+    assert_eq!(draw(&particles), 150);
+    particles.update();
+    particles.update();
+    assert_eq!(draw(&particles), 50);
+
+    // See-through colors are mixed with what's underneath
+    let mut fb = vec![0, 0, 200, 255];
+    let mut screen = Screen::wrap(&mut fb, 1, 1, 4, Vec2i(0, 0));
+    screen.blend_rect(
+        Rect {
+            x: 0,
+            y: 0,
+            w: 1,
+            h: 1,
+        },
+        Rgba(200, 0, 0, 128),
+    );
+    assert_eq!(fb, vec![100, 0, 100, 255]);
+}
+
+#[test]
+fn textured_particles_play_their_frames_and_fade() {
+    // Two 2x2 frames side by side, red then green
+    let img = RgbaImage::from_fn(4, 2, |x, _| {
+        if x < 2 {
+            Pixel([200, 0, 0, 255])
+        } else {
+            Pixel([0, 200, 0, 255])
+        }
+    });
+    let frame = |x| Rect {
+        x,
+        y: 0,
+        w: 2,
+        h: 2,
+    };
+    let anim = Rc::new(Animation::new(vec![(frame(0), 1), (frame(2), 2)], true));
+    let style = Rc::new(ParticleStyle {
+        lifetime: (4, 4),
+        speed: (0.0, 0.0),
+        start_color: Rgba(255, 255, 255, 255),
+        end_color: Rgba(255, 255, 255, 0),
+        sprite: Some((Handle::new("spark", Texture::new(img)), anim)),
+        ..ParticleStyle::default()
+    });
+    let mut particles = Particles::new(1);
+    particles.burst(Vec2f(1.0, 1.0), 1, &style);
+    let mut fb = vec![0; 2 * 2 * 4];
+    let mut screen = Screen::wrap(&mut fb, 2, 2, 4, Vec2i(0, 0));
+    particles.draw(&mut screen);
+    assert_eq!(&fb[0..4], &[200, 0, 0, 255]);
+
+    particles.update();
+    particles.update();
+    let mut fb = vec![0; 2 * 2 * 4];
+    let mut screen = Screen::wrap(&mut fb, 2, 2, 4, Vec2i(0, 0));
+    particles.draw(&mut screen);
+    // Halfway through its life, half faded
+    assert_eq!(&fb[0..4], &[0, 100, 0, 128]);
+}
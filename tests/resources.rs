@@ -0,0 +1,309 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+use image::{Rgba as Pixel, RgbaImage};
+
+use Game2DEngine::animation::Animation;
+use Game2DEngine::audio::Sound;
+use Game2DEngine::error::EngineError;
+use Game2DEngine::resources::*;
+use Game2DEngine::texture::Texture;
+use Game2DEngine::tiles::*;
+use Game2DEngine::types::*;
+
+fn still(x: i32) -> Animation {
+    Animation::freeze(Rect {
+        x,
+        y: 0,
+        w: 8,
+        h: 8,
+    })
+}
+
+#[test]
+fn loading_a_file_twice_shares_one_asset() {
+    let rsrc = Resources::new();
+    let a = rsrc.load_texture("content/tilesheet.png").unwrap();
+    let b = rsrc.load_texture("content/tilesheet.png").unwrap();
+    assert!(Handle::ptr_eq(&a, &b));
+    assert!(Rc::ptr_eq(&a.get(), &b.get()));
+    let other = rsrc.load_texture("content/wiry_all_side.png").unwrap();
+    assert!(!Handle::ptr_eq(&a, &other));
+    assert_eq!(rsrc.loaded::<Texture>(), 2);
+    // Each kind of asset has its own cache
+    assert_eq!(rsrc.loaded::<Sound>(), 0);
+    rsrc.load_sound("content/jump.wav").unwrap();
+    rsrc.load_font("content/helvetica.ttf").unwrap();
+    assert_eq!(rsrc.loaded::<Sound>(), 1);
+    assert_eq!(a.key(), Path::new("content/tilesheet.png"));
+}
+
+#[test]
+fn assets_made_in_code_are_found_by_key() {
+    let rsrc = Resources::new();
+    assert!(rsrc.get::<Animation>("walk").is_none());
+    let walk = rsrc.add("walk", still(8));
+    let found = rsrc.get::<Animation>("walk").unwrap();
+    assert!(Handle::ptr_eq(&walk, &found));
+    assert_eq!(found.get().start().frame().x, 8);
+    // Tilesets go in their own cache, so the same key doesn't clash
+    let tex = rsrc.load_texture("content/tilesheet.png").unwrap().get();
+    rsrc.add("walk", Tileset::new(vec![], &tex));
+    let found = rsrc.get::<Animation>("walk").unwrap();
+    assert!(Handle::ptr_eq(&found, &walk));
+    // Adding over an asset replaces it, but old handles keep working
+    rsrc.add("walk", still(16));
+    let replaced = rsrc.get::<Animation>("walk").unwrap();
+    assert_eq!(replaced.get().start().frame().x, 16);
+    assert_eq!(walk.get().start().frame().x, 8);
+}
+
+#[test]
+fn handles_and_rcs_count_as_users() {
+    let rsrc = Resources::new();
+    let walk = rsrc.add("walk", still(0));
+    assert_eq!(rsrc.users::<Animation>("walk"), 1);
+    let again = walk.clone();
+    let anim = again.get();
+    assert_eq!(rsrc.users::<Animation>("walk"), 3);
+    drop(walk);
+    drop(again);
+    assert_eq!(rsrc.users::<Animation>("walk"), 1);
+    drop(anim);
+    assert_eq!(rsrc.users::<Animation>("walk"), 0);
+    assert_eq!(rsrc.users::<Animation>("run"), 0);
+}
+
+#[test]
+fn unloading_lets_go_of_assets() {
+    let rsrc = Resources::new();
+    let kept = rsrc.load_texture("content/tilesheet.png").unwrap();
+    rsrc.load_texture("content/wiry_all_side.png").unwrap();
+    rsrc.add("idle", still(0));
+    assert_eq!(rsrc.unload_unused(), 2);
+    assert_eq!(rsrc.loaded::<Texture>(), 1);
+    assert_eq!(rsrc.loaded::<Animation>(), 0);
+
+    // Unloading on purpose forgets it even while it's used; the next load reads it again
+    assert!(rsrc.unload::<Texture>("content/tilesheet.png"));
+    assert!(!rsrc.unload::<Texture>("content/tilesheet.png"));
+    assert_eq!(kept.get().size(), (128, 128));
+    let reloaded = rsrc.load_texture("content/tilesheet.png").unwrap();
+    assert!(!Handle::ptr_eq(&kept, &reloaded));
+}
+
+// A file in the temp directory holding some bytes, for loaders to choke on
+fn junk(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+fn strict() -> Resources {
+    let mut rsrc = Resources::new();
+    rsrc.placeholders = false;
+    rsrc
+}
+
+#[test]
+fn loaders_say_what_went_wrong() {
+    let rsrc = strict();
+    match rsrc.load_texture("content/nothing.png") {
+        Err(EngineError::NotFound(path)) => assert_eq!(path, Path::new("content/nothing.png")),
+        _ => panic!("a missing texture should be NotFound"),
+    }
+    assert!(matches!(
+        rsrc.load_texture("content/jump.wav"),
+        Err(EngineError::UnsupportedFormat(_))
+    ));
+    let broken = junk("game2dengine_broken.png", b"not really a png");
+    assert!(matches!(
+        rsrc.load_texture(&broken),
+        Err(EngineError::DecodeFailed { .. })
+    ));
+    assert!(matches!(
+        rsrc.load_sound(&broken),
+        Err(EngineError::DecodeFailed { .. })
+    ));
+    assert!(matches!(
+        rsrc.load_font(&broken),
+        Err(EngineError::DecodeFailed { .. })
+    ));
+    assert!(matches!(
+        rsrc.load_sound("content/nothing.wav"),
+        Err(EngineError::NotFound(_))
+    ));
+    std::fs::remove_file(&broken).unwrap();
+    // Nothing that failed was kept
+    assert_eq!(rsrc.loaded::<Texture>(), 0);
+    assert_eq!(rsrc.loaded::<Sound>(), 0);
+}
+
+#[test]
+fn tilesheets_have_to_be_whole_tiles() {
+    let rsrc = strict();
+    assert!(rsrc.load_tilesheet("content/tilesheet.png").is_ok());
+    let err = rsrc
+        .load_tilesheet("content/badland_background.png")
+        .err()
+        .unwrap();
+    match &err {
+        EngineError::WrongDimensions { size, cell, .. } => {
+            assert_eq!(*size, (810, 481));
+            assert_eq!(*cell, (32, 32));
+        }
+        _ => panic!("expected WrongDimensions, got {}", err),
+    }
+    assert_eq!(err.path(), Path::new("content/badland_background.png"));
+}
+
+#[test]
+fn placeholders_stand_in_for_textures_that_dont_load() {
+    let mut rsrc = Resources::new();
+    rsrc.placeholders = true;
+    let missing = rsrc.load_texture("content/nothing.png").unwrap();
+    let tex = missing.get();
+    assert_eq!(tex.size(), (32, 32));
+    // Magenta and black squares
+    assert_eq!(&tex.buffer()[0..4], &[255, 0, 255, 255]);
+    let next_square = 8 * tex.depth();
+    assert_eq!(&tex.buffer()[next_square..next_square + 4], &[0, 0, 0, 255]);
+    // It's kept under the path it was meant to be, so it's only complained about once
+    let again = rsrc.load_texture("content/nothing.png").unwrap();
+    assert!(Handle::ptr_eq(&missing, &again));
+    // Sounds and fonts don't get placeholders
+    assert!(rsrc.load_sound("content/nothing.wav").is_err());
+}
+
+// Saves a 32x32 picture of one color, and makes sure it looks newer than the last time
+fn paint(path: &Path, color: [u8; 4], age: u64) {
+    RgbaImage::from_pixel(32, 32, Pixel(color))
+        .save(path)
+        .unwrap();
+    let when = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 + age);
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(when)
+        .unwrap();
+}
+
+#[test]
+fn changed_files_are_swapped_in_behind_the_same_handles() {
+    let path = std::env::temp_dir().join("game2dengine_reload_test.png");
+    paint(&path, [200, 0, 0, 255], 0);
+    let rsrc = strict();
+    let handle = rsrc.load_texture(&path).unwrap();
+    let tiles = Tileset::new(vec![], &handle);
+    assert_eq!(tiles.texture().get().buffer()[0], 200);
+    // Nothing's changed yet
+    assert!(rsrc.reload_changed().is_empty());
+
+    paint(&path, [0, 200, 0, 255], 1);
+    assert_eq!(rsrc.reload_changed(), vec![path.clone()]);
+    assert_eq!(&handle.get().buffer()[0..4], &[0, 200, 0, 255]);
+    // Things made from the handle see it too
+    assert_eq!(tiles.texture().get().buffer()[1], 200);
+    assert!(rsrc.reload_changed().is_empty());
+
+    // A save that's broken halfway keeps the last good version
+    std::fs::write(&path, b"half a png").unwrap();
+    File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_002))
+        .unwrap();
+    assert!(rsrc.reload_changed().is_empty());
+    assert_eq!(handle.get().buffer()[1], 200);
+    std::fs::remove_file(&path).unwrap();
+    assert!(rsrc.reload_changed().is_empty());
+}
+
+#[test]
+fn placeholders_are_replaced_once_the_file_turns_up() {
+    let path = std::env::temp_dir().join("game2dengine_late_test.png");
+    let _ = std::fs::remove_file(&path);
+    let mut rsrc = Resources::new();
+    rsrc.placeholders = true;
+    let handle = rsrc.load_texture(&path).unwrap();
+    assert_eq!(handle.get().size(), (32, 32));
+    assert_eq!(handle.get().buffer()[0], 255);
+    paint(&path, [0, 0, 90, 255], 0);
+    assert_eq!(rsrc.reload_changed(), vec![path.clone()]);
+    assert_eq!(&handle.get().buffer()[0..4], &[0, 0, 90, 255]);
+    std::fs::remove_file(&path).unwrap();
+
+    // Assets made in code have nothing to reload from, even under a file's name
+    rsrc.add("content/tilesheet.png", Texture::missing());
+    assert!(rsrc.reload_changed().is_empty());
+}
+
+// Writes a map file, and makes sure it looks newer than the last time
+fn draw_map(path: &Path, rows: &str, age: u64) {
+    std::fs::write(path, rows).unwrap();
+    let when = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 + age);
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(when)
+        .unwrap();
+}
+
+#[test]
+fn maps_and_tileset_colors_follow_their_files() {
+    let tile = Tile {
+        solid: true,
+        jump_reset: false,
+        restart: false,
+        shape: TileShape::Full,
+    };
+    let texture = std::env::temp_dir().join("game2dengine_reload_tiles_test.png");
+    let grid = std::env::temp_dir().join("game2dengine_reload_test.map");
+    paint(&texture, [200, 0, 0, 255], 0);
+    draw_map(&grid, "0,0\n", 0);
+    let rsrc = strict();
+    let sheet = rsrc.load_texture(&texture).unwrap();
+    let tiles = Rc::new(Tileset::new(vec![tile], &sheet));
+    let map = Tilemap::with_grid(Vec2i(0, 0), rsrc.load_map(&grid).unwrap(), &tiles).unwrap();
+    // Loading it again for another level shares the grid
+    let again = Tilemap::with_grid(Vec2i(0, 0), rsrc.load_map(&grid).unwrap(), &tiles).unwrap();
+    let everything = Rect {
+        x: 0,
+        y: 0,
+        w: 100,
+        h: 100,
+    };
+    assert_eq!(map.size(), (2, 1));
+    let (color, _) = map.colors_in(everything).next().unwrap();
+    assert_eq!(color, Rgba(200, 0, 0, 255));
+
+    paint(&texture, [0, 0, 90, 255], 1);
+    draw_map(&grid, "0,0,0\n0,0,0\n", 1);
+    assert_eq!(rsrc.reload_changed().len(), 2);
+    assert_eq!(map.size(), (3, 2));
+    assert_eq!(again.size(), (3, 2));
+    assert!(map.tile_at(Vec2i(70, 40)).is_some());
+    // The minimap's colors are worked out again from the new picture
+    let (color, _) = map.colors_in(everything).next().unwrap();
+    assert_eq!(color, Rgba(0, 0, 90, 255));
+
+    // Tiles the tileset doesn't have are left empty rather than taking the game down
+    draw_map(&grid, "0,7\n", 2);
+    assert_eq!(rsrc.reload_changed(), vec![grid.clone()]);
+    assert!(map.tile_at(Vec2i(0, 0)).is_some());
+    assert!(map.tile_at(Vec2i(40, 0)).is_none());
+    assert_eq!(map.tiles_in(everything).count(), 1);
+    assert_eq!(map.colors_in(everything).count(), 1);
+    // But they're still an error when the map's first loaded
+    assert!(matches!(
+        Tilemap::with_grid(Vec2i(0, 0), rsrc.load_map(&grid).unwrap(), &tiles),
+        Err(EngineError::DecodeFailed { .. })
+    ));
+    std::fs::remove_file(&texture).unwrap();
+    std::fs::remove_file(&grid).unwrap();
+}
@@ -0,0 +1,64 @@
+use Game2DEngine::error::EngineError;
+use Game2DEngine::save::*;
+use Game2DEngine::types::*;
+
+#[test]
+fn saves_load_back_and_bad_ones_are_errors() {
+    let path = std::env::temp_dir().join("game2dengine_save_test.json");
+    let mut player = Player::new();
+    player.pos = Vec2f(12.0, 34.0);
+    player.world = 1;
+    save(&player, &path).unwrap();
+    assert!(load(&path).unwrap() == player);
+
+    std::fs::write(&path, "{\"id\": 3, \"pos\": ").unwrap();
+    assert!(matches!(load(&path), Err(EngineError::DecodeFailed { .. })));
+    // A broken save isn't mistaken for no save at all
+    assert!(load_or_new(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(matches!(load(&path), Err(EngineError::NotFound(_))));
+    assert!(load_or_new(&path).unwrap() == Player::new());
+
+    let nowhere = std::env::temp_dir()
+        .join("game2dengine_no_such_dir")
+        .join("save.json");
+    assert!(matches!(
+        save(&player, &nowhere),
+        Err(EngineError::WriteFailed { .. })
+    ));
+}
+
+#[test]
+fn positions_are_saved_as_whole_pixels() {
+    let path = std::env::temp_dir().join("game2dengine_pixels_test.json");
+    let mut player = Player::new();
+    player.pos = Vec2f(12.4, -33.6);
+    player.vel = Vec2f(1.5, 0.0);
+    save(&player, &path).unwrap();
+    // Integers, like Vec2i wrote them, so older builds and the server can read them
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(json["pos"], serde_json::json!([12, -34]));
+    assert_eq!(json["vel"], serde_json::json!([2, 0]));
+    let loaded = load(&path).unwrap();
+    assert!(loaded.pos == Vec2f(12.0, -34.0));
+    assert!(loaded.vel == Vec2f(2.0, 0.0));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn old_integer_saves_and_float_ones_both_load() {
+    let path = std::env::temp_dir().join("game2dengine_old_save_test.json");
+    let old_save = r#"{"id": 3, "pos": [64, 96], "vel": [0, -2], "world": 1}"#;
+    std::fs::write(&path, old_save).unwrap();
+    let old = load(&path).unwrap();
+    assert!(old.pos == Vec2f(64.0, 96.0));
+    assert!(old.vel == Vec2f(0.0, -2.0));
+    assert_eq!((old.id, old.world), (3, 1));
+
+    let new_save = r#"{"id": 3, "pos": [64.5, 96.25], "vel": [0, 0], "world": 0}"#;
+    std::fs::write(&path, new_save).unwrap();
+    assert!(load(&path).unwrap().pos == Vec2f(64.5, 96.25));
+    std::fs::remove_file(&path).unwrap();
+}
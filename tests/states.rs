@@ -0,0 +1,477 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use image::RgbaImage;
+use winit_input_helper::WinitInputHelper;
+
+use Game2DEngine::animation::Animation;
+use Game2DEngine::audio::{Audio, Output};
+use Game2DEngine::camera::Camera;
+use Game2DEngine::components::*;
+use Game2DEngine::ecs::World;
+use Game2DEngine::graphics::Screen;
+use Game2DEngine::minimap::Minimap;
+use Game2DEngine::resources::{Handle, Resources};
+use Game2DEngine::server::Server;
+use Game2DEngine::states::*;
+use Game2DEngine::texture::Texture;
+use Game2DEngine::tiles::*;
+use Game2DEngine::transition::{Effect, Transition, Transitioning};
+use Game2DEngine::types::*;
+use Game2DEngine::Control;
+
+type Log = Rc<RefCell<Vec<String>>>;
+
+// What a scripted state returns from update
+#[derive(Clone, Copy, Debug)]
+enum Step {
+    Keep,
+    Remove,
+    Push(&'static str),
+    Swap(&'static str),
+    Replace(&'static str),
+    PushWith(&'static str),
+    SwapWith(&'static str),
+}
+
+// A state that records its updates and hooks into a shared log
+#[derive(Debug)]
+struct Scripted {
+    name: &'static str,
+    step: Step,
+    log: Log,
+}
+
+fn scripted(name: &'static str, step: Step, log: &Log) -> Box<dyn State> {
+    Box::new(Scripted {
+        name,
+        step,
+        log: Rc::clone(log),
+    })
+}
+
+impl Scripted {
+    fn record(&self, what: &str) {
+        self.log
+            .borrow_mut()
+            .push(format!("{} {}", what, self.name));
+    }
+}
+
+impl State for Scripted {
+    fn update(
+        &mut self,
+        _game: &mut GameState,
+        _resources: &Resources,
+        _levels: &Vec<Level>,
+        _frame: usize,
+        _key_input: &WinitInputHelper,
+    ) -> StateResult {
+        self.record("update");
+        let transition = Transition::new(Effect::Crossfade, 2);
+        match self.step {
+            Step::Keep => StateResult::Keep,
+            Step::Remove => StateResult::Remove,
+            Step::Push(n) => StateResult::Push(scripted(n, Step::Keep, &self.log)),
+            Step::Swap(n) => StateResult::Swap(scripted(n, Step::Keep, &self.log)),
+            Step::Replace(n) => StateResult::Replace(scripted(n, Step::Keep, &self.log)),
+            Step::PushWith(n) => {
+                StateResult::PushWith(scripted(n, Step::Keep, &self.log), transition)
+            }
+            Step::SwapWith(n) => {
+                StateResult::SwapWith(scripted(n, Step::Keep, &self.log), transition)
+            }
+        }
+    }
+    fn display(
+        &self,
+        _game: &GameState,
+        _resources: &Resources,
+        _levels: &Vec<Level>,
+        _screen: &mut Screen,
+        _frame: usize,
+    ) {
+    }
+    fn on_enter(&mut self, _game: &mut GameState, _resources: &Resources, _levels: &Vec<Level>) {
+        self.record("enter");
+    }
+    fn on_exit(&mut self, _game: &mut GameState, _resources: &Resources, _levels: &Vec<Level>) {
+        self.record("exit");
+    }
+    fn on_pause(&mut self, _game: &mut GameState, _resources: &Resources, _levels: &Vec<Level>) {
+        self.record("pause");
+    }
+    fn on_resume(&mut self, _game: &mut GameState, _resources: &Resources, _levels: &Vec<Level>) {
+        self.record("resume");
+    }
+}
+
+// A state that swaps itself for another copy of itself forever
+#[derive(Debug)]
+struct Restless(Log);
+
+impl State for Restless {
+    fn update(
+        &mut self,
+        _game: &mut GameState,
+        _resources: &Resources,
+        _levels: &Vec<Level>,
+        _frame: usize,
+        _key_input: &WinitInputHelper,
+    ) -> StateResult {
+        self.0.borrow_mut().push("update restless".to_string());
+        StateResult::Swap(Box::new(Restless(Rc::clone(&self.0))))
+    }
+    fn display(
+        &self,
+        _game: &GameState,
+        _resources: &Resources,
+        _levels: &Vec<Level>,
+        _screen: &mut Screen,
+        _frame: usize,
+    ) {
+    }
+}
+
+// A state that fills the screen with one color
+#[derive(Debug)]
+struct Painted(Rgba);
+
+impl State for Painted {
+    fn update(
+        &mut self,
+        _game: &mut GameState,
+        _resources: &Resources,
+        _levels: &Vec<Level>,
+        _frame: usize,
+        _key_input: &WinitInputHelper,
+    ) -> StateResult {
+        StateResult::Keep
+    }
+    fn display(
+        &self,
+        _game: &GameState,
+        _resources: &Resources,
+        _levels: &Vec<Level>,
+        screen: &mut Screen,
+        _frame: usize,
+    ) {
+        screen.clear(self.0);
+    }
+}
+
+fn game_with(stack: Vec<Box<dyn State>>) -> GameState {
+    let tex = Rc::new(Texture::new(RgbaImage::new(32, 32)));
+    let mut entities = World::new();
+    let player = entities.spawn();
+    GameState {
+        entities,
+        player,
+        server: Server::new(),
+        players: HashMap::new(),
+        enemy_tex: Handle::new("enemy", Texture::new(RgbaImage::new(32, 32))),
+        enemy_anim: Handle::new(
+            "enemy",
+            Animation::freeze(Rect {
+                x: 0,
+                y: 0,
+                w: 32,
+                h: 32,
+            }),
+        ),
+        background_tex: Handle::new("background", Texture::new(RgbaImage::new(32, 32))),
+        level: 0,
+        camera: Camera::new(Vec2i(0, 0), (320, 240)),
+        camera_events: vec![],
+        guest: None,
+        effects: Effects::default(),
+        audio: Audio::new(Output::Null),
+        sounds: Sounds::load(&Resources::new()).unwrap(),
+        ambience: vec![],
+        background_pos: Vec2i(0, 0),
+        state_stack: stack,
+        game_data: GameData {
+            score: 0,
+            speed_multiplier: 1,
+            num_jumps: 0,
+            portals: vec![],
+        },
+        map_x_boundary: 0,
+        map_y_boundary: 0,
+        tt_tileset: Handle::new("tileset", Tileset::new(vec![], &tex)),
+        maps: vec![],
+        minimap: Minimap::new(2),
+        side_map: vec![],
+        game: 1,
+        start_point: Vec2i(0, 0),
+        spawn_point: Vec2i(0, 0),
+        texts_overworld: vec![],
+        texts_sidescroll: vec![],
+    }
+}
+
+// Updates a game that's already going; the first frame enters the starting states too
+fn step_frame(game: &mut GameState, frame: usize) -> Control {
+    process_input(
+        game,
+        &Resources::new(),
+        &vec![],
+        frame,
+        &WinitInputHelper::new(),
+    )
+}
+
+fn step(game: &mut GameState) -> Control {
+    step_frame(game, 1)
+}
+
+fn names(game: &GameState) -> Vec<String> {
+    game.state_stack
+        .iter()
+        .map(|s| format!("{:?}", s))
+        .collect()
+}
+
+fn take(log: &Log) -> Vec<String> {
+    log.borrow_mut().drain(..).collect()
+}
+
+#[test]
+fn the_first_frame_enters_the_starting_states() {
+    let log = Log::default();
+    let mut game = game_with(vec![
+        scripted("a", Step::Keep, &log),
+        scripted("b", Step::Keep, &log),
+    ]);
+    assert_eq!(step_frame(&mut game, 0), Control::Continue);
+    assert_eq!(
+        take(&log),
+        vec!["enter a", "pause a", "enter b", "update b"]
+    );
+    // Just the once
+    assert_eq!(step_frame(&mut game, 1), Control::Continue);
+    assert_eq!(take(&log), vec!["update b"]);
+}
+
+#[test]
+fn keep_updates_only_the_top() {
+    let log = Log::default();
+    let mut game = game_with(vec![
+        scripted("a", Step::Keep, &log),
+        scripted("b", Step::Keep, &log),
+    ]);
+    assert_eq!(step(&mut game), Control::Continue);
+    assert_eq!(take(&log), vec!["update b"]);
+    assert_eq!(game.state_stack.len(), 2);
+}
+
+#[test]
+fn remove_pops_and_updates_the_one_below() {
+    let log = Log::default();
+    let mut game = game_with(vec![
+        scripted("a", Step::Keep, &log),
+        scripted("b", Step::Remove, &log),
+    ]);
+    assert_eq!(step(&mut game), Control::Continue);
+    assert_eq!(
+        take(&log),
+        vec!["update b", "exit b", "resume a", "update a"]
+    );
+    assert_eq!(game.state_stack.len(), 1);
+}
+
+#[test]
+fn removing_the_last_state_quits() {
+    let log = Log::default();
+    let mut game = game_with(vec![scripted("a", Step::Remove, &log)]);
+    assert_eq!(step(&mut game), Control::Quit);
+    assert_eq!(take(&log), vec!["update a", "exit a"]);
+    assert!(game.state_stack.is_empty());
+}
+
+#[test]
+fn empty_stack_quits() {
+    let mut game = game_with(vec![]);
+    assert_eq!(step(&mut game), Control::Quit);
+}
+
+#[test]
+fn push_keeps_the_old_state_underneath() {
+    let log = Log::default();
+    let mut game = game_with(vec![scripted("a", Step::Push("b"), &log)]);
+    assert_eq!(step(&mut game), Control::Continue);
+    assert_eq!(
+        take(&log),
+        vec!["update a", "pause a", "enter b", "update b"]
+    );
+    assert_eq!(game.state_stack.len(), 2);
+    assert!(names(&game)[1].contains("\"b\""));
+}
+
+#[test]
+fn swap_replaces_the_old_state() {
+    let log = Log::default();
+    let mut game = game_with(vec![scripted("a", Step::Swap("b"), &log)]);
+    assert_eq!(step(&mut game), Control::Continue);
+    assert_eq!(
+        take(&log),
+        vec!["update a", "exit a", "enter b", "update b"]
+    );
+    assert_eq!(game.state_stack.len(), 1);
+    assert!(names(&game)[0].contains("\"b\""));
+}
+
+#[test]
+fn replace_skips_the_hooks() {
+    let log = Log::default();
+    let mut game = game_with(vec![scripted("a", Step::Replace("b"), &log)]);
+    assert_eq!(step(&mut game), Control::Continue);
+    assert_eq!(take(&log), vec!["update a", "update b"]);
+    assert_eq!(game.state_stack.len(), 1);
+}
+
+#[test]
+fn swap_with_plays_a_transition_first() {
+    let log = Log::default();
+    let mut game = game_with(vec![scripted("a", Step::SwapWith("b"), &log)]);
+    // The transition takes over the top of the stack...
+    assert_eq!(step(&mut game), Control::Continue);
+    assert_eq!(take(&log), vec!["update a", "exit a", "enter b"]);
+    assert_eq!(game.state_stack.len(), 1);
+    assert!(names(&game)[0].starts_with("Transitioning"));
+    // ...and hands over to the new state once it's finished
+    assert_eq!(step(&mut game), Control::Continue);
+    assert_eq!(take(&log), vec!["update b"]);
+    assert!(names(&game)[0].contains("\"b\""));
+}
+
+#[test]
+fn push_with_plays_a_transition_first() {
+    let log = Log::default();
+    let mut game = game_with(vec![scripted("a", Step::PushWith("b"), &log)]);
+    assert_eq!(step(&mut game), Control::Continue);
+    assert_eq!(take(&log), vec!["update a", "pause a", "enter b"]);
+    assert_eq!(game.state_stack.len(), 2);
+    assert!(names(&game)[1].starts_with("Transitioning"));
+    assert_eq!(step(&mut game), Control::Continue);
+    assert_eq!(take(&log), vec!["update b"]);
+    assert_eq!(game.state_stack.len(), 2);
+    assert!(names(&game)[1].contains("\"b\""));
+}
+
+#[test]
+fn endless_swapping_is_cut_off_each_frame() {
+    let log = Log::default();
+    let mut game = game_with(vec![Box::new(Restless(Rc::clone(&log)))]);
+    assert_eq!(step(&mut game), Control::Continue);
+    assert_eq!(take(&log).len(), MAX_TRANSITIONS_PER_FRAME);
+    assert_eq!(game.state_stack.len(), 1);
+    // The next frame picks up where the last one stopped
+    assert_eq!(step(&mut game), Control::Continue);
+    assert_eq!(take(&log).len(), MAX_TRANSITIONS_PER_FRAME);
+}
+
+const W: usize = 8;
+const H: usize = 4;
+
+// Plays a transition from red to blue on an 8x4 screen, and returns the red and blue
+// of every pixel, row by row, on frame 0, the halfway frame and the last frame
+fn play(effect: Effect, frames: usize) -> Vec<Vec<(u8, u8)>> {
+    let mut game = game_with(vec![]);
+    let rsrc = Resources::new();
+    let mut transition = Transitioning::swap(
+        Box::new(Painted(Rgba(200, 0, 0, 255))),
+        Box::new(Painted(Rgba(0, 0, 100, 255))),
+        Transition::new(effect, frames),
+    );
+    let mut shots = vec![];
+    for frame in 0..frames {
+        if frame == 0 || frame == frames / 2 || frame == frames - 1 {
+            let mut fb = vec![0; W * H * 4];
+            let mut screen = Screen::wrap(&mut fb, W, H, 4, Vec2i(0, 0));
+            transition.display(&game, &rsrc, &vec![], &mut screen, frame);
+            assert!(fb.chunks_exact(4).all(|px| px[3] == 255));
+            shots.push(fb.chunks_exact(4).map(|px| (px[0], px[2])).collect());
+        }
+        transition.update(&mut game, &rsrc, &vec![], frame, &WinitInputHelper::new());
+    }
+    shots
+}
+
+const RED: (u8, u8) = (200, 0);
+const BLUE: (u8, u8) = (0, 100);
+const BLACK: (u8, u8) = (0, 0);
+
+#[test]
+fn fades_go_through_black() {
+    let shots = play(Effect::FadeToBlack, 4);
+    assert_eq!(shots[0], vec![RED; W * H]);
+    assert_eq!(shots[1], vec![BLACK; W * H]);
+    assert_eq!(shots[2], vec![(0, 50); W * H]);
+}
+
+#[test]
+fn crossfades_blend_both_states() {
+    let shots = play(Effect::Crossfade, 4);
+    assert_eq!(shots[0], vec![RED; W * H]);
+    assert_eq!(shots[1], vec![(100, 50); W * H]);
+    assert_eq!(shots[2], vec![(50, 75); W * H]);
+}
+
+#[test]
+fn wipes_uncover_the_new_state_from_the_left() {
+    let shots = play(Effect::Wipe, 4);
+    assert_eq!(shots[0], vec![RED; W * H]);
+    for row in shots[1].chunks(W) {
+        assert_eq!(row, [&[BLUE; 4][..], &[RED; 4]].concat());
+    }
+    for row in shots[2].chunks(W) {
+        assert_eq!(row, [&[BLUE; 6][..], &[RED; 2]].concat());
+    }
+}
+
+#[test]
+fn irises_close_and_open_on_the_middle_without_a_player() {
+    let shots = play(Effect::Iris, 8);
+    assert_eq!(shots[0], vec![RED; W * H]);
+    // Shut all the way but for the center pixel
+    let mut shut = vec![BLACK; W * H];
+    shut[2 * W + 4] = BLUE;
+    assert_eq!(shots[1], shut);
+    // Three quarters open reaches past the corners
+    assert_eq!(shots[2], vec![BLUE; W * H]);
+}
+
+#[test]
+fn the_camera_frames_a_guest_along_with_the_player() {
+    let mut game = game_with(vec![]);
+    let mut me = Player::new();
+    me.pos = Vec2f(100.0, 100.0);
+    game.players.insert(me.id, me);
+    let texture = Handle::new("player", Texture::new(RgbaImage::new(32, 32)));
+    let anim = Rc::new(Animation::freeze(Rect {
+        x: 0,
+        y: 0,
+        w: 32,
+        h: 32,
+    }));
+    game.player = spawn_player(&mut game.entities, &me, &texture, &anim);
+
+    toggle_guest(&mut game);
+    let guest = game.guest.as_ref().unwrap().entity;
+    assert_eq!(game.entities.get::<PlayerId>(guest), Some(&PlayerId(me.id)));
+    let move_guest = |game: &mut GameState, x| {
+        game.entities.get_mut::<Position>(guest).unwrap().0 = Vec2f(x, 120.0);
+        update_camera(game);
+        game.camera.view_rect()
+    };
+    let in_view = |view: Rect, r: Rect| view.union(r) == view;
+    let view = move_guest(&mut game, 250.0);
+    let guest_rect = Rect::from_pos_size(Vec2i(250, 120), (32, 32));
+    assert!(in_view(view, game.player_rect()));
+    assert!(in_view(view, guest_rect));
+    // Too far apart for our half of the screen, so it sticks with us
+    let view = move_guest(&mut game, 2000.0);
+    assert_eq!(view.center(), game.player_rect().center());
+}